@@ -13,7 +13,7 @@ fn main() -> Result<(), String> {
 
     let initial_state = computer.zero_state();
 
-    let result = circuit.execute(&initial_state);
+    let result = circuit.execute(&initial_state)?;
 
     println!("input  : {:?}", initial_state);
     println!("output : {:?}", result.current_state());
@@ -18,8 +18,15 @@ fn circuit1(computer: &QuantumComputer) -> Result<Circuit, String> {
         .build()?;
 
 
+    let bell = QuantumComputer::new(2).new_circuit_builder()
+        .add_operation(Hadamard(0))
+        .add_operation(CNot(1, [0]))
+        .build()?;
+
     computer.new_circuit_builder()
+        .add_definition("bell", bell)
         .add_operation(Toffoli(2, [1, 0]))
+        .add_sub_circuit("bell", &[3, 5])
         .add_loop(circuit, MaxIteration(10))
         .build()
 }
@@ -114,6 +121,11 @@ fn main() -> Result<(), String> {
                         println!("Remove {:?}", id)
                     }
                 }
+                if let HoverData::SubCircuit(id) = h {
+                    if d.is_mouse_button_pressed(crate::consts::MouseButton::MOUSE_LEFT_BUTTON) {
+                        need_layout = circuit.toggle_sub_circuit(*id);
+                    }
+                }
             }
         }
     };
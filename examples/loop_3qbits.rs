@@ -18,7 +18,7 @@ fn main() -> Result<(), String> {
 
     for i in 1..5 {
         let initial_state = computer.zero_state();
-        let result = circuit.execute(&initial_state);
+        let result = circuit.execute(&initial_state)?;
         println!("-- Iteration {} --",i);
         println!("input       : {:?}", initial_state);
         println!("output      : {:?}", result.current_state());
@@ -1,8 +1,12 @@
+use std::collections::VecDeque;
+
 use serde::{Deserialize, Serialize};
 
+use crate::backend::Backend;
 use crate::circuit::Circuit;
 use crate::condition::StopCondition;
-use crate::execution::ExecutionContext;
+use crate::execution::{ExecutionContext, ExecutionError};
+use crate::measure::MeasureRecord;
 use crate::operation::{CircuitElement, QuantumOperation};
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -11,6 +15,9 @@ pub struct Loop {
     pub circuit: Circuit,
     /// the condition used to stop the loop
     pub stop_condition: StopCondition,
+    /// the execution fails instead of starting more iterations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_iterations: Option<u32>,
 }
 
 impl Into<CircuitElement> for Loop {
@@ -19,18 +26,242 @@ impl Into<CircuitElement> for Loop {
     }
 }
 
-impl QuantumOperation for Loop {
-    fn apply(&self, context: &mut ExecutionContext) {
-        let mut i = 0;
-        while !(self.stop_condition.is_end_of_loop(i, &context)) {
-            self.circuit.apply(context);
-            i += 1;
+/// A measurement with a probability above this value is considered deterministic
+const DETERMINISTIC_THRESHOLD: f64 = 1.0 - 1e-9;
+
+/// Longest cycle of deterministic iterations detected by [`LoopGuard`]
+const MAX_CYCLE_PERIOD: usize = 4;
+
+/// Checks done around each iteration of a loop: the limits of the execution
+/// and the detection of the loops that can never end.
+///
+/// A loop can never end when its last iterations are deterministic (all their
+/// measurements have a probability of 1), bring the state back to the one it had
+/// up to [`MAX_CYCLE_PERIOD`] iterations before, and the stop condition cannot be
+/// met by repeating them. Longer cycles are only stopped by the limits.
+pub(crate) struct LoopGuard<B: Backend> {
+    detect_cycles: bool,
+    /// state at the start of the current iteration if the previous one was deterministic
+    start_of_iteration: Option<B>,
+    /// the last deterministic iterations: state at their start and their measurements
+    recent: VecDeque<(B, Vec<MeasureRecord>)>,
+    /// number of the last iterations that repeat forever
+    period: Option<usize>,
+    history_start: usize,
+}
+
+impl<B: Backend> LoopGuard<B> {
+    pub(crate) fn new(the_loop: &Loop) -> Self {
+        let condition = &the_loop.stop_condition;
+        LoopGuard {
+            detect_cycles: !condition.has_predicate() && !condition.ends_by_iteration_count(),
+            start_of_iteration: None,
+            recent: VecDeque::new(),
+            period: None,
+            history_start: 0,
+        }
+    }
+
+    /// Called before starting an iteration, after `nb_iterations` completed ones
+    pub(crate) fn before_iteration(&mut self, the_loop: &Loop, nb_iterations: u32, context: &mut ExecutionContext<B>) -> Result<(), ExecutionError> {
+        if let Some(period) = self.period {
+            let cycle: Vec<(&B, &[MeasureRecord])> = self.recent.range(self.recent.len() - period..)
+                .map(|(state, records)| (state, records.as_slice()))
+                .collect();
+            if the_loop.stop_condition.is_unreachable(nb_iterations, context, &cycle)? {
+                return Err(ExecutionError::UnreachableCondition { path: context.path().clone(), nb_iterations });
+            }
+        }
+        context.check_loop_limits(nb_iterations, the_loop.max_iterations)?;
+        self.history_start = context.history().len();
+        Ok(())
+    }
+
+    /// Called once an iteration is completed
+    pub(crate) fn after_iteration(&mut self, context: &ExecutionContext<B>) {
+        let last_iteration = &context.history()[self.history_start..];
+        let deterministic = self.detect_cycles && last_iteration.iter().all(|r| r.probability >= DETERMINISTIC_THRESHOLD);
+        match (deterministic, self.start_of_iteration.take()) {
+            (true, Some(start)) => {
+                self.recent.push_back((start, last_iteration.to_vec()));
+                if self.recent.len() > MAX_CYCLE_PERIOD {
+                    self.recent.pop_front();
+                }
+            }
+            _ => self.recent.clear(),
         }
+        let current = context.current_state();
+        self.period = (1..=self.recent.len()).find(|period| self.recent[self.recent.len() - period].0.same_state(current));
+        self.start_of_iteration = if deterministic { current.snapshot() } else { None };
+    }
+}
+
+impl QuantumOperation for Loop {
+    /// Fail with [`ExecutionError::UnreachableCondition`] when the loop
+    /// can never end (see [`LoopGuard`]). The loop is exited even when it fails.
+    fn apply<B: Backend>(&self, context: &mut ExecutionContext<B>) -> Result<(), ExecutionError> {
+        let mut guard = LoopGuard::new(self);
+        context.enter_loop();
+        let result = (|| {
+            let mut i = 0;
+            while !self.stop_condition.is_end_of_loop(i, context)? {
+                guard.before_iteration(self, i, context)?;
+                self.circuit.apply(context)?;
+                i += 1;
+                context.loop_iteration_completed(i)?;
+                guard.after_iteration(context);
+            }
+            Ok(())
+        })();
+        context.exit_loop();
+        result
     }
     fn max_qbit_idx(&self) -> u8 {
         self.circuit.max_qbit_idx()
     }
     fn check_validity(&self, nb_qbits: u8) -> Result<(), String> {
-        self.circuit.check_validity(nb_qbits)
+        self.circuit.check_elements_validity(nb_qbits)
+    }
+}
+
+#[cfg(test)]
+mod tests_loop {
+    use std::time::Duration;
+
+    use crate::common_gate::CommonGate::{Hadamard, Not};
+    use crate::computer::QuantumComputer;
+    use crate::debugger::ElementPath;
+    use crate::execution::{DEFAULT_MAX_TOTAL_ITERATIONS, ExecutionLimits};
+    use crate::measure::Measure;
+
+    use super::*;
+
+    fn loop_circuit(computer: &QuantumComputer, inner: Circuit, stop_condition: StopCondition) -> Circuit {
+        computer.new_circuit_builder()
+            .add_loop(inner, stop_condition)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn unreachable_condition_is_detected() {
+        let computer = QuantumComputer::new(2);
+        let inner = computer.new_circuit_builder().add_measure("q0", 0).build().unwrap();
+        let circuit = loop_circuit(&computer, inner, StopCondition::MaxOneSample { id: "q0".to_string(), nb: 1 });
+
+        let error = circuit.execute(&computer.zero_state()).err().unwrap();
+        assert_eq!(error, ExecutionError::UnreachableCondition { path: ElementPath::new(&[0]), nb_iterations: 2 });
+
+        let error = circuit.execute_stabilizer().err().unwrap();
+        assert!(matches!(error, ExecutionError::UnreachableCondition { .. }));
+    }
+
+    #[test]
+    fn deterministic_loops_that_end_are_not_stopped() {
+        let computer = QuantumComputer::new(1);
+        let inner = computer.new_circuit_builder().add_measure("q0", 0).build().unwrap();
+        let circuit = loop_circuit(&computer, inner, StopCondition::MaxZeroSampling { id: "q0".to_string(), nb: 5 });
+        assert_eq!(circuit.execute(&computer.zero_state()).unwrap().get_nb_zero("q0"), 5);
+
+        let inner = computer.new_circuit_builder()
+            .add_operation(Not(0))
+            .add_measure("q0", 0)
+            .add_operation(Not(0))
+            .build()
+            .unwrap();
+        let three = StopCondition::MaxOneSample { id: "q0".to_string(), nb: 3 };
+        let four = StopCondition::MaxOneSample { id: "q0".to_string(), nb: 4 };
+        let exactly_three = StopCondition::And(Box::new(three), Box::new(StopCondition::Not(Box::new(four))));
+        let circuit = loop_circuit(&computer, inner, exactly_three);
+        assert_eq!(circuit.execute(&computer.zero_state()).unwrap().get_nb_one("q0"), 3);
+    }
+
+    #[test]
+    fn short_cycles_are_detected() {
+        let computer = QuantumComputer::new(1);
+        let inner = computer.new_circuit_builder()
+            .add_operation(Not(0))
+            .add_measure("m", 0)
+            .build()
+            .unwrap();
+        let circuit = loop_circuit(&computer, inner.clone(), StopCondition::ConsecutiveOnes { id: "m".to_string(), nb: 2 });
+        let error = circuit.execute(&computer.zero_state()).err().unwrap();
+        assert_eq!(error, ExecutionError::UnreachableCondition { path: ElementPath::new(&[0]), nb_iterations: 3 });
+
+        let circuit = loop_circuit(&computer, inner, StopCondition::MaxZeroSampling { id: "m".to_string(), nb: 6 });
+        let context = circuit.execute(&computer.zero_state()).unwrap();
+        assert_eq!(context.get_nb_zero("m"), 6);
+        assert_eq!(context.get_nb_one("m"), 6);
+    }
+
+    #[test]
+    fn iteration_limits() {
+        let computer = QuantumComputer::new(1);
+        let inner = computer.new_circuit_builder()
+            .add_operation(Hadamard(0))
+            .add_measure("q0", 0)
+            .build()
+            .unwrap();
+        let never = StopCondition::MaxOneSample { id: "q0".to_string(), nb: u32::MAX };
+        let circuit = computer.new_circuit_builder()
+            .add_loop_with_max_iterations(inner.clone(), never.clone(), 10)
+            .build()
+            .unwrap();
+
+        let error = circuit.execute(&computer.zero_state()).err().unwrap();
+        assert_eq!(error, ExecutionError::IterationLimit { path: ElementPath::new(&[0]), limit: 10 });
+
+        let nested = computer.new_circuit_builder()
+            .add_loop(inner.clone(), StopCondition::MaxIteration(4))
+            .build()
+            .unwrap();
+        let circuit = loop_circuit(&computer, nested, never.clone());
+        let limits = ExecutionLimits::default().with_max_total_iterations(20);
+        let error = circuit.execute_with_limits(computer.zero_state(), limits).err().unwrap();
+        assert!(matches!(error, ExecutionError::GlobalIterationLimit { limit: 20, .. }));
+
+        let circuit = loop_circuit(&computer, inner, never);
+        let error = circuit.execute(&computer.zero_state()).err().unwrap();
+        assert_eq!(error, ExecutionError::GlobalIterationLimit { path: ElementPath::new(&[0]), limit: DEFAULT_MAX_TOTAL_ITERATIONS });
+    }
+
+    #[test]
+    fn failing_loop_is_exited() {
+        let computer = QuantumComputer::new(1);
+        let inner = computer.new_circuit_builder().add_operation(Hadamard(0)).add_measure("q0", 0).build().unwrap();
+        let the_loop = Loop { circuit: inner, stop_condition: StopCondition::MaxIteration(100), max_iterations: Some(3) };
+        let mut context = ExecutionContext::initialize(computer.zero_state());
+
+        assert!(matches!(the_loop.apply(&mut context), Err(ExecutionError::IterationLimit { limit: 3, .. })));
+        Measure::new("after", 0).apply(&mut context).unwrap();
+        assert!(context.history().last().unwrap().iterations.is_empty());
+    }
+
+    #[test]
+    fn max_iterations_is_optional_in_json() {
+        let computer = QuantumComputer::new(1);
+        let inner = computer.new_circuit_builder().add_operation(Hadamard(0)).build().unwrap();
+        let circuit = loop_circuit(&computer, inner.clone(), StopCondition::MaxIteration(2));
+        let json = serde_json::to_string(&circuit).unwrap();
+        assert!(!json.contains("max_iterations"));
+
+        let circuit = computer.new_circuit_builder()
+            .add_loop_with_max_iterations(inner, StopCondition::MaxIteration(2), 5)
+            .build()
+            .unwrap();
+        let circuit: Circuit = serde_json::from_str(&serde_json::to_string(&circuit).unwrap()).unwrap();
+        assert!(matches!(&circuit.elements[0], CircuitElement::Loop(l) if l.max_iterations == Some(5)));
+    }
+
+    #[test]
+    fn time_budget() {
+        let computer = QuantumComputer::new(1);
+        let inner = computer.new_circuit_builder().add_operation(Hadamard(0)).build().unwrap();
+        let circuit = loop_circuit(&computer, inner, StopCondition::MaxIteration(u32::MAX));
+
+        let limits = ExecutionLimits::default().with_time_budget(Duration::from_millis(10));
+        let error = circuit.execute_with_limits(computer.zero_state(), limits).err().unwrap();
+        assert!(matches!(error, ExecutionError::TimeBudgetExceeded { .. }));
+        assert!(String::from(error).starts_with("Execution exceeded its time budget"));
     }
 }
@@ -0,0 +1,91 @@
+use crate::base_gate::BaseGate;
+use crate::pauli::{rotate_back, rotate_to_z, Pauli};
+
+/// Two states are considered equal when the squared norm of their overlap is above this value
+pub(crate) const SAME_STATE_THRESHOLD: f64 = 1.0 - 1e-9;
+
+/// Representation of the quantum state used to execute a circuit.
+///
+/// The circuit model only talks to the state through this trait so
+/// that the same [`Circuit`] can be run on a dense state vector,
+/// a stabilizer tableau or any other representation.
+///
+/// [`Circuit`]: crate::circuit::Circuit
+pub trait Backend {
+    /// Return the number of qbits of the state. It may be more than the 255 qbits
+    /// a circuit can address, for instance for a large stabilizer tableau.
+    fn nb_qbits(&self) -> usize;
+
+    /// Apply the gate, controlled by the given qbits, to the state.
+    /// Fails if the backend does not support the gate.
+    fn apply_gate(&mut self, gate: &BaseGate, control_qbits: &[u8]) -> Result<(), String>;
+
+    /// Return the probability to measure |1> on the given qbit
+    fn probability_of_one(&self, qbit: u8) -> f64;
+
+    /// Measure the given qbit, collapse the state accordingly
+    /// and return true if |1> has been measured
+    fn measure(&mut self, qbit: u8) -> bool;
+
+    /// Project the given qbit on |1> (or on |0> if `one` is false) and renormalize the state.
+    /// Return the probability of this outcome before the projection, the state
+    /// is left unchanged when the probability is zero.
+    ///
+    /// The default implementation fails.
+    fn postselect(&mut self, _qbit: u8, _one: bool) -> Result<f64, String> {
+        Err("Postselection is not supported by this backend".to_string())
+    }
+
+    /// Return the probability of each basis state, indexed like the amplitudes
+    /// of a [`QuantumState`]. The result has 2^nb_qbits elements. The backends
+    /// without [`QbitOrdering`] use the default one (the qbit 0 is the most significant bit).
+    ///
+    /// [`QbitOrdering`]: crate::state::QbitOrdering
+    ///
+    /// [`QuantumState`]: crate::state::QuantumState
+    fn probabilities(&self) -> Vec<f64>;
+
+    /// Pick a basis state at random without modifying the state and
+    /// return the value of each qbit (qbit 0 first)
+    fn sample(&self) -> Vec<bool>;
+
+    /// Return the expectation value of a product of Pauli operators,
+    /// given as (qbit, operator) pairs.
+    ///
+    /// The default implementation maps the product to Z on one qbit with
+    /// Clifford gates applied to a [`snapshot`](Backend::snapshot) of the state.
+    fn pauli_expectation(&self, product: &[(u8, Pauli)]) -> Result<f64, String> where Self: Sized {
+        let mut copy = self.snapshot().ok_or("Pauli expectation is not supported by this backend")?;
+        match rotate_to_z(&mut copy, product)? {
+            None => Ok(1.0),
+            Some(qbit) => Ok(1.0 - 2.0 * copy.probability_of_one(qbit)),
+        }
+    }
+
+    /// Measure a product of Pauli operators and return true for the eigenvalue -1.
+    ///
+    /// The default implementation maps the product to Z on one qbit, measures it and
+    /// maps it back. It is only a projection on an eigenspace of the product when
+    /// [`measure`](Backend::measure) collapses the measured qbit alone, the backends
+    /// whose `measure` collapses the whole register must override it.
+    fn measure_pauli_product(&mut self, product: &[(u8, Pauli)]) -> Result<bool, String> {
+        let outcome = match rotate_to_z(self, product)? {
+            None => return Ok(false),
+            Some(qbit) => self.measure(qbit),
+        };
+        rotate_back(self, product)?;
+        Ok(outcome)
+    }
+
+    /// Return a copy of the state. Used to detect the loops whose stop
+    /// condition cannot be met; backends that are not copied return None.
+    fn snapshot(&self) -> Option<Self> where Self: Sized {
+        None
+    }
+
+    /// Return true if the other state is the same as this one up to a global phase.
+    /// The default implementation always returns false.
+    fn same_state(&self, _other: &Self) -> bool where Self: Sized {
+        false
+    }
+}
@@ -1,10 +1,10 @@
+use std::f64::consts::{FRAC_1_SQRT_2, FRAC_PI_4};
+
+use num_complex::Complex64;
+use num_traits::{One, Zero};
 use serde::{Deserialize, Serialize};
 
-use crate::execution::ExecutionContext;
 use crate::gate::Gate;
-use crate::gate_op::hadamard::apply_controlled_hadamard;
-use crate::gate_op::pauli::{apply_controlled_not, apply_controlled_pauli_x, apply_controlled_pauli_y, apply_controlled_pauli_z};
-use crate::gate_op::swap::apply_controlled_swap;
 use crate::operation::CircuitElement;
 
 #[derive(Copy, Clone, Serialize, Deserialize)]
@@ -15,13 +15,14 @@ pub enum BaseGate {
     Z(u8),
     Hadamard(u8),
     Swap(u8, u8),
-    // Todo
-    // Phase(f64,u8),
-    // S(u8),
-    // T(u8),
-    // Rx(f64,u8)
-    // Ry(f64,u8)
-    // Rz(f64,u8)
+    S(u8),
+    T(u8),
+    /// multiply the amplitude of |1> by e^(i.angle)
+    Phase(f64, u8),
+    /// rotation of the given angle around the X axis: e^(-i.angle.X/2)
+    Rx(f64, u8),
+    Ry(f64, u8),
+    Rz(f64, u8),
 }
 
 impl Into<Gate> for BaseGate {
@@ -73,6 +74,19 @@ impl BaseGate {
         Gate::new(self, controls.to_vec())
     }
 
+    /// Create a [`Gate`] whose angle is a symbolic expression, bound later
+    /// with [`Circuit::bind`](crate::circuit::Circuit::bind)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use quamputer::base_gate::BaseGate::Rx;
+    /// let rotation = Rx(0.0, 1).with_parameter("2*θ_0").unwrap();
+    /// ```
+    pub fn with_parameter(self, angle: &str) -> Result<Gate, String> {
+        Gate::new(self, vec![]).with_parameter(angle)
+    }
+
     pub fn get_involved_qbits(&self, others: &[u8]) -> Vec<u8> {
         let mut result = Vec::new();
         result.extend_from_slice(others);
@@ -86,6 +100,12 @@ impl BaseGate {
                 result.push(*t2);
             }
             BaseGate::Hadamard(t) => result.push(*t),
+            BaseGate::S(t) => result.push(*t),
+            BaseGate::T(t) => result.push(*t),
+            BaseGate::Phase(_, t) => result.push(*t),
+            BaseGate::Rx(_, t) => result.push(*t),
+            BaseGate::Ry(_, t) => result.push(*t),
+            BaseGate::Rz(_, t) => result.push(*t),
         };
         result
     }
@@ -98,17 +118,77 @@ impl BaseGate {
             BaseGate::Z(target) => *target,
             BaseGate::Hadamard(target) => *target,
             BaseGate::Swap(target1, target2) => *target1.max(target2),
+            BaseGate::S(target) => *target,
+            BaseGate::T(target) => *target,
+            BaseGate::Phase(_, target) => *target,
+            BaseGate::Rx(_, target) => *target,
+            BaseGate::Ry(_, target) => *target,
+            BaseGate::Rz(_, target) => *target,
+        }
+    }
+
+    /// Return the same gate applied on the qbits given by `map` for each target
+    pub fn map_qbits(self, map: impl Fn(u8) -> u8) -> BaseGate {
+        match self {
+            BaseGate::Not(target) => BaseGate::Not(map(target)),
+            BaseGate::X(target) => BaseGate::X(map(target)),
+            BaseGate::Y(target) => BaseGate::Y(map(target)),
+            BaseGate::Z(target) => BaseGate::Z(map(target)),
+            BaseGate::Hadamard(target) => BaseGate::Hadamard(map(target)),
+            BaseGate::Swap(target1, target2) => BaseGate::Swap(map(target1), map(target2)),
+            BaseGate::S(target) => BaseGate::S(map(target)),
+            BaseGate::T(target) => BaseGate::T(map(target)),
+            BaseGate::Phase(angle, target) => BaseGate::Phase(angle, map(target)),
+            BaseGate::Rx(angle, target) => BaseGate::Rx(angle, map(target)),
+            BaseGate::Ry(angle, target) => BaseGate::Ry(angle, map(target)),
+            BaseGate::Rz(angle, target) => BaseGate::Rz(angle, map(target)),
+        }
+    }
+
+    /// Return the angle of the rotation and phase gates
+    pub fn angle(&self) -> Option<f64> {
+        match self {
+            BaseGate::Phase(angle, _) | BaseGate::Rx(angle, _) | BaseGate::Ry(angle, _) | BaseGate::Rz(angle, _) => Some(*angle),
+            _ => None,
         }
     }
 
-    pub(crate) fn apply_controlled(&self, control_qbits: &[u8], context: &mut ExecutionContext) {
+    /// Return the same gate with another angle. The gates without angle are returned unchanged.
+    pub fn with_angle(self, angle: f64) -> BaseGate {
         match self {
-            BaseGate::Not(target) => apply_controlled_not(*target, control_qbits, context),
-            BaseGate::X(target) => apply_controlled_pauli_x(*target, control_qbits, context),
-            BaseGate::Y(target) => apply_controlled_pauli_y(*target, control_qbits, context),
-            BaseGate::Z(target) => apply_controlled_pauli_z(*target, control_qbits, context),
-            BaseGate::Hadamard(target) => apply_controlled_hadamard(control_qbits, *target, context),
-            BaseGate::Swap(target1, target2) => apply_controlled_swap(control_qbits, *target1, *target2, context),
+            BaseGate::Phase(_, t) => BaseGate::Phase(angle, t),
+            BaseGate::Rx(_, t) => BaseGate::Rx(angle, t),
+            BaseGate::Ry(_, t) => BaseGate::Ry(angle, t),
+            BaseGate::Rz(_, t) => BaseGate::Rz(angle, t),
+            other => other,
+        }
+    }
+
+    /// Return the matrix (`matrix[row][column]`) of the gate
+    /// if it acts on a single qbit, None otherwise
+    pub fn matrix(&self) -> Option<[[Complex64; 2]; 2]> {
+        let one = Complex64::one();
+        let zero = Complex64::zero();
+        let i = Complex64::i();
+        let h = Complex64::new(FRAC_1_SQRT_2, 0.0);
+        match self {
+            BaseGate::Not(_) | BaseGate::X(_) => Some([[zero, one], [one, zero]]),
+            BaseGate::Y(_) => Some([[zero, -i], [i, zero]]),
+            BaseGate::Z(_) => Some([[one, zero], [zero, -one]]),
+            BaseGate::S(_) => Some([[one, zero], [zero, i]]),
+            BaseGate::Hadamard(_) => Some([[h, h], [h, -h]]),
+            BaseGate::T(_) => Some([[one, zero], [zero, Complex64::from_polar(1.0, FRAC_PI_4)]]),
+            BaseGate::Phase(angle, _) => Some([[one, zero], [zero, Complex64::from_polar(1.0, *angle)]]),
+            BaseGate::Rx(angle, _) => {
+                let (sin, cos) = (angle * 0.5).sin_cos();
+                Some([[cos * one, -sin * i], [-sin * i, cos * one]])
+            }
+            BaseGate::Ry(angle, _) => {
+                let (sin, cos) = (angle * 0.5).sin_cos();
+                Some([[cos * one, -sin * one], [sin * one, cos * one]])
+            }
+            BaseGate::Rz(angle, _) => Some([[Complex64::from_polar(1.0, -angle * 0.5), zero], [zero, Complex64::from_polar(1.0, angle * 0.5)]]),
+            BaseGate::Swap(_, _) => None,
         }
     }
 }
@@ -1,27 +1,75 @@
+use std::collections::BTreeMap;
+
 use crate::_loop::Loop;
 use crate::circuit::Circuit;
 use crate::condition::StopCondition;
-use crate::measure::Measure;
+use crate::measure::{Measure, RegisterMeasure};
 use crate::operation::CircuitElement;
 use crate::operation::QuantumOperation;
+use crate::pauli::Pauli;
+use crate::postselect::Postselect;
+use crate::sub_circuit::{merge_definitions, SubCircuit};
 
 pub struct QuantumCircuitBuilder {
     nb_qbits: u8,
     operations: Vec<CircuitElement>,
+    definitions: BTreeMap<String, Circuit>,
+    /// the first conflict between two definitions, reported by `build`
+    definition_error: Option<String>,
 }
 
 impl QuantumCircuitBuilder {
     pub(crate) fn new(nb_qbits: u8) -> Self {
-        return Self { nb_qbits, operations: Vec::new() };
+        return Self { nb_qbits, operations: Vec::new(), definitions: BTreeMap::new(), definition_error: None };
     }
 
     pub fn build(&self) -> Result<Circuit, String> {
-        let circuit = Circuit { nb_qbits: self.nb_qbits, elements: self.operations.clone() };
+        if let Some(error) = &self.definition_error {
+            return Err(error.clone());
+        }
+        let circuit = Circuit { nb_qbits: self.nb_qbits, elements: self.operations.clone(), definitions: self.definitions.clone() };
         circuit.check_validity(self.nb_qbits).map(|()| circuit)
     }
 
+    /// Add a loop. The sub-circuits defined by the content of the loop are moved to this circuit.
     pub fn add_loop(&mut self, circuit: impl Into<Circuit>, loop_condition: StopCondition) -> &mut QuantumCircuitBuilder {
-        self.add_operation(Loop { circuit: circuit.into(), stop_condition: loop_condition })
+        self.push_loop(circuit.into(), loop_condition, None)
+    }
+
+    /// Add a loop that fails with [`ExecutionError::IterationLimit`](crate::execution::ExecutionError::IterationLimit)
+    /// instead of starting its iteration number `max_iterations + 1`
+    pub fn add_loop_with_max_iterations(&mut self, circuit: impl Into<Circuit>, loop_condition: StopCondition, max_iterations: u32) -> &mut QuantumCircuitBuilder {
+        self.push_loop(circuit.into(), loop_condition, Some(max_iterations))
+    }
+
+    fn push_loop(&mut self, mut circuit: Circuit, stop_condition: StopCondition, max_iterations: Option<u32>) -> &mut QuantumCircuitBuilder {
+        let definitions = std::mem::take(&mut circuit.definitions);
+        self.merge_definitions(&definitions);
+        self.add_operation(Loop { circuit, stop_condition, max_iterations })
+    }
+
+    /// Define a sub-circuit that can be called with [`add_sub_circuit`](QuantumCircuitBuilder::add_sub_circuit).
+    /// The definition is stored once in the circuit whatever the number of calls. The sub-circuits
+    /// it defines itself are moved to this circuit, the build fails if two different sub-circuits have the same name.
+    pub fn add_definition(&mut self, name: &str, circuit: impl Into<Circuit>) -> &mut QuantumCircuitBuilder {
+        let mut circuit = circuit.into();
+        let mut definitions = std::mem::take(&mut circuit.definitions);
+        definitions.insert(name.to_string(), circuit);
+        self.merge_definitions(&definitions);
+        self
+    }
+
+    /// Add a call of the sub-circuit with the given name, the qbit `i` of the
+    /// sub-circuit being the qbit `qbits[i]`. Use [`SubCircuit::controlled`] with
+    /// [`add_operation`](QuantumCircuitBuilder::add_operation) for a controlled call.
+    pub fn add_sub_circuit(&mut self, name: &str, qbits: &[u8]) -> &mut QuantumCircuitBuilder {
+        self.add_operation(SubCircuit::new(name, qbits))
+    }
+
+    fn merge_definitions(&mut self, definitions: &BTreeMap<String, Circuit>) {
+        if let Err(error) = merge_definitions(&mut self.definitions, definitions) {
+            self.definition_error.get_or_insert(error);
+        }
     }
 
     /// Add a measurement operation that will measure a given qbits.
@@ -32,7 +80,36 @@ impl QuantumCircuitBuilder {
     ///
     /// [`StopCondition`]: condition/StopCondition
     pub fn add_measure(&mut self, id: &str, qbit_target: u8) -> &mut QuantumCircuitBuilder {
-        self.add_operation(Measure { id: id.to_string(), qbit_target })
+        self.add_operation(Measure::new(id, qbit_target))
+    }
+
+    /// Add a measurement of several qbits whose result is recorded as an integer,
+    /// the first qbit giving the most significant bit. Like with [`add_measure`](QuantumCircuitBuilder::add_measure)
+    /// the qbits are measured in the Z basis.
+    pub fn add_measure_register(&mut self, id: &str, qbits: &[u8]) -> &mut QuantumCircuitBuilder {
+        self.add_operation(RegisterMeasure { id: id.to_string(), qbits: qbits.to_vec() })
+    }
+
+    /// Add a measurement of a qbit in the X, Y or Z basis.
+    /// The outcome is 'zero' for the eigenstate of eigenvalue +1 (|+> for X)
+    pub fn add_measure_in_basis(&mut self, id: &str, qbit_target: u8, basis: Pauli) -> &mut QuantumCircuitBuilder {
+        self.add_operation(Measure::in_basis(id, qbit_target, basis))
+    }
+
+    /// Add the measurement of a product of Pauli operators on several qbits, like the ZZ parity.
+    /// A single outcome is recorded under the id: 'zero' for the eigenvalue +1 and 'one' for -1.
+    /// Unlike the single qbit measurement, the state is only projected on the eigenspace
+    /// of the measured eigenvalue.
+    pub fn add_pauli_measure(&mut self, id: &str, product: &[(u8, Pauli)]) -> &mut QuantumCircuitBuilder {
+        self.add_operation(Measure::pauli_product(id, product))
+    }
+
+    /// Add a postselection that projects the qbit on |1> (or on |0> if `outcome` is false)
+    /// and renormalizes the state. The probability of the outcome is recorded in the execution
+    /// context; use [`Postselect::aborting_on_zero`] with [`add_operation`](QuantumCircuitBuilder::add_operation)
+    /// to fail the execution when the outcome cannot happen.
+    pub fn add_postselect(&mut self, id: &str, qbit_target: u8, outcome: bool) -> &mut QuantumCircuitBuilder {
+        self.add_operation(Postselect::new(id, qbit_target, outcome))
     }
 
     pub fn add_operation(&mut self, operation: impl Into<CircuitElement>) -> &mut QuantumCircuitBuilder {
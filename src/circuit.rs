@@ -1,7 +1,15 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
 use serde::{Deserialize, Serialize};
 
-use crate::execution::ExecutionContext;
+use crate::_loop::Loop;
+use crate::backend::Backend;
+use crate::debugger::ExecutionCursor;
+use crate::observer::ExecutionObserver;
+use crate::execution::{ExecutionContext, ExecutionError, ExecutionLimits};
 use crate::operation::{CircuitElement, QuantumOperation};
+use crate::sparse::AdaptiveState;
+use crate::stabilizer::Tableau;
 use crate::state::QuantumState;
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -10,15 +18,113 @@ pub struct Circuit {
     pub nb_qbits: u8,
     /// the elements composing this circuit
     pub elements: Vec<CircuitElement>,
+    /// the sub-circuits called by the elements, by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub definitions: BTreeMap<String, Circuit>,
 }
 
 
 impl Circuit {
 
-    pub fn execute(&self, initial_state: &QuantumState) -> ExecutionContext {
-        let mut context = ExecutionContext::initialize(&initial_state);
-        self.apply(&mut context);
-        return context;
+    /// Execute this circuit from a copy of the initial state. The state is only stored sparsely
+    /// while few basis states are populated (see [`AdaptiveState`]), the final state is dense.
+    pub fn execute(&self, initial_state: &QuantumState) -> Result<ExecutionContext, ExecutionError> {
+        let context = self.execute_on(AdaptiveState::from_dense(initial_state))?;
+        Ok(context.map_state(AdaptiveState::into_dense))
+    }
+
+    /// Execute this circuit with the given backend as initial state
+    pub fn execute_on<B: Backend>(&self, initial_state: B) -> Result<ExecutionContext<B>, ExecutionError> {
+        let mut context = self.new_context(initial_state);
+        self.apply(&mut context)?;
+        return Ok(context);
+    }
+
+    /// Execute this circuit and notify the given observers of the execution events
+    pub fn execute_observed<B: Backend>(&self, initial_state: B, observers: Vec<Box<dyn ExecutionObserver>>) -> Result<ExecutionContext<B>, ExecutionError> {
+        let mut context = self.new_context(initial_state);
+        for observer in observers {
+            context.add_observer(observer);
+        }
+        self.apply(&mut context)?;
+        Ok(context)
+    }
+
+    /// Execute this circuit and fail if the execution goes beyond the given limits
+    pub fn execute_with_limits<B: Backend>(&self, initial_state: B, limits: ExecutionLimits) -> Result<ExecutionContext<B>, ExecutionError> {
+        let mut context = self.new_context(initial_state);
+        context.set_limits(limits);
+        self.apply(&mut context)?;
+        Ok(context)
+    }
+
+    pub(crate) fn new_context<B: Backend>(&self, initial_state: B) -> ExecutionContext<B> {
+        let mut context = ExecutionContext::initialize(initial_state);
+        context.set_definitions(self.definitions.clone());
+        context
+    }
+
+    /// Execute this circuit from the state |0...0> with the stabilizer simulator.
+    /// Only Clifford gates are supported but the execution time is polynomial
+    /// in the number of qbits.
+    pub fn execute_stabilizer(&self) -> Result<ExecutionContext<Tableau>, ExecutionError> {
+        crate::stabilizer::check_clifford(self)?;
+        self.execute_on(Tableau::new(self.nb_qbits as usize))
+    }
+
+    /// Return a cursor to execute this circuit step by step
+    pub fn debug(&self, initial_state: &QuantumState) -> ExecutionCursor<'_> {
+        self.debug_on(QuantumState::from(initial_state))
+    }
+
+    /// Return a cursor to execute this circuit step by step with the given backend
+    pub fn debug_on<B: Backend>(&self, initial_state: B) -> ExecutionCursor<'_, B> {
+        ExecutionCursor::new(self, initial_state)
+    }
+
+    /// Return a cursor that fails if the execution goes beyond the given limits
+    pub fn debug_with_limits<B: Backend>(&self, initial_state: B, limits: ExecutionLimits) -> ExecutionCursor<'_, B> {
+        let mut cursor = ExecutionCursor::new(self, initial_state);
+        cursor.set_limits(limits);
+        cursor
+    }
+
+    /// Return the names of the free parameters of the circuit in alphabetical order
+    pub fn parameters(&self) -> Vec<String> {
+        let mut parameters = BTreeSet::new();
+        collect_parameters(&self.elements, &mut parameters);
+        for definition in self.definitions.values() {
+            collect_parameters(&definition.elements, &mut parameters);
+        }
+        parameters.into_iter().collect()
+    }
+
+    /// Return the concrete circuit obtained by giving a value to the parameters,
+    /// the parameters of the sub-circuits included. Fails if the value of a parameter is missing.
+    pub fn bind(&self, values: &HashMap<String, f64>) -> Result<Circuit, String> {
+        let elements = self.elements.iter()
+            .map(|element| match element {
+                CircuitElement::Gate(gate) => gate.bind(values).map(CircuitElement::Gate),
+                CircuitElement::Loop(l) => l.circuit.bind(values)
+                    .map(|circuit| CircuitElement::Loop(Loop { circuit, stop_condition: l.stop_condition.clone(), max_iterations: l.max_iterations })),
+                other => Ok(other.clone()),
+            })
+            .collect::<Result<Vec<CircuitElement>, String>>()?;
+        let definitions = self.definitions.iter()
+            .map(|(name, definition)| definition.bind(values).map(|definition| (name.clone(), definition)))
+            .collect::<Result<BTreeMap<String, Circuit>, String>>()?;
+        Ok(Circuit { nb_qbits: self.nb_qbits, elements, definitions })
+    }
+
+    /// Check the elements only, the sub-circuits being defined by the enclosing circuit
+    pub(crate) fn check_elements_validity(&self, nb_qbits: u8) -> Result<(), String> {
+        for operation in self.elements.iter() {
+            let op_validity = operation.check_validity(nb_qbits);
+            if op_validity.is_err() {
+                return op_validity;
+            }
+        }
+        Ok(())
     }
 
     pub fn to_string(&self) -> serde_json::error::Result<String> {
@@ -30,23 +136,38 @@ impl Circuit {
     }
 }
 
+fn collect_parameters(elements: &[CircuitElement], parameters: &mut BTreeSet<String>) {
+    for element in elements {
+        match element {
+            CircuitElement::Gate(gate) => {
+                if let Some(parameter) = gate.get_parameter() {
+                    parameters.extend(parameter.parameters().into_iter().map(|p| p.to_string()));
+                }
+            }
+            CircuitElement::Loop(l) => collect_parameters(&l.circuit.elements, parameters),
+            CircuitElement::Measure(_) | CircuitElement::RegisterMeasure(_) | CircuitElement::Postselect(_) | CircuitElement::SubCircuit(_) => {}
+        }
+    }
+}
 
 impl QuantumOperation for Circuit {
-    fn apply(&self, context: &mut ExecutionContext) {
-        self.elements.iter().for_each(|op| op.apply(context))
+    fn apply<B: Backend>(&self, context: &mut ExecutionContext<B>) -> Result<(), ExecutionError> {
+        for (index, element) in self.elements.iter().enumerate() {
+            context.enter_element(index, element)?;
+            let result = element.apply(context);
+            context.exit_element(element)?;
+            result?;
+        }
+        Ok(())
     }
 
     fn max_qbit_idx(&self) -> u8 {
         self.nb_qbits - 1
     }
 
+    /// Check the elements and, for the circuit holding the definitions, the calls of the sub-circuits
     fn check_validity(&self, nb_qbits: u8) -> Result<(), String> {
-        for operation in self.elements.iter() {
-            let op_validity = operation.check_validity(nb_qbits);
-            if op_validity.is_err() {
-                return op_validity;
-            }
-        }
-        Ok(())
+        self.check_elements_validity(nb_qbits)?;
+        self.check_sub_circuits()
     }
 }
@@ -1,7 +1,7 @@
 use crate::gate::Gate;
 use crate::base_gate::BaseGate;
 use crate::operation::CircuitElement;
-use crate::common_gate::CommonGate::{CNot, CSwap, Fredkin, Hadamard, Not, Swap, Toffoli, X, Y, Z, CCNot};
+use crate::common_gate::CommonGate::{CNot, CSwap, Fredkin, Hadamard, Not, Swap, Toffoli, X, Y, Z, CCNot, S, CZ, T, Phase, Rx, Ry, Rz, CPhase};
 
 ///
 /// Gate without any control qbits.
@@ -14,11 +14,19 @@ pub enum CommonGate {
     Z(u8),
     Swap(u8, u8),
     Hadamard(u8),
+    S(u8),
+    T(u8),
+    Phase(f64, u8),
+    Rx(f64, u8),
+    Ry(f64, u8),
+    Rz(f64, u8),
     CNot(u8, [u8; 1]),
+    CZ(u8, [u8; 1]),
     Toffoli(u8, [u8; 2]),
     CCNot(u8, [u8; 2]),
     CSwap(u8, u8, [u8; 1]),
     Fredkin(u8, u8, [u8; 1]),
+    CPhase(f64, u8, [u8; 1]),
 }
 
 
@@ -31,11 +39,19 @@ impl Into<Gate> for CommonGate {
             Z(t) => crate::gate::Gate::new(BaseGate::Z(t), vec![]),
             Swap(t1, t2) => crate::gate::Gate::new(BaseGate::Swap(t1, t2), vec![]),
             Hadamard(t) => crate::gate::Gate::new(BaseGate::Hadamard(t), vec![]),
+            S(t) => crate::gate::Gate::new(BaseGate::S(t), vec![]),
+            T(t) => crate::gate::Gate::new(BaseGate::T(t), vec![]),
+            Phase(a, t) => crate::gate::Gate::new(BaseGate::Phase(a, t), vec![]),
+            Rx(a, t) => crate::gate::Gate::new(BaseGate::Rx(a, t), vec![]),
+            Ry(a, t) => crate::gate::Gate::new(BaseGate::Ry(a, t), vec![]),
+            Rz(a, t) => crate::gate::Gate::new(BaseGate::Rz(a, t), vec![]),
             CNot(t, c) => crate::gate::Gate::new(BaseGate::Not(t), Vec::from(c)),
+            CZ(t, c) => crate::gate::Gate::new(BaseGate::Z(t), Vec::from(c)),
             Toffoli(t, c) => crate::gate::Gate::new(BaseGate::Not(t), Vec::from(c)),
             CCNot(t, c) => crate::gate::Gate::new(BaseGate::Not(t), Vec::from(c)),
             CSwap(t1, t2, c) => crate::gate::Gate::new(BaseGate::Swap(t1, t2), Vec::from(c)),
             Fredkin(t1, t2, c) => crate::gate::Gate::new(BaseGate::Swap(t1, t2), Vec::from(c)),
+            CPhase(a, t, c) => crate::gate::Gate::new(BaseGate::Phase(a, t), Vec::from(c)),
         }
     }
 }
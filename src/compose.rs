@@ -0,0 +1,224 @@
+use std::collections::BTreeMap;
+
+use crate::_loop::Loop;
+use crate::circuit::Circuit;
+use crate::condition::StopCondition;
+use crate::gate::check_for_no_duplicate;
+use crate::measure::{Measure, RegisterMeasure};
+use crate::operation::{CircuitElement, QuantumOperation};
+use crate::postselect::Postselect;
+use crate::sub_circuit::{merge_definitions, SubCircuit};
+
+impl Circuit {
+    /// Return the circuit executing this circuit then the other one. The other circuit
+    /// may have less qbits, it is then applied on the first qbits of this one.
+    pub fn append(&self, other: &Circuit) -> Result<Circuit, String> {
+        if other.nb_qbits > self.nb_qbits {
+            return Err(format!("Cannot append a circuit of {} qbits to a circuit of {} qbits", other.nb_qbits, self.nb_qbits));
+        }
+        let mapping: Vec<u8> = (0..other.nb_qbits).collect();
+        self.compose(other, &mapping)
+    }
+
+    /// Return the circuit executing this circuit then the other one, the qbit `i` of the
+    /// other circuit being the qbit `mapping[i]` of this one. The sub-circuits of both
+    /// circuits are kept, it fails if two different sub-circuits have the same name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use quamputer::computer::QuantumComputer;
+    /// let bell = QuantumComputer::new(2).bell_state().build().unwrap();
+    /// let register = QuantumComputer::new(4).new_circuit_builder().build().unwrap();
+    /// // entangle the qbits 3 and 1
+    /// let circuit = register.compose(&bell, &[3, 1]).unwrap();
+    /// ```
+    pub fn compose(&self, other: &Circuit, mapping: &[u8]) -> Result<Circuit, String> {
+        if mapping.len() != other.nb_qbits as usize {
+            return Err(format!("The mapping must give a qbit for each of the {} qbits of the circuit, got {}", other.nb_qbits, mapping.len()));
+        }
+        if let Some(qbit) = mapping.iter().find(|qbit| **qbit >= self.nb_qbits) {
+            return Err(format!("Index to high {}", qbit));
+        }
+        check_for_no_duplicate(mapping.to_vec())?;
+
+        let mut elements = self.elements.clone();
+        elements.extend(other.elements.iter().map(|element| map_element(element, mapping, self.nb_qbits)));
+        let mut definitions = self.definitions.clone();
+        merge_definitions(&mut definitions, &other.definitions)?;
+        let circuit = Circuit { nb_qbits: self.nb_qbits, elements, definitions };
+        circuit.check_validity(circuit.nb_qbits).map(|()| circuit)
+    }
+
+    /// Return the circuit executing the two circuits side by side: the qbits of the other
+    /// circuit follow the qbits of this one. Measurements sharing an id are merged.
+    pub fn tensor(&self, other: &Circuit) -> Result<Circuit, String> {
+        let nb_qbits = self.nb_qbits.checked_add(other.nb_qbits)
+            .ok_or_else(|| format!("Too many qbits: {} + {}", self.nb_qbits, other.nb_qbits))?;
+        let register = Circuit { nb_qbits, elements: self.elements.clone(), definitions: self.definitions.clone() };
+        let mapping: Vec<u8> = (self.nb_qbits..nb_qbits).collect();
+        register.compose(other, &mapping)
+    }
+}
+
+/// Move the element on the qbits given by the mapping, in a register of `nb_qbits` qbits
+pub(crate) fn map_element(element: &CircuitElement, mapping: &[u8], nb_qbits: u8) -> CircuitElement {
+    let map = |qbit: u8| mapping[qbit as usize];
+    match element {
+        CircuitElement::Gate(gate) => CircuitElement::Gate(gate.map_qbits(map)),
+        CircuitElement::Measure(measure) => CircuitElement::Measure(Measure {
+            id: measure.id.clone(),
+            qbit_target: map(measure.qbit_target),
+            basis: measure.basis,
+            pauli_product: measure.pauli_product.as_ref().map(|product| product.iter().map(|(qbit, pauli)| (map(*qbit), *pauli)).collect()),
+        }),
+        CircuitElement::RegisterMeasure(measure) => CircuitElement::RegisterMeasure(RegisterMeasure {
+            id: measure.id.clone(),
+            qbits: measure.qbits.iter().map(|qbit| map(*qbit)).collect(),
+        }),
+        CircuitElement::Postselect(postselect) => CircuitElement::Postselect(Postselect {
+            qbit_target: map(postselect.qbit_target),
+            ..postselect.clone()
+        }),
+        CircuitElement::Loop(l) => CircuitElement::Loop(Loop {
+            circuit: Circuit {
+                nb_qbits,
+                elements: l.circuit.elements.iter().map(|element| map_element(element, mapping, nb_qbits)).collect(),
+                definitions: BTreeMap::new(),
+            },
+            stop_condition: map_condition(&l.stop_condition, mapping),
+            max_iterations: l.max_iterations,
+        }),
+        CircuitElement::SubCircuit(call) => CircuitElement::SubCircuit(SubCircuit {
+            name: call.name.clone(),
+            qbits: call.qbits.iter().map(|qbit| map(*qbit)).collect(),
+            control_bits: call.control_bits.iter().map(|qbit| map(*qbit)).collect(),
+        }),
+    }
+}
+
+/// Move the qbits referenced by the condition, the measurement ids are kept
+fn map_condition(condition: &StopCondition, mapping: &[u8]) -> StopCondition {
+    let map_box = |c: &StopCondition| Box::new(map_condition(c, mapping));
+    match condition {
+        StopCondition::ProbabilityOfOneAbove { qbit, threshold } => {
+            StopCondition::ProbabilityOfOneAbove { qbit: mapping[*qbit as usize], threshold: *threshold }
+        }
+        StopCondition::ProbabilityOfOneBelow { qbit, threshold } => {
+            StopCondition::ProbabilityOfOneBelow { qbit: mapping[*qbit as usize], threshold: *threshold }
+        }
+        StopCondition::Not(c) => StopCondition::Not(map_box(c)),
+        StopCondition::Or(lhs, rhs) => StopCondition::Or(map_box(lhs), map_box(rhs)),
+        StopCondition::And(lhs, rhs) => StopCondition::And(map_box(lhs), map_box(rhs)),
+        StopCondition::Xor(lhs, rhs) => StopCondition::Xor(map_box(lhs), map_box(rhs)),
+        other => other.clone(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests_compose {
+    use std::collections::HashMap;
+
+    use crate::backend::Backend;
+    use crate::base_gate::BaseGate;
+    use crate::common_gate::CommonGate::{CNot, Hadamard, Not};
+    use crate::computer::QuantumComputer;
+    use crate::condition::StopCondition;
+    use crate::state::QuantumState;
+
+    use super::*;
+
+    fn bell() -> Circuit {
+        QuantumComputer::new(2).bell_state().build().unwrap()
+    }
+
+    fn empty(nb_qbits: u8) -> Circuit {
+        QuantumComputer::new(nb_qbits).new_circuit_builder().build().unwrap()
+    }
+
+    fn final_state(circuit: &Circuit) -> QuantumState {
+        let result = circuit.execute(&QuantumComputer::new(circuit.nb_qbits).zero_state()).unwrap();
+        QuantumState::from(result.current_state())
+    }
+
+    fn assert_state(circuit: &Circuit, bitstrings: &[&str]) {
+        let state = final_state(circuit);
+        let expected = 1.0 / bitstrings.len() as f64;
+        for bitstring in bitstrings {
+            let basis = QuantumState::from_bitstring(bitstring).unwrap();
+            assert!((basis.inner_product(&state).unwrap().norm_sqr() - expected).abs() < 1e-9, "{}", bitstring);
+        }
+    }
+
+    #[test]
+    fn append_executes_both_circuits() {
+        let undo = QuantumComputer::new(2).new_circuit_builder()
+            .add_operation(CNot(1, [0]))
+            .add_operation(Hadamard(0))
+            .build()
+            .unwrap();
+        let circuit = bell().append(&undo).unwrap();
+        assert_eq!(circuit.elements.len(), 4);
+        assert_state(&circuit, &["00"]);
+
+        let flip = QuantumComputer::new(1).new_circuit_builder().add_operation(Not(0)).build().unwrap();
+        assert_state(&empty(2).append(&flip).unwrap(), &["10"]);
+        assert!(flip.append(&bell()).is_err());
+    }
+
+    #[test]
+    fn compose_moves_the_qbits() {
+        let circuit = empty(4).compose(&bell(), &[3, 1]).unwrap();
+        assert_state(&circuit, &["0000", "0101"]);
+
+        let measured = QuantumComputer::new(2).new_circuit_builder()
+            .add_operation(Not(1))
+            .add_measure_register("r", &[0, 1])
+            .add_postselect("p", 1, true)
+            .build()
+            .unwrap();
+        let result = empty(3).compose(&measured, &[2, 0]).unwrap()
+            .execute(&QuantumComputer::new(3).zero_state())
+            .unwrap();
+        assert_eq!(result.last_register_value("r"), Some(0b01));
+        assert!((result.current_state().probability_of_one(0) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn compose_checks_the_mapping() {
+        assert!(empty(4).compose(&bell(), &[3]).is_err());
+        assert!(empty(4).compose(&bell(), &[1, 1]).is_err());
+        assert!(empty(4).compose(&bell(), &[0, 4]).is_err());
+    }
+
+    #[test]
+    fn compose_maps_the_loops_and_keeps_the_parameters() {
+        let body = QuantumComputer::new(1).new_circuit_builder()
+            .add_operation(Not(0))
+            .build()
+            .unwrap();
+        let flip_back = QuantumComputer::new(1).new_circuit_builder()
+            .add_operation(BaseGate::Ry(0.0, 0).with_parameter("θ").unwrap())
+            .add_loop(body, StopCondition::ProbabilityOfOneBelow { qbit: 0, threshold: 0.5 })
+            .build()
+            .unwrap();
+        let circuit = empty(2).compose(&flip_back, &[1]).unwrap();
+        assert_eq!(circuit.parameters(), vec!["θ"]);
+
+        let values: HashMap<String, f64> = vec![("θ".to_string(), std::f64::consts::PI)].into_iter().collect();
+        // the loop runs once only if its condition looks at the qbit 1
+        assert_state(&circuit.bind(&values).unwrap(), &["00"]);
+    }
+
+    #[test]
+    fn tensor_places_the_circuits_side_by_side() {
+        let flip = QuantumComputer::new(1).new_circuit_builder().add_operation(Not(0)).build().unwrap();
+        let circuit = bell().tensor(&flip).unwrap();
+        assert_eq!(circuit.nb_qbits, 3);
+        assert_state(&circuit, &["001", "111"]);
+
+        let circuit = flip.tensor(&bell()).unwrap();
+        assert_state(&circuit, &["100", "111"]);
+    }
+}
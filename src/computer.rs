@@ -1,15 +1,27 @@
 use crate::builder::QuantumCircuitBuilder;
 use crate::common_gate::CommonGate::{CNot, Hadamard};
-use crate::state::QuantumState;
+use crate::state::{QbitOrdering, QuantumState};
 
 pub struct QuantumComputer {
     nb_qbits: u8,
+    ordering: QbitOrdering,
 }
 
 impl QuantumComputer {
     /// Create a new computer
     pub fn new(nb_qbits: u8) -> Self {
-        Self { nb_qbits }
+        Self { nb_qbits, ordering: QbitOrdering::default() }
+    }
+
+    /// Set the order of the qbits in the index of the basis states
+    /// of the states created by this computer
+    pub fn with_ordering(mut self, ordering: QbitOrdering) -> Self {
+        self.ordering = ordering;
+        self
+    }
+
+    pub fn ordering(&self) -> QbitOrdering {
+        self.ordering
     }
 
 
@@ -33,12 +45,14 @@ impl QuantumComputer {
 
     /// Create a initial quantum state with all amplitudes to zero
     pub fn zero_state(&self) -> QuantumState {
-        QuantumState::zero(self.nb_qbits)
+        QuantumState::zero(self.nb_qbits).with_ordering_unchanged(self.ordering)
     }
 
-    /// Create a initial quantum state with the same amplitudes for all qbits
-    pub fn same_amplitude(&self, qbit_idx: &[usize]) -> QuantumState {
-        QuantumState::same_amplitude(self.nb_qbits, qbit_idx)
+    /// Create a initial quantum state that is the superposition with the same amplitude
+    /// of the given basis states. The basis states are indices of the amplitudes in the
+    /// ordering of this computer. See also [`QuantumState::from_bitstrings_in`].
+    pub fn same_amplitude(&self, basis_states: &[usize]) -> QuantumState {
+        QuantumState::same_amplitude(self.nb_qbits, basis_states).with_ordering_unchanged(self.ordering)
     }
 
     /// Return the number of qbits this computer has
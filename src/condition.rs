@@ -1,6 +1,11 @@
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+
 use serde::{Deserialize, Serialize};
 
+use crate::backend::Backend;
 use crate::execution::ExecutionContext;
+use crate::measure::MeasureRecord;
 
 /// Variants used to define the stop condition in a loop.
 #[derive(Clone, Serialize, Deserialize)]
@@ -13,8 +18,125 @@ pub enum StopCondition {
     MaxZeroSampling { id: String, nb: u32 },
     /// Condition that stops when the measurement gate with the id 'id' measures at least 'one' 'nb' times
     MaxOneSample { id: String, nb: u32 },
+    /// Condition that stops when the last measurement with the id 'id' gives 'one' (true for |1>)
+    LastOutcome { id: String, one: bool },
+    /// Condition that stops when the measurement gate with the id 'id' measures 'one' 'nb' times in a row
+    ConsecutiveOnes { id: String, nb: u32 },
+    /// Condition that stops when the measurement gate with the id 'id' measures 'zero' 'nb' times in a row
+    ConsecutiveZeros { id: String, nb: u32 },
+    /// Condition that stops when the probability of measuring |1> on 'qbit' is greater than 'threshold'
+    ProbabilityOfOneAbove { qbit: u8, threshold: f64 },
+    /// Condition that stops when the probability of measuring |1> on 'qbit' is lower than 'threshold'
+    ProbabilityOfOneBelow { qbit: u8, threshold: f64 },
+    /// Condition that compares the number of 'one' measured by the measurement gates 'lhs' and 'rhs'
+    CompareOnes { lhs: String, comparison: Comparison, rhs: String },
+    /// Condition that compares the number of 'zero' measured by the measurement gates 'lhs' and 'rhs'
+    CompareZeros { lhs: String, comparison: Comparison, rhs: String },
+    /// Condition that stops when the last register measurement with the id 'id' gives 'value'
+    RegisterValue { id: String, value: u64 },
+    /// Condition that stops when the register measurement with the id 'id' gives 'value' at least 'nb' times
+    RegisterValueCount { id: String, value: u64, nb: u32 },
+    /// Condition evaluated by the predicate registered with [`register_predicate`] under this name
+    Predicate(String),
+    Not(Box<StopCondition>),
     Or(Box<StopCondition>, Box<StopCondition>),
     And(Box<StopCondition>, Box<StopCondition>),
+    Xor(Box<StopCondition>, Box<StopCondition>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparison {
+    pub fn compare(&self, lhs: u32, rhs: u32) -> bool {
+        match self {
+            Comparison::Less => lhs < rhs,
+            Comparison::LessOrEqual => lhs <= rhs,
+            Comparison::Equal => lhs == rhs,
+            Comparison::NotEqual => lhs != rhs,
+            Comparison::GreaterOrEqual => lhs >= rhs,
+            Comparison::Greater => lhs > rhs,
+        }
+    }
+}
+
+/// Information about the execution available to the registered predicates
+pub trait ConditionContext {
+    fn get_nb_zero(&self, id: &str) -> u32;
+    fn get_nb_one(&self, id: &str) -> u32;
+    fn last_outcome(&self, id: &str) -> Option<bool>;
+    fn get_nb_consecutive(&self, id: &str, outcome: bool) -> u32;
+    fn get_register_count(&self, id: &str, value: u64) -> u32;
+    fn last_register_value(&self, id: &str) -> Option<u64>;
+    fn history(&self) -> &[MeasureRecord];
+    fn probability_of_one(&self, qbit: u8) -> f64;
+}
+
+impl<B: Backend> ConditionContext for ExecutionContext<B> {
+    fn get_nb_zero(&self, id: &str) -> u32 {
+        ExecutionContext::get_nb_zero(self, id)
+    }
+
+    fn get_nb_one(&self, id: &str) -> u32 {
+        ExecutionContext::get_nb_one(self, id)
+    }
+
+    fn last_outcome(&self, id: &str) -> Option<bool> {
+        ExecutionContext::last_outcome(self, id)
+    }
+
+    fn get_nb_consecutive(&self, id: &str, outcome: bool) -> u32 {
+        ExecutionContext::get_nb_consecutive(self, id, outcome)
+    }
+
+    fn get_register_count(&self, id: &str, value: u64) -> u32 {
+        ExecutionContext::get_register_count(self, id, value)
+    }
+
+    fn last_register_value(&self, id: &str) -> Option<u64> {
+        ExecutionContext::last_register_value(self, id)
+    }
+
+    fn history(&self) -> &[MeasureRecord] {
+        ExecutionContext::history(self)
+    }
+
+    fn probability_of_one(&self, qbit: u8) -> f64 {
+        self.current_state().probability_of_one(qbit)
+    }
+}
+
+/// A predicate receives the current number of iterations of the loop and the execution context
+pub type Predicate = dyn Fn(u32, &dyn ConditionContext) -> bool + Send + Sync;
+
+fn predicates() -> &'static RwLock<HashMap<String, Arc<Predicate>>> {
+    static PREDICATES: OnceLock<RwLock<HashMap<String, Arc<Predicate>>>> = OnceLock::new();
+    PREDICATES.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// Register a predicate that can be used with [`StopCondition::Predicate`].
+/// Only the name is stored in the condition so circuits using it can still be serialized.
+/// A predicate already registered with the same name is replaced.
+pub fn register_predicate(name: &str, predicate: impl Fn(u32, &dyn ConditionContext) -> bool + Send + Sync + 'static) {
+    predicates().write().unwrap().insert(name.to_string(), Arc::new(predicate));
+}
+
+pub fn unregister_predicate(name: &str) {
+    predicates().write().unwrap().remove(name);
+}
+
+fn find_predicate(name: &str) -> Result<Arc<Predicate>, String> {
+    predicates().read().unwrap()
+        .get(name)
+        .cloned()
+        .ok_or_else(|| format!("Unknown predicate '{}'", name))
 }
 
 
@@ -25,18 +147,355 @@ impl StopCondition {
     ///
     /// - nb_iterations : the current number of iterations of the loop
     /// - context : the execution context of the quantum program
-    pub fn is_end_of_loop(&self, nb_iterations: u32, context: &ExecutionContext) -> bool {
-        match self {
+    pub fn is_end_of_loop<B: Backend>(&self, nb_iterations: u32, context: &ExecutionContext<B>) -> Result<bool, String> {
+        self.evaluate(nb_iterations, context)
+    }
+
+    fn evaluate(&self, nb_iterations: u32, context: &dyn ConditionContext) -> Result<bool, String> {
+        let end_of_loop = match self {
             StopCondition::Once() => nb_iterations >= 1,
             StopCondition::MaxIteration(nb) => nb_iterations >= *nb,
             StopCondition::MaxZeroSampling { id, nb } => context.get_nb_zero(id) >= *nb,
             StopCondition::MaxOneSample { id, nb } => context.get_nb_one(id) >= *nb,
+            StopCondition::LastOutcome { id, one } => context.last_outcome(id) == Some(*one),
+            StopCondition::ConsecutiveOnes { id, nb } => context.get_nb_consecutive(id, true) >= *nb,
+            StopCondition::ConsecutiveZeros { id, nb } => context.get_nb_consecutive(id, false) >= *nb,
+            StopCondition::ProbabilityOfOneAbove { qbit, threshold } => context.probability_of_one(*qbit) > *threshold,
+            StopCondition::ProbabilityOfOneBelow { qbit, threshold } => context.probability_of_one(*qbit) < *threshold,
+            StopCondition::CompareOnes { lhs, comparison, rhs } => comparison.compare(context.get_nb_one(lhs), context.get_nb_one(rhs)),
+            StopCondition::CompareZeros { lhs, comparison, rhs } => comparison.compare(context.get_nb_zero(lhs), context.get_nb_zero(rhs)),
+            StopCondition::RegisterValue { id, value } => context.last_register_value(id) == Some(*value),
+            StopCondition::RegisterValueCount { id, value, nb } => context.get_register_count(id, *value) >= *nb,
+            StopCondition::Predicate(name) => (find_predicate(name)?)(nb_iterations, context),
+            StopCondition::Not(condition) => !condition.evaluate(nb_iterations, context)?,
             StopCondition::Or(lhs, rhs) => {
-                lhs.is_end_of_loop(nb_iterations, context) || rhs.is_end_of_loop(nb_iterations, context)
+                lhs.evaluate(nb_iterations, context)? || rhs.evaluate(nb_iterations, context)?
             }
             StopCondition::And(lhs, rhs) => {
-                lhs.is_end_of_loop(nb_iterations, context) && rhs.is_end_of_loop(nb_iterations, context)
+                lhs.evaluate(nb_iterations, context)? && rhs.evaluate(nb_iterations, context)?
+            }
+            StopCondition::Xor(lhs, rhs) => {
+                lhs.evaluate(nb_iterations, context)? != rhs.evaluate(nb_iterations, context)?
+            }
+        };
+        Ok(end_of_loop)
+    }
+
+    /// Return true if this condition uses a registered predicate. The value
+    /// of such a condition in the following iterations cannot be predicted.
+    pub(crate) fn has_predicate(&self) -> bool {
+        match self {
+            StopCondition::Predicate(_) => true,
+            StopCondition::Not(condition) => condition.has_predicate(),
+            StopCondition::Or(lhs, rhs) | StopCondition::And(lhs, rhs) | StopCondition::Xor(lhs, rhs) => {
+                lhs.has_predicate() || rhs.has_predicate()
+            }
+            _ => false,
+        }
+    }
+
+    /// Return true if this condition is met after a number of iterations whatever the
+    /// measurement results are. There is no need to check if such a condition is reachable
+    pub(crate) fn ends_by_iteration_count(&self) -> bool {
+        match self {
+            StopCondition::Once() | StopCondition::MaxIteration(_) => true,
+            StopCondition::Or(lhs, rhs) => lhs.ends_by_iteration_count() || rhs.ends_by_iteration_count(),
+            StopCondition::And(lhs, rhs) => lhs.ends_by_iteration_count() && rhs.ends_by_iteration_count(),
+            _ => false,
+        }
+    }
+
+    /// Check if this condition can still be met when the following iterations
+    /// repeat the `cycle` forever: same states at the start of each iteration
+    /// and same measurement results. `cycle` starts with the current state.
+    ///
+    /// Each part of the condition changes its value at most once as the number of
+    /// repeated cycles grows, so the condition only needs to be evaluated during
+    /// the next cycle and during the cycles around each of these changes.
+    pub(crate) fn is_unreachable<B: Backend>(&self, nb_iterations: u32, context: &ExecutionContext<B>, cycle: &[(&B, &[MeasureRecord])]) -> Result<bool, String> {
+        if self.has_predicate() || cycle.is_empty() {
+            return Ok(false);
+        }
+        let period = cycle.len() as u64;
+        let cycle_records: Vec<MeasureRecord> = cycle.iter().flat_map(|(_, records)| records.iter().cloned()).collect();
+        let mut candidates = vec![1];
+        self.add_changes(nb_iterations, period, context, &cycle_records, &mut candidates);
+        for nb_cycles in candidates {
+            for nb_additional in (nb_cycles.saturating_sub(1) * period + 1)..=((nb_cycles + 1) * period) {
+                let projected = ProjectedContext { context, cycle, nb_additional };
+                let projected_iterations = (nb_iterations as u64 + nb_additional).min(u32::MAX as u64) as u32;
+                if self.evaluate(projected_iterations, &projected)? {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Add the numbers of additional cycles of `period` iterations
+    /// after which a part of this condition may change
+    fn add_changes(&self, nb_iterations: u32, period: u64, context: &dyn ConditionContext, last_iteration: &[MeasureRecord], changes: &mut Vec<u64>) {
+        let count = |id: &str, outcome: bool| last_iteration.iter().filter(|r| r.id == id && r.outcome == outcome).count() as u64;
+        let register_count = |id: &str, value: u64| last_iteration.iter().filter(|r| r.id == id && r.register_value == Some(value)).count() as u64;
+        match self {
+            StopCondition::Once() => changes.push(1),
+            StopCondition::MaxIteration(nb) => add_threshold(nb_iterations, period, *nb, changes),
+            StopCondition::MaxZeroSampling { id, nb } => add_threshold(context.get_nb_zero(id), count(id, false), *nb, changes),
+            StopCondition::MaxOneSample { id, nb } => add_threshold(context.get_nb_one(id), count(id, true), *nb, changes),
+            StopCondition::ConsecutiveOnes { id, nb } => {
+                add_threshold(context.get_nb_consecutive(id, true), count(id, true), *nb, changes)
+            }
+            StopCondition::ConsecutiveZeros { id, nb } => {
+                add_threshold(context.get_nb_consecutive(id, false), count(id, false), *nb, changes)
+            }
+            StopCondition::RegisterValueCount { id, value, nb } => {
+                add_threshold(context.get_register_count(id, *value), register_count(id, *value), *nb, changes)
+            }
+            StopCondition::CompareOnes { lhs, rhs, .. } => {
+                add_crossing(context.get_nb_one(lhs), count(lhs, true), context.get_nb_one(rhs), count(rhs, true), changes)
+            }
+            StopCondition::CompareZeros { lhs, rhs, .. } => {
+                add_crossing(context.get_nb_zero(lhs), count(lhs, false), context.get_nb_zero(rhs), count(rhs, false), changes)
+            }
+            StopCondition::Not(condition) => condition.add_changes(nb_iterations, period, context, last_iteration, changes),
+            StopCondition::Or(lhs, rhs) | StopCondition::And(lhs, rhs) | StopCondition::Xor(lhs, rhs) => {
+                lhs.add_changes(nb_iterations, period, context, last_iteration, changes);
+                rhs.add_changes(nb_iterations, period, context, last_iteration, changes);
             }
+            StopCondition::LastOutcome { .. }
+            | StopCondition::RegisterValue { .. }
+            | StopCondition::ProbabilityOfOneAbove { .. }
+            | StopCondition::ProbabilityOfOneBelow { .. }
+            | StopCondition::Predicate(_) => {}
         }
     }
 }
+
+/// Add the number of additional iterations needed for a value
+/// growing by 'step' at each iteration to reach 'threshold'
+fn add_threshold(value: u32, step: u64, threshold: u32, changes: &mut Vec<u64>) {
+    if step > 0 && value < threshold {
+        changes.push(((threshold - value) as u64).div_ceil(step));
+    }
+}
+
+/// Add the numbers of additional iterations around the one where
+/// two values growing at a constant rate become equal
+fn add_crossing(lhs: u32, lhs_step: u64, rhs: u32, rhs_step: u64, changes: &mut Vec<u64>) {
+    let difference = lhs as i64 - rhs as i64;
+    let step_difference = rhs_step as i64 - lhs_step as i64;
+    if step_difference == 0 || difference.signum() != step_difference.signum() {
+        return;
+    }
+    let crossing = difference / step_difference;
+    changes.extend([crossing, crossing + 1].iter().filter(|n| **n > 0).map(|n| *n as u64));
+}
+
+/// Context after 'nb_additional' iterations repeating 'cycle'
+struct ProjectedContext<'a, 'b, B: Backend> {
+    context: &'a ExecutionContext<B>,
+    cycle: &'a [(&'b B, &'b [MeasureRecord])],
+    nb_additional: u64,
+}
+
+impl<'a, 'b, B: Backend> ProjectedContext<'a, 'b, B> {
+    fn nb_cycles(&self) -> u64 {
+        self.nb_additional / self.cycle.len() as u64
+    }
+
+    /// Number of iterations of the last cycle that is not complete
+    fn partial(&self) -> usize {
+        (self.nb_additional % self.cycle.len() as u64) as usize
+    }
+
+    fn records(iterations: &'a [(&'b B, &'b [MeasureRecord])]) -> impl DoubleEndedIterator<Item=&'b MeasureRecord> + Clone + 'a {
+        iterations.iter().flat_map(|(_, records)| records.iter())
+    }
+
+    /// The measurements of the projected iterations from the last one, the whole
+    /// cycle being included once at most since the following ones are identical
+    fn latest(&self) -> impl Iterator<Item=&'b MeasureRecord> + 'a {
+        let whole = if self.nb_cycles() > 0 { self.cycle } else { &[] };
+        Self::records(&self.cycle[..self.partial()]).rev().chain(Self::records(whole).rev())
+    }
+
+    fn project(&self, value: u32, matches: impl Fn(&MeasureRecord) -> bool) -> u32 {
+        let per_cycle = Self::records(self.cycle).filter(|r| matches(r)).count() as u64;
+        let in_partial = Self::records(&self.cycle[..self.partial()]).filter(|r| matches(r)).count() as u64;
+        (value as u64 + per_cycle * self.nb_cycles() + in_partial).min(u32::MAX as u64) as u32
+    }
+}
+
+impl<'a, 'b, B: Backend> ConditionContext for ProjectedContext<'a, 'b, B> {
+    fn get_nb_zero(&self, id: &str) -> u32 {
+        self.project(self.context.get_nb_zero(id), |r| r.id == id && !r.outcome)
+    }
+
+    fn get_nb_one(&self, id: &str) -> u32 {
+        self.project(self.context.get_nb_one(id), |r| r.id == id && r.outcome)
+    }
+
+    fn last_outcome(&self, id: &str) -> Option<bool> {
+        self.latest().find(|r| r.id == id).map(|r| r.outcome).or_else(|| self.context.last_outcome(id))
+    }
+
+    /// If the projected iterations only measured 'outcome' the count keeps
+    /// growing, otherwise it is the number of the last ones
+    fn get_nb_consecutive(&self, id: &str, outcome: bool) -> u32 {
+        if self.latest().filter(|r| r.id == id).all(|r| r.outcome == outcome) {
+            self.project(self.context.get_nb_consecutive(id, outcome), |r| r.id == id && r.outcome == outcome)
+        } else {
+            self.latest().filter(|r| r.id == id).take_while(|r| r.outcome == outcome).count() as u32
+        }
+    }
+
+    fn get_register_count(&self, id: &str, value: u64) -> u32 {
+        self.project(self.context.get_register_count(id, value), |r| r.id == id && r.register_value == Some(value))
+    }
+
+    fn last_register_value(&self, id: &str) -> Option<u64> {
+        match self.latest().find(|r| r.id == id) {
+            Some(record) => record.register_value,
+            None => self.context.last_register_value(id),
+        }
+    }
+
+    /// The history of the projected iterations is not available
+    fn history(&self) -> &[MeasureRecord] {
+        self.context.history()
+    }
+
+    fn probability_of_one(&self, qbit: u8) -> f64 {
+        self.cycle[self.partial()].0.probability_of_one(qbit)
+    }
+}
+
+
+#[cfg(test)]
+mod tests_condition {
+    use crate::common_gate::CommonGate::{Hadamard, Not};
+    use crate::computer::QuantumComputer;
+
+    use super::*;
+
+    #[test]
+    fn repeat_until_success() {
+        let computer = QuantumComputer::new(1);
+        let inner = computer.new_circuit_builder()
+            .add_operation(Hadamard(0))
+            .add_measure("q0", 0)
+            .build()
+            .unwrap();
+        let circuit = computer.new_circuit_builder()
+            .add_loop(inner, StopCondition::LastOutcome { id: "q0".to_string(), one: true })
+            .build()
+            .unwrap();
+
+        for _ in 0..10 {
+            let result = circuit.execute(&computer.zero_state()).unwrap();
+            let history = result.history();
+            assert!(history.last().unwrap().outcome);
+            assert!(history[..history.len() - 1].iter().all(|r| !r.outcome));
+            for (iteration, record) in history.iter().enumerate() {
+                assert_eq!(record.iterations, vec![iteration as u32]);
+                assert!((record.probability - 0.5).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn consecutive_ones() {
+        let computer = QuantumComputer::new(1);
+        let inner = computer.new_circuit_builder()
+            .add_operation(Not(0))
+            .add_measure("q0", 0)
+            .add_operation(Not(0))
+            .build()
+            .unwrap();
+        let circuit = computer.new_circuit_builder()
+            .add_loop(inner, StopCondition::ConsecutiveOnes { id: "q0".to_string(), nb: 3 })
+            .build()
+            .unwrap();
+
+        let result = circuit.execute(&computer.zero_state()).unwrap();
+        assert_eq!(result.history().len(), 3);
+        assert_eq!(result.get_nb_consecutive("q0", true), 3);
+        assert_eq!(result.get_nb_consecutive("q0", false), 0);
+        assert_eq!(result.history()[2].probability, 1.0);
+    }
+
+    #[test]
+    fn not_and_xor() {
+        let computer = QuantumComputer::new(1);
+        let context = ExecutionContext::initialize(computer.zero_state());
+        let once = || Box::new(StopCondition::Once());
+        let max_2 = || Box::new(StopCondition::MaxIteration(2));
+
+        assert!(StopCondition::Not(once()).is_end_of_loop(0, &context).unwrap());
+        assert!(!StopCondition::Not(once()).is_end_of_loop(1, &context).unwrap());
+        assert!(StopCondition::Xor(once(), max_2()).is_end_of_loop(1, &context).unwrap());
+        assert!(!StopCondition::Xor(once(), max_2()).is_end_of_loop(2, &context).unwrap());
+    }
+
+    #[test]
+    fn probability_threshold() {
+        let computer = QuantumComputer::new(1);
+        let inner = computer.new_circuit_builder()
+            .add_operation(Not(0))
+            .build()
+            .unwrap();
+        let above = StopCondition::ProbabilityOfOneAbove { qbit: 0, threshold: 0.99 };
+        let circuit = computer.new_circuit_builder()
+            .add_loop(inner, StopCondition::Or(Box::new(above), Box::new(StopCondition::MaxIteration(10))))
+            .build()
+            .unwrap();
+
+        let result = circuit.execute(&computer.zero_state()).unwrap();
+        assert!(result.current_state().probability_of_one(0) > 0.99);
+    }
+
+    #[test]
+    fn compare_measurements() {
+        let computer = QuantumComputer::new(2);
+        let inner = computer.new_circuit_builder()
+            .add_operation(Not(0))
+            .add_measure("q0", 0)
+            .add_measure("q1", 1)
+            .build()
+            .unwrap();
+        let condition = StopCondition::CompareOnes { lhs: "q0".to_string(), comparison: Comparison::Greater, rhs: "q1".to_string() };
+        let circuit = computer.new_circuit_builder()
+            .add_loop(inner, condition)
+            .build()
+            .unwrap();
+
+        let result = circuit.execute(&computer.zero_state()).unwrap();
+        assert_eq!(result.get_nb_one("q0"), 1);
+        assert_eq!(result.get_nb_one("q1"), 0);
+    }
+
+    #[test]
+    fn registered_predicate() {
+        register_predicate("tests_condition::three_iterations", |nb_iterations, _| nb_iterations >= 3);
+        let condition = StopCondition::Predicate("tests_condition::three_iterations".to_string());
+        let serialized = serde_json::to_string(&condition).unwrap();
+        let condition: StopCondition = serde_json::from_str(&serialized).unwrap();
+
+        let computer = QuantumComputer::new(1);
+        let inner = computer.new_circuit_builder()
+            .add_measure("q0", 0)
+            .build()
+            .unwrap();
+        let circuit = computer.new_circuit_builder()
+            .add_loop(inner, condition)
+            .build()
+            .unwrap();
+
+        let result = circuit.execute(&computer.zero_state()).unwrap();
+        assert_eq!(result.get_nb_zero("q0"), 3);
+
+        let unknown = StopCondition::Predicate("tests_condition::unknown".to_string());
+        let context = ExecutionContext::initialize(computer.zero_state());
+        assert!(unknown.is_end_of_loop(0, &context).is_err());
+    }
+}
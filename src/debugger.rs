@@ -0,0 +1,392 @@
+use std::fmt::{Display, Formatter};
+
+use crate::_loop::{Loop, LoopGuard};
+use crate::backend::Backend;
+use crate::circuit::Circuit;
+use crate::execution::{ExecutionContext, ExecutionError, ExecutionLimits};
+use crate::measure::MeasureCount;
+use crate::operation::{CircuitElement, QuantumOperation};
+use crate::state::QuantumState;
+
+/// Position of an element in a circuit: the index of the element
+/// in each of the nested circuits, starting with the outermost one.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct ElementPath(pub Vec<usize>);
+
+impl ElementPath {
+    pub fn new(indices: &[usize]) -> Self {
+        ElementPath(indices.to_vec())
+    }
+}
+
+impl Display for ElementPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let indices: Vec<String> = self.0.iter().map(|i| i.to_string()).collect();
+        write!(f, "/{}", indices.join("/"))
+    }
+}
+
+/// What has been executed during a step
+#[derive(Clone, PartialEq, Debug)]
+pub enum StepKind {
+    Gate,
+    /// A measurement and its outcome (true for |1>)
+    Measure { id: String, outcome: bool },
+    /// A register measurement and the measured value
+    RegisterMeasure { id: String, value: u64 },
+    /// A postselection and the probability of the selected outcome
+    Postselect { id: String, probability: f64 },
+    /// An iteration of a loop has been completed. `iteration` is the number of completed iterations
+    LoopIteration { iteration: u32 },
+    /// The stop condition of a loop has been met
+    LoopEnd { nb_iterations: u32 },
+    /// A sub-circuit has been executed as a whole
+    SubCircuit { name: String },
+}
+
+/// Information about the last executed step
+#[derive(Clone, PartialEq, Debug)]
+pub struct Step {
+    /// path of the executed element (the loop for loop steps)
+    pub path: ElementPath,
+    /// current iteration (starting at 0) of each loop enclosing the element
+    pub iterations: Vec<u32>,
+    pub kind: StepKind,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Breakpoint {
+    /// Break after the execution of the element at the given path
+    Path(ElementPath),
+    /// Break after each measurement (single qbit or register) with the given id
+    Measure(String),
+}
+
+impl Breakpoint {
+    fn matches(&self, step: &Step) -> bool {
+        match (self, &step.kind) {
+            (Breakpoint::Path(path), _) => *path == step.path,
+            (Breakpoint::Measure(id), StepKind::Measure { id: measure_id, .. }) => id == measure_id,
+            (Breakpoint::Measure(id), StepKind::RegisterMeasure { id: measure_id, .. }) => id == measure_id,
+            (Breakpoint::Measure(_), _) => false,
+        }
+    }
+}
+
+struct LoopFrame<'a, B: Backend> {
+    element: &'a CircuitElement,
+    the_loop: &'a Loop,
+    guard: LoopGuard<B>,
+    iteration: u32,
+    exiting: bool,
+}
+
+struct Frame<'a, B: Backend> {
+    circuit: &'a Circuit,
+    next: usize,
+    loop_frame: Option<LoopFrame<'a, B>>,
+}
+
+/// Execute a circuit one element at a time.
+///
+/// The cursor is an iterator that yields a [`Step`] after each gate and measurement,
+/// after each iteration of a loop and when a loop ends. The state of the execution
+/// can be inspected between steps with [`context`](ExecutionCursor::context).
+pub struct ExecutionCursor<'a, B: Backend = QuantumState> {
+    context: ExecutionContext<B>,
+    frames: Vec<Frame<'a, B>>,
+    breakpoints: Vec<Breakpoint>,
+    finished: bool,
+}
+
+impl<'a, B: Backend> ExecutionCursor<'a, B> {
+    pub(crate) fn new(circuit: &'a Circuit, initial_state: B) -> Self {
+        let root = Frame { circuit, next: 0, loop_frame: None };
+        ExecutionCursor { context: circuit.new_context(initial_state), frames: vec![root], breakpoints: vec![], finished: false }
+    }
+
+    pub(crate) fn set_limits(&mut self, limits: ExecutionLimits) {
+        self.context.set_limits(limits);
+    }
+
+    pub fn context(&self) -> &ExecutionContext<B> {
+        &self.context
+    }
+
+    pub fn current_state(&self) -> &B {
+        self.context.current_state()
+    }
+
+    pub fn get_count(&self, id: &str) -> Option<&MeasureCount> {
+        self.context.get_count(id)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> &mut Self {
+        self.breakpoints.push(breakpoint);
+        self
+    }
+
+    pub fn remove_breakpoint(&mut self, breakpoint: &Breakpoint) -> &mut Self {
+        self.breakpoints.retain(|b| b != breakpoint);
+        self
+    }
+
+    /// Execute the circuit until a breakpoint is reached.
+    /// Return the step that matched the breakpoint or None if the execution is finished.
+    pub fn run_to_breakpoint(&mut self) -> Result<Option<Step>, ExecutionError> {
+        while let Some(step) = self.next() {
+            let step = step?;
+            if self.breakpoints.iter().any(|b| b.matches(&step)) {
+                return Ok(Some(step));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Execute the remaining elements and return the final context
+    pub fn finish(mut self) -> Result<ExecutionContext<B>, ExecutionError> {
+        for step in self.by_ref() {
+            step?;
+        }
+        Ok(self.context)
+    }
+
+    /// Build a step for the element executed in the first `depth` frames
+    fn make_step(&self, kind: StepKind, depth: usize) -> Step {
+        let frames = &self.frames[..depth];
+        let path = ElementPath(frames.iter().map(|f| f.next - 1).collect());
+        let iterations = frames.iter().filter_map(|f| f.loop_frame.as_ref()).map(|l| l.iteration).collect();
+        Step { path, iterations, kind }
+    }
+
+    /// Execute the next step. The loops are unrolled here instead of using
+    /// `Loop::apply` so that the execution can be interrupted inside a loop,
+    /// the same [`LoopGuard`] checks the limits and the loops that can never end.
+    fn execute_step(&mut self) -> Result<Option<Step>, ExecutionError> {
+        loop {
+            let depth = self.frames.len();
+            let frame = match self.frames.last_mut() {
+                None => return Ok(None),
+                Some(frame) => frame,
+            };
+
+            if let Some(loop_frame) = &frame.loop_frame {
+                if loop_frame.exiting {
+                    let nb_iterations = loop_frame.iteration;
+                    let element = loop_frame.element;
+                    self.frames.pop();
+                    self.context.exit_loop();
+                    self.context.exit_element(element)?;
+                    return Ok(Some(self.make_step(StepKind::LoopEnd { nb_iterations }, depth - 1)));
+                }
+            }
+
+            let circuit: &'a Circuit = frame.circuit;
+            if frame.next < circuit.elements.len() {
+                let index = frame.next;
+                let element = &circuit.elements[index];
+                frame.next += 1;
+                self.context.enter_element(index, element)?;
+                match element {
+                    CircuitElement::Gate(gate) => {
+                        gate.apply(&mut self.context)?;
+                        self.context.exit_element(element)?;
+                        return Ok(Some(self.make_step(StepKind::Gate, depth)));
+                    }
+                    CircuitElement::Measure(measure) => {
+                        measure.apply(&mut self.context)?;
+                        self.context.exit_element(element)?;
+                        let outcome = self.context.last_outcome(&measure.id) == Some(true);
+                        return Ok(Some(self.make_step(StepKind::Measure { id: measure.id.clone(), outcome }, depth)));
+                    }
+                    CircuitElement::RegisterMeasure(measure) => {
+                        measure.apply(&mut self.context)?;
+                        self.context.exit_element(element)?;
+                        let value = self.context.last_register_value(&measure.id).unwrap_or(0);
+                        return Ok(Some(self.make_step(StepKind::RegisterMeasure { id: measure.id.clone(), value }, depth)));
+                    }
+                    CircuitElement::Postselect(postselect) => {
+                        postselect.apply(&mut self.context)?;
+                        self.context.exit_element(element)?;
+                        let probability = self.context.last_postselection_probability(&postselect.id).unwrap_or(0.0);
+                        return Ok(Some(self.make_step(StepKind::Postselect { id: postselect.id.clone(), probability }, depth)));
+                    }
+                    CircuitElement::SubCircuit(call) => {
+                        call.apply(&mut self.context)?;
+                        self.context.exit_element(element)?;
+                        return Ok(Some(self.make_step(StepKind::SubCircuit { name: call.name.clone() }, depth)));
+                    }
+                    CircuitElement::Loop(l) => {
+                        if l.stop_condition.is_end_of_loop(0, &self.context)? {
+                            self.context.exit_element(element)?;
+                            return Ok(Some(self.make_step(StepKind::LoopEnd { nb_iterations: 0 }, depth)));
+                        }
+                        self.context.enter_loop();
+                        let mut guard = LoopGuard::new(l);
+                        guard.before_iteration(l, 0, &mut self.context)?;
+                        let loop_frame = LoopFrame { element, the_loop: l, guard, iteration: 0, exiting: false };
+                        self.frames.push(Frame { circuit: &l.circuit, next: 0, loop_frame: Some(loop_frame) });
+                        continue;
+                    }
+                }
+            }
+
+            match &mut frame.loop_frame {
+                None => {
+                    self.frames.pop();
+                }
+                Some(loop_frame) => {
+                    loop_frame.iteration += 1;
+                    let iteration = loop_frame.iteration;
+                    self.context.loop_iteration_completed(iteration)?;
+                    loop_frame.guard.after_iteration(&self.context);
+                    loop_frame.exiting = loop_frame.the_loop.stop_condition.is_end_of_loop(iteration, &self.context)?;
+                    if !loop_frame.exiting {
+                        loop_frame.guard.before_iteration(loop_frame.the_loop, iteration, &mut self.context)?;
+                    }
+                    frame.next = 0;
+                    return Ok(Some(self.make_step(StepKind::LoopIteration { iteration }, depth - 1)));
+                }
+            }
+        }
+    }
+}
+
+impl<'a, B: Backend> Iterator for ExecutionCursor<'a, B> {
+    type Item = Result<Step, ExecutionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.execute_step() {
+            Ok(Some(step)) => Some(Ok(step)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(e) => {
+                self.finished = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests_debugger {
+    use crate::common_gate::CommonGate::{Hadamard, Not};
+    use crate::computer::QuantumComputer;
+    use crate::condition::StopCondition;
+
+    use super::*;
+
+    fn loop_circuit(computer: &QuantumComputer) -> Circuit {
+        let inner = computer.new_circuit_builder()
+            .add_operation(Not(0))
+            .add_measure("q0", 0)
+            .build()
+            .unwrap();
+        computer.new_circuit_builder()
+            .add_operation(Hadamard(1))
+            .add_loop(inner, StopCondition::MaxIteration(2))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn steps_of_a_loop() {
+        let computer = QuantumComputer::new(2);
+        let circuit = loop_circuit(&computer);
+
+        let steps: Vec<Step> = circuit.debug(&computer.zero_state()).map(|s| s.unwrap()).collect();
+        let summary: Vec<(String, Vec<u32>)> = steps.iter().map(|s| (s.path.to_string(), s.iterations.clone())).collect();
+
+        assert_eq!(summary, vec![
+            ("/0".to_string(), vec![]),
+            ("/1/0".to_string(), vec![0]),
+            ("/1/1".to_string(), vec![0]),
+            ("/1".to_string(), vec![]),
+            ("/1/0".to_string(), vec![1]),
+            ("/1/1".to_string(), vec![1]),
+            ("/1".to_string(), vec![]),
+            ("/1".to_string(), vec![]),
+        ]);
+        assert_eq!(steps[2].kind, StepKind::Measure { id: "q0".to_string(), outcome: true });
+        assert_eq!(steps[3].kind, StepKind::LoopIteration { iteration: 1 });
+        assert_eq!(steps[5].kind, StepKind::Measure { id: "q0".to_string(), outcome: false });
+        assert_eq!(steps[7].kind, StepKind::LoopEnd { nb_iterations: 2 });
+    }
+
+    #[test]
+    fn break_on_measure_and_path() {
+        let computer = QuantumComputer::new(2);
+        let circuit = loop_circuit(&computer);
+
+        let mut cursor = circuit.debug(&computer.zero_state());
+        cursor.add_breakpoint(Breakpoint::Measure("q0".to_string()))
+            .add_breakpoint(Breakpoint::Path(ElementPath::new(&[1, 0])));
+
+        let step = cursor.run_to_breakpoint().unwrap().unwrap();
+        assert_eq!(step.path, ElementPath::new(&[1, 0]));
+        assert!(cursor.current_state().probability_of_one(0) > 0.99);
+
+        let step = cursor.run_to_breakpoint().unwrap().unwrap();
+        assert_eq!(step.kind, StepKind::Measure { id: "q0".to_string(), outcome: true });
+        assert_eq!(cursor.get_count("q0").unwrap().nb_one, 1);
+
+        cursor.remove_breakpoint(&Breakpoint::Path(ElementPath::new(&[1, 0])));
+        let step = cursor.run_to_breakpoint().unwrap().unwrap();
+        assert_eq!(step.iterations, vec![1]);
+
+        assert_eq!(cursor.run_to_breakpoint().unwrap(), None);
+        assert!(cursor.is_finished());
+    }
+
+    #[test]
+    fn finish_gives_the_same_counts_as_execute() {
+        let computer = QuantumComputer::new(2);
+        let circuit = loop_circuit(&computer);
+
+        let mut cursor = circuit.debug(&computer.zero_state());
+        cursor.next().unwrap().unwrap();
+        let context = cursor.finish().unwrap();
+
+        assert_eq!(context.get_nb_one("q0"), 1);
+        assert_eq!(context.get_nb_zero("q0"), 1);
+    }
+
+    #[test]
+    fn loops_that_never_end_are_stopped() {
+        let computer = QuantumComputer::new(1);
+        let inner = computer.new_circuit_builder()
+            .add_operation(Not(0))
+            .add_measure("m", 0)
+            .build()
+            .unwrap();
+        let circuit = computer.new_circuit_builder()
+            .add_loop(inner.clone(), StopCondition::ConsecutiveOnes { id: "m".to_string(), nb: 2 })
+            .build()
+            .unwrap();
+        let mut cursor = circuit.debug(&computer.zero_state());
+        cursor.add_breakpoint(Breakpoint::Path(ElementPath::new(&[0])));
+        let step = cursor.run_to_breakpoint().unwrap().unwrap();
+        assert_eq!(step.kind, StepKind::LoopIteration { iteration: 1 });
+        let error = cursor.finish().err().unwrap();
+        assert!(matches!(error, ExecutionError::UnreachableCondition { .. }));
+
+        let never = StopCondition::MaxOneSample { id: "m".to_string(), nb: u32::MAX };
+        let circuit = computer.new_circuit_builder()
+            .add_loop(inner, never)
+            .build()
+            .unwrap();
+        let limits = ExecutionLimits::default().with_max_total_iterations(10);
+        let error = circuit.debug_with_limits(QuantumState::from(&computer.zero_state()), limits).finish().err().unwrap();
+        assert!(matches!(error, ExecutionError::GlobalIterationLimit { limit: 10, .. }));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::gate::check_for_no_duplicate;
+use crate::linalg::{svd, Matrix};
+use crate::state::QuantumState;
+
+/// Singular values below this value are ignored in the entropies
+const EPSILON: f64 = 1e-12;
+
+/// Schmidt decomposition of a state for a bipartition A|B:
+/// |ψ> = Σ coefficients[k] |left_k>|right_k>.
+///
+/// The states are the columns of `left` and `right`, indexed like the values of a
+/// register measurement: the first qbit of each part gives the most significant bit.
+pub struct SchmidtDecomposition {
+    /// Schmidt coefficients in decreasing order
+    pub coefficients: Vec<f64>,
+    /// states of the part A
+    pub left: Matrix,
+    /// states of the part B, the qbits that are not in A in increasing order
+    pub right: Matrix,
+}
+
+impl SchmidtDecomposition {
+    /// Return the number of non-zero coefficients
+    pub fn rank(&self) -> usize {
+        self.coefficients.iter().filter(|c| **c > EPSILON).count()
+    }
+
+    /// Return the von Neumann entropy of the parts
+    pub fn entropy(&self) -> f64 {
+        entropy_of(self.coefficients.iter().map(|c| c * c))
+    }
+
+    /// Return the Rényi entropy of order `alpha` of the parts
+    pub fn renyi_entropy(&self, alpha: f64) -> f64 {
+        renyi_entropy_of(self.coefficients.iter().map(|c| c * c), alpha)
+    }
+}
+
+/// Return the von Neumann entropy -Tr(ρ log2 ρ) of a density matrix, in bits
+pub fn von_neumann_entropy(density_matrix: &Matrix) -> f64 {
+    entropy_of(svd(density_matrix).singular_values.into_iter())
+}
+
+/// Return the Rényi entropy log2(Tr(ρ^alpha))/(1-alpha) of a density matrix, in bits.
+/// The order 1 gives the von Neumann entropy.
+pub fn renyi_entropy(density_matrix: &Matrix, alpha: f64) -> f64 {
+    renyi_entropy_of(svd(density_matrix).singular_values.into_iter(), alpha)
+}
+
+fn entropy_of(eigenvalues: impl Iterator<Item=f64>) -> f64 {
+    eigenvalues.filter(|p| *p > EPSILON).map(|p| -p * p.log2()).sum::<f64>().max(0.0)
+}
+
+fn renyi_entropy_of(eigenvalues: impl Iterator<Item=f64>, alpha: f64) -> f64 {
+    if (alpha - 1.0).abs() < EPSILON {
+        return entropy_of(eigenvalues);
+    }
+    let sum: f64 = eigenvalues.filter(|p| *p > EPSILON).map(|p| p.powf(alpha)).sum();
+    (sum.log2() / (1.0 - alpha)).max(0.0)
+}
+
+impl QuantumState {
+    fn check_subsystem(&self, qbits: &[u8]) -> Result<(), String> {
+        for qbit in qbits.iter() {
+            if *qbit >= self.nb_qbits() {
+                return Err(format!("Index to high {}", qbit));
+            }
+        }
+        check_for_no_duplicate(qbits.to_vec())
+    }
+
+    /// Return the amplitudes as a matrix whose rows are indexed by the value
+    /// of the given qbits and the columns by the value of the other qbits
+    fn bipartition(&self, qbits: &[u8]) -> Result<Matrix, String> {
+        self.check_subsystem(qbits)?;
+        let others: Vec<u8> = (0..self.nb_qbits()).filter(|q| !qbits.contains(q)).collect();
+        let value_of = |basis_state: usize, part: &[u8]| {
+            part.iter().fold(0, |value, qbit| (value << 1) | ((basis_state & self.mask(*qbit)) != 0) as usize)
+        };
+
+        let mut result = Matrix::zero(1 << qbits.len(), 1 << others.len());
+        for (i, amplitude) in self.iter().enumerate() {
+            result.set(value_of(i, qbits), value_of(i, &others), *amplitude);
+        }
+        Ok(result)
+    }
+
+    /// Return the density matrix of the given qbits, the other ones being traced out.
+    /// The first qbit gives the most significant bit of the row and column indices.
+    pub fn reduced_density_matrix(&self, qbits: &[u8]) -> Result<Matrix, String> {
+        let amplitudes = self.bipartition(qbits)?;
+        Ok(amplitudes.mul(&amplitudes.adjoint()))
+    }
+
+    /// Trace out the given qbits and return the density matrix of the remaining ones
+    pub fn partial_trace(&self, traced_out: &[u8]) -> Result<Matrix, String> {
+        self.check_subsystem(traced_out)?;
+        let kept: Vec<u8> = (0..self.nb_qbits()).filter(|q| !traced_out.contains(q)).collect();
+        self.reduced_density_matrix(&kept)
+    }
+
+    /// Return the Schmidt decomposition for the bipartition between the given qbits and the other ones
+    pub fn schmidt_decomposition(&self, qbits: &[u8]) -> Result<SchmidtDecomposition, String> {
+        let decomposition = svd(&self.bipartition(qbits)?);
+        let mut right = decomposition.v;
+        for row in 0..right.nb_rows() {
+            for column in 0..right.nb_columns() {
+                right.set(row, column, right.get(row, column).conj());
+            }
+        }
+        Ok(SchmidtDecomposition { coefficients: decomposition.singular_values, left: decomposition.u, right })
+    }
+
+    /// Return the entanglement entropy, in bits, between the given qbits and the other ones
+    pub fn entanglement_entropy(&self, qbits: &[u8]) -> Result<f64, String> {
+        self.schmidt_decomposition(qbits).map(|s| s.entropy())
+    }
+
+    /// Return the Rényi entropy of order `alpha`, in bits, between the given qbits and the other ones
+    pub fn renyi_entropy(&self, qbits: &[u8], alpha: f64) -> Result<f64, String> {
+        self.schmidt_decomposition(qbits).map(|s| s.renyi_entropy(alpha))
+    }
+}
+
+
+#[cfg(test)]
+mod tests_entanglement {
+    use crate::backend::Backend;
+    use crate::base_gate::BaseGate;
+    use crate::computer::QuantumComputer;
+    use num_complex::Complex64;
+
+    use super::*;
+
+    /// Rebuild the amplitude of `left_value`/`right_value` from a Schmidt decomposition
+    fn schmidt_amplitude(schmidt: &SchmidtDecomposition, left_value: usize, right_value: usize) -> Complex64 {
+        schmidt.coefficients.iter()
+            .enumerate()
+            .map(|(k, c)| schmidt.left.get(left_value, k) * schmidt.right.get(right_value, k) * c)
+            .sum()
+    }
+
+    #[test]
+    fn bell_state_is_maximally_entangled() {
+        let computer = QuantumComputer::new(2);
+        let result = computer.bell_state().build().unwrap().execute(&computer.zero_state()).unwrap();
+        let state = result.current_state();
+
+        let density = state.partial_trace(&[1]).unwrap();
+        assert!((density.get(0, 0).re - 0.5).abs() < 1e-9);
+        assert!((density.get(1, 1).re - 0.5).abs() < 1e-9);
+        assert!(density.get(0, 1).norm() < 1e-9);
+        assert!((von_neumann_entropy(&density) - 1.0).abs() < 1e-9);
+        assert!((state.entanglement_entropy(&[0]).unwrap() - 1.0).abs() < 1e-9);
+        assert!((state.renyi_entropy(&[1], 2.0).unwrap() - 1.0).abs() < 1e-9);
+        assert_eq!(state.schmidt_decomposition(&[0]).unwrap().rank(), 2);
+    }
+
+    #[test]
+    fn product_state_is_not_entangled() {
+        let mut state = QuantumComputer::new(3).zero_state();
+        state.apply_gate(&BaseGate::Hadamard(0), &[]).unwrap();
+        state.apply_gate(&BaseGate::Hadamard(2), &[]).unwrap();
+        state.apply_gate(&BaseGate::Not(1), &[2]).unwrap();
+
+        assert!(state.entanglement_entropy(&[0]).unwrap().abs() < 1e-9);
+        assert!((state.entanglement_entropy(&[2]).unwrap() - 1.0).abs() < 1e-9);
+        let density = state.reduced_density_matrix(&[0]).unwrap();
+        assert!(renyi_entropy(&density, 2.0).abs() < 1e-9);
+        assert!(state.partial_trace(&[3]).is_err());
+    }
+
+    #[test]
+    fn schmidt_decomposition_rebuilds_the_state() {
+        let mut state = QuantumComputer::new(3).zero_state();
+        state.apply_gate(&BaseGate::Hadamard(0), &[]).unwrap();
+        state.apply_gate(&BaseGate::Hadamard(1), &[0]).unwrap();
+        state.apply_gate(&BaseGate::S(1), &[]).unwrap();
+        state.apply_gate(&BaseGate::Not(2), &[1]).unwrap();
+
+        let schmidt = state.schmidt_decomposition(&[2, 0]).unwrap();
+        for i in 0..8 {
+            let left = (((i & 1) != 0) as usize) << 1 | (((i & 4) != 0) as usize);
+            let right = ((i & 2) != 0) as usize;
+            assert!((schmidt_amplitude(&schmidt, left, right) - state[i]).norm() < 1e-9);
+        }
+    }
+}
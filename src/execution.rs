@@ -1,30 +1,139 @@
-use std::collections::HashMap;
-use std::ops::Sub;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use num_complex::Complex64;
-use num_traits::One;
-
-use crate::execution::State::{Measured, NotMeasured};
-use crate::measure::MeasureCount;
+use crate::backend::Backend;
+use crate::circuit::Circuit;
+use crate::debugger::ElementPath;
+use crate::measure::{MeasureCount, MeasureRecord};
+use crate::observer::ExecutionObserver;
+use crate::operation::CircuitElement;
+use crate::postselect::PostselectionRecord;
 use crate::state::QuantumState;
+use crate::sub_circuit::SubCircuit;
+
+/// Error returned when the execution of a circuit fails
+#[derive(Clone, PartialEq, Debug)]
+pub enum ExecutionError {
+    /// The loop at 'path' reached the maximal number of iterations of a loop
+    IterationLimit { path: ElementPath, limit: u32 },
+    /// The total number of loop iterations reached the maximal number of iterations of the execution
+    GlobalIterationLimit { path: ElementPath, limit: u64 },
+    /// The execution took longer than the time budget
+    TimeBudgetExceeded { path: ElementPath, budget: Duration },
+    /// The stop condition of the loop at 'path' can never be met: the iterations
+    /// are deterministic and leave the state unchanged
+    UnreachableCondition { path: ElementPath, nb_iterations: u32 },
+    /// The outcome selected by the postselection 'id' at 'path' has a probability of zero
+    PostselectionFailed { path: ElementPath, id: String },
+    /// Any other error (unsupported gate, failing observer...)
+    Other(String),
+}
+
+impl Display for ExecutionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExecutionError::IterationLimit { path, limit } => {
+                write!(f, "Loop {} reached the limit of {} iterations", path, limit)
+            }
+            ExecutionError::GlobalIterationLimit { path, limit } => {
+                write!(f, "Execution reached the limit of {} loop iterations in loop {}", limit, path)
+            }
+            ExecutionError::TimeBudgetExceeded { path, budget } => {
+                write!(f, "Execution exceeded its time budget of {:?} in loop {}", budget, path)
+            }
+            ExecutionError::UnreachableCondition { path, nb_iterations } => {
+                write!(f, "The stop condition of loop {} cannot be met (detected after {} iterations)", path, nb_iterations)
+            }
+            ExecutionError::PostselectionFailed { path, id } => {
+                write!(f, "The outcome selected by postselection {} at {} cannot happen", id, path)
+            }
+            ExecutionError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<String> for ExecutionError {
+    fn from(message: String) -> Self {
+        ExecutionError::Other(message)
+    }
+}
+
+impl From<ExecutionError> for String {
+    fn from(error: ExecutionError) -> Self {
+        error.to_string()
+    }
+}
+
+/// Default maximal number of loop iterations of an execution
+pub const DEFAULT_MAX_TOTAL_ITERATIONS: u64 = 1_000_000;
+
+/// Limits checked before each loop iteration. By default, an execution
+/// stops after [`DEFAULT_MAX_TOTAL_ITERATIONS`] loop iterations.
+/// The limit of a single loop is set on the loop itself
+/// (see [`QuantumCircuitBuilder::add_loop_with_max_iterations`](crate::builder::QuantumCircuitBuilder::add_loop_with_max_iterations)).
+#[derive(Clone, Copy, Debug)]
+pub struct ExecutionLimits {
+    /// Maximal number of iterations of all the loops of the execution
+    pub max_total_iterations: Option<u64>,
+    /// Maximal duration of the execution
+    pub time_budget: Option<Duration>,
+}
+
+impl Default for ExecutionLimits {
+    fn default() -> Self {
+        ExecutionLimits { max_total_iterations: Some(DEFAULT_MAX_TOTAL_ITERATIONS), time_budget: None }
+    }
+}
+
+impl ExecutionLimits {
+    /// No limit at all: a loop whose condition is never met runs forever
+    pub fn unlimited() -> Self {
+        ExecutionLimits { max_total_iterations: None, time_budget: None }
+    }
+
+    pub fn with_max_total_iterations(mut self, max_total_iterations: u64) -> Self {
+        self.max_total_iterations = Some(max_total_iterations);
+        self
+    }
 
-pub enum State {
-    Measured(usize),
-    NotMeasured,
+    pub fn with_time_budget(mut self, time_budget: Duration) -> Self {
+        self.time_budget = Some(time_budget);
+        self
+    }
 }
 
 /// Contains information about the execution
 /// of the quantum circuit
-pub struct ExecutionContext {
+pub struct ExecutionContext<B: Backend = QuantumState> {
     /// Current quantum state
-    current_state: QuantumState,
-    /// Indicates if the quantum state has just been measured
-    state: State,
+    current_state: B,
     /// Measurement results by measurement's id
     count: HashMap<String, MeasureCount>,
+    /// Register measurement results by measurement's id and value
+    register_count: HashMap<String, HashMap<u64, u32>>,
+    /// Measurement results in the order of execution
+    history: Vec<MeasureRecord>,
+    /// Postselection results in the order of execution
+    postselections: Vec<PostselectionRecord>,
+    /// Path of the element being executed
+    path: ElementPath,
+    /// Current iteration of each loop being executed
+    iterations: Vec<u32>,
+    observers: Vec<Box<dyn ExecutionObserver>>,
+    limits: ExecutionLimits,
+    /// Number of loop iterations since the beginning of the execution
+    total_iterations: u64,
+    start: Instant,
+    /// Sub-circuits of the executed circuit by name
+    definitions: BTreeMap<String, Circuit>,
+    /// Elements executed by each call of a sub-circuit, by name, qbits and control qbits
+    instances: HashMap<(String, Vec<u8>, Vec<u8>), Rc<Circuit>>,
 }
 
-impl ExecutionContext {
+impl<B: Backend> ExecutionContext<B> {
     pub(crate) fn increase_zero(&mut self, id: &String) {
         self.increase_count(id, |c| c.nb_zero += 1)
     }
@@ -44,57 +153,166 @@ impl ExecutionContext {
         }
     }
 
-    pub(crate) fn set_measurement(&mut self, select_state: usize) {
-        let mut output = QuantumState::nil(self.nb_qbits());
-        output[select_state] = Complex64::one();
+    pub fn current_state(&self) -> &B {
+        &self.current_state
+    }
+
+    pub(crate) fn current_state_mut(&mut self) -> &mut B {
+        &mut self.current_state
+    }
+
+    pub(crate) fn initialize(initial_state: B) -> Self {
+        Self {
+            current_state: initial_state,
+            count: HashMap::new(),
+            register_count: HashMap::new(), history: vec![], postselections: vec![], path: ElementPath::default(), iterations: vec![],
+            observers: vec![],
+            limits: ExecutionLimits::default(),
+            total_iterations: 0,
+            start: Instant::now(),
+            definitions: BTreeMap::new(),
+            instances: HashMap::new(),
+        }
+    }
+
+    /// Return the same context with the state converted by `convert`
+    pub(crate) fn map_state<C: Backend>(self, convert: impl FnOnce(B) -> C) -> ExecutionContext<C> {
+        ExecutionContext {
+            current_state: convert(self.current_state),
+            count: self.count,
+            register_count: self.register_count,
+            history: self.history,
+            postselections: self.postselections,
+            path: self.path,
+            iterations: self.iterations,
+            observers: self.observers,
+            limits: self.limits,
+            total_iterations: self.total_iterations,
+            start: self.start,
+            definitions: self.definitions,
+            instances: self.instances,
+        }
+    }
+
+    pub(crate) fn set_definitions(&mut self, definitions: BTreeMap<String, Circuit>) {
+        self.definitions = definitions;
+        self.instances.clear();
+    }
 
-        self.current_state = output;
-        self.state = Measured(select_state);
+    /// Return the elements of the definition moved on the qbits of the call.
+    /// They are computed on the first execution of the call only.
+    pub(crate) fn sub_circuit_instance(&mut self, call: &SubCircuit) -> Result<Rc<Circuit>, String> {
+        let key = (call.name.clone(), call.qbits.clone(), call.control_bits.clone());
+        if let Some(instance) = self.instances.get(&key) {
+            return Ok(instance.clone());
+        }
+        let definition = self.definitions.get(&call.name).ok_or_else(|| format!("Unknown sub-circuit {}", call.name))?;
+        // the circuit addresses at most 255 qbits even if the state has more
+        let nb_qbits = u8::try_from(self.current_state.nb_qbits()).unwrap_or(u8::MAX);
+        let instance = Rc::new(Circuit { nb_qbits, elements: call.instantiate(definition, nb_qbits)?, definitions: BTreeMap::new() });
+        self.instances.insert(key, instance.clone());
+        Ok(instance)
     }
 
-    pub(crate) fn pick_on_state(&self) -> usize {
-        let mut target = 1.0 - (rand::random::<f64>());
-        for (index, amplitude) in self.current_state.iter().enumerate() {
-            target -= amplitude.norm_sqr();
-            if target <= 0.0 {
-                return index;
+    pub(crate) fn set_limits(&mut self, limits: ExecutionLimits) {
+        self.limits = limits;
+    }
+
+    /// Check the limits before starting a new iteration of the
+    /// current loop, `nb_iterations` being the number of completed iterations
+    /// and `max_iterations` the limit of the loop
+    pub(crate) fn check_loop_limits(&mut self, nb_iterations: u32, max_iterations: Option<u32>) -> Result<(), ExecutionError> {
+        let path = || self.path.clone();
+        if let Some(limit) = max_iterations {
+            if nb_iterations >= limit {
+                return Err(ExecutionError::IterationLimit { path: path(), limit });
             }
-        };
-        self.current_state.len() - 1
+        }
+        if let Some(limit) = self.limits.max_total_iterations {
+            if self.total_iterations >= limit {
+                return Err(ExecutionError::GlobalIterationLimit { path: path(), limit });
+            }
+        }
+        if let Some(budget) = self.limits.time_budget {
+            if self.start.elapsed() > budget {
+                return Err(ExecutionError::TimeBudgetExceeded { path: path(), budget });
+            }
+        }
+        self.total_iterations += 1;
+        Ok(())
     }
 
-    pub fn current_state(&self) -> &QuantumState {
-        &self.current_state
+    pub fn add_observer(&mut self, observer: Box<dyn ExecutionObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// Return the path of the element being executed
+    pub fn path(&self) -> &ElementPath {
+        &self.path
+    }
+
+    pub(crate) fn enter_element(&mut self, index: usize, element: &CircuitElement) -> Result<(), String> {
+        self.path.0.push(index);
+        self.notify(|o, path| o.element_entered(path, element))
     }
 
-    pub(crate) fn current_amplitude_at(&self, idx: usize) -> Complex64 {
-        self.current_state[idx]
+    pub(crate) fn exit_element(&mut self, element: &CircuitElement) -> Result<(), String> {
+        let result = self.notify(|o, path| o.element_exited(path, element));
+        self.path.0.pop();
+        result
     }
 
-    pub(crate) fn _norm_of_diff(&self, idx: usize, reference: Complex64) -> f64 {
-        self.current_state[idx].sub(reference).norm()
+    pub(crate) fn enter_loop(&mut self) {
+        self.iterations.push(0);
     }
 
-    pub(crate) fn set_current_state(&mut self, new_state: QuantumState) {
-        self.current_state = new_state;
-        self.state = NotMeasured;
+    pub(crate) fn loop_iteration_completed(&mut self, iteration: u32) -> Result<(), String> {
+        if let Some(current) = self.iterations.last_mut() {
+            *current = iteration;
+        }
+        self.notify(|o, path| o.loop_iteration(path, iteration))
     }
 
-    pub(crate) fn initialize(initial_state: &QuantumState) -> Self {
-        Self { current_state: QuantumState::from(initial_state), state: NotMeasured, count: HashMap::new() }
+    pub(crate) fn exit_loop(&mut self) {
+        self.iterations.pop();
     }
 
-    pub(crate) fn mask(&self, qbit_idx: u8) -> usize {
-        self.current_state.mask(qbit_idx)
+    pub(crate) fn record_measure(&mut self, id: &str, outcome: bool, probability: f64) {
+        let record = MeasureRecord {
+            id: id.to_string(),
+            path: self.path.clone(),
+            iterations: self.iterations.clone(),
+            outcome,
+            register_value: None,
+            probability,
+        };
+        self.history.push(record);
     }
 
-    pub(crate) fn control_mask(&self, control_qbits: &[u8]) -> usize {
-        self.current_state.control_mask(control_qbits)
+    pub(crate) fn record_register_measure(&mut self, id: &str, value: u64, probability: f64) {
+        *self.register_count.entry(id.to_string()).or_default().entry(value).or_insert(0) += 1;
+        let record = MeasureRecord {
+            id: id.to_string(),
+            path: self.path.clone(),
+            iterations: self.iterations.clone(),
+            outcome: value != 0,
+            register_value: Some(value),
+            probability,
+        };
+        self.history.push(record);
     }
-    pub(crate) fn nb_amplitudes(&self) -> usize {
-        self.current_state.len()
+
+    pub(crate) fn record_postselection(&mut self, id: &str, probability: f64) {
+        let record = PostselectionRecord { id: id.to_string(), path: self.path.clone(), probability };
+        self.postselections.push(record);
     }
 
+    pub(crate) fn notify(&mut self, mut event: impl FnMut(&mut dyn ExecutionObserver, &ElementPath) -> Result<(), String>) -> Result<(), String> {
+        for observer in self.observers.iter_mut() {
+            event(observer.as_mut(), &self.path)?;
+        }
+        Ok(())
+    }
 
     pub fn get_count(&self, variable: &str) -> Option<&MeasureCount> {
         self.count.get(variable)
@@ -113,11 +331,64 @@ impl ExecutionContext {
             None => 0
         }
     }
+
+    /// Return the number of times each value has been measured by the register measurement with the given id
+    pub fn get_register_counts(&self, variable: &str) -> Option<&HashMap<u64, u32>> {
+        self.register_count.get(variable)
+    }
+
+    /// Return the number of times the register measurement with the given id gave the value
+    pub fn get_register_count(&self, variable: &str, value: u64) -> u32 {
+        self.register_count.get(variable)
+            .and_then(|counts| counts.get(&value))
+            .cloned()
+            .unwrap_or(0)
+    }
+
+    /// Return the value of the last register measurement with the given id
+    pub fn last_register_value(&self, variable: &str) -> Option<u64> {
+        self.history.iter().rev().find(|r| r.id == variable).and_then(|r| r.register_value)
+    }
+
+    /// Return all the measurement results in the order of execution
+    pub fn history(&self) -> &[MeasureRecord] {
+        &self.history
+    }
+
+    /// Return all the postselection results in the order of execution
+    pub fn postselections(&self) -> &[PostselectionRecord] {
+        &self.postselections
+    }
+
+    /// Return the probability of the outcome selected by the last postselection with the given id
+    pub fn last_postselection_probability(&self, variable: &str) -> Option<f64> {
+        self.postselections.iter().rev().find(|r| r.id == variable).map(|r| r.probability)
+    }
+
+    /// Return the probability that all the postselections of the execution succeed,
+    /// that is the weight of this execution among the ones that are not postselected
+    pub fn postselection_probability(&self) -> f64 {
+        self.postselections.iter().map(|r| r.probability).product()
+    }
+
+    /// Return the outcome of the last measurement with the given id (true for |1>)
+    pub fn last_outcome(&self, variable: &str) -> Option<bool> {
+        self.history.iter().rev().find(|r| r.id == variable).map(|r| r.outcome)
+    }
+
+    /// Return the number of times the given outcome has been measured in a row
+    /// by the measurements with the given id, counting from the last one
+    pub fn get_nb_consecutive(&self, variable: &str, outcome: bool) -> u32 {
+        self.history.iter()
+            .rev()
+            .filter(|r| r.id == variable)
+            .take_while(|r| r.outcome == outcome)
+            .count() as u32
+    }
 }
 
-impl ExecutionContext {
-    pub fn nb_qbits(&self) -> u8 {
+impl<B: Backend> ExecutionContext<B> {
+    pub fn nb_qbits(&self) -> usize {
         self.current_state.nb_qbits()
     }
 }
-
@@ -1,18 +1,62 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
-use crate::execution::ExecutionContext;
+use crate::backend::Backend;
+use crate::execution::{ExecutionContext, ExecutionError};
 use crate::base_gate::BaseGate;
 use crate::operation::{CircuitElement, QuantumOperation};
+use crate::parameter::ParameterExpression;
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Gate {
     gate: BaseGate,
     control_bits: Vec<u8>,
+    /// symbolic angle of the gate, the angle of `gate` is ignored until the parameters are bound
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parameter: Option<ParameterExpression>,
 }
 
 impl Gate {
     pub fn new(gate: BaseGate, control_bits: Vec<u8>) -> Self {
-        Gate { gate, control_bits }
+        Gate { gate, control_bits, parameter: None }
+    }
+
+    /// Replace the angle of the gate by a symbolic expression like `2*θ_1`.
+    /// Fails if the gate has no angle.
+    pub fn with_parameter(mut self, angle: &str) -> Result<Self, String> {
+        if self.gate.angle().is_none() {
+            return Err("Only the phase and rotation gates can be parameterized".to_string());
+        }
+        self.parameter = Some(angle.parse()?);
+        Ok(self)
+    }
+
+    pub fn get_parameter(&self) -> Option<&ParameterExpression> {
+        self.parameter.as_ref()
+    }
+
+    /// Return the gate with the angle computed from the values of the parameters
+    pub fn bind(&self, values: &HashMap<String, f64>) -> Result<Gate, String> {
+        match &self.parameter {
+            None => Ok(self.clone()),
+            Some(parameter) => Ok(Gate::new(self.gate.with_angle(parameter.evaluate(values)?), self.control_bits.clone())),
+        }
+    }
+
+    /// Return the same gate, with its parameter, applied on the qbits given by `map`
+    pub fn map_qbits(&self, map: impl Fn(u8) -> u8) -> Gate {
+        Gate {
+            gate: self.gate.map_qbits(&map),
+            control_bits: self.control_bits.iter().map(|qbit| map(*qbit)).collect(),
+            parameter: self.parameter.clone(),
+        }
+    }
+
+    /// Return the same gate controlled by the given qbits too
+    pub fn controlled_by(mut self, control_bits: &[u8]) -> Gate {
+        self.control_bits.extend_from_slice(control_bits);
+        self
     }
 
     pub fn get_control_bits(&self) -> &Vec<u8> {
@@ -31,8 +75,12 @@ impl Into<CircuitElement> for Gate {
 }
 
 impl QuantumOperation for Gate {
-    fn apply(&self, context: &mut ExecutionContext) {
-        self.gate.apply_controlled(self.control_bits.as_slice(), context)
+    fn apply<B: Backend>(&self, context: &mut ExecutionContext<B>) -> Result<(), ExecutionError> {
+        if let Some(parameter) = &self.parameter {
+            return Err(ExecutionError::Other(format!("The parameters of the angle {} must be bound before the execution", parameter)));
+        }
+        context.current_state_mut().apply_gate(&self.gate, self.control_bits.as_slice())?;
+        Ok(())
     }
 
     fn max_qbit_idx(&self) -> u8 {
@@ -46,6 +94,9 @@ impl QuantumOperation for Gate {
     }
 
     fn check_validity(&self, nb_qbits: u8) -> Result<(), String> {
+        if self.parameter.is_some() && self.gate.angle().is_none() {
+            return Err("Only the phase and rotation gates can be parameterized".to_string());
+        }
         let qbit_indices = self.gate.get_involved_qbits(self.control_bits.as_slice());
         for qbit_index in qbit_indices.iter() {
             if *qbit_index >= nb_qbits {
@@ -56,7 +107,7 @@ impl QuantumOperation for Gate {
     }
 }
 
-fn check_for_no_duplicate(bits: Vec<u8>) -> Result<(), String> {
+pub(crate) fn check_for_no_duplicate(bits: Vec<u8>) -> Result<(), String> {
     if bits.len() <= 1 {
         return Ok(());
     }
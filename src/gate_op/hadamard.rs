@@ -1,18 +1,17 @@
 use std::f64::consts::FRAC_1_SQRT_2;
 
-use crate::execution::ExecutionContext;
 use crate::state::QuantumState;
 
-pub fn apply_controlled_hadamard(control_qbits: &[u8], target: u8, context: &mut ExecutionContext) {
-    let control_mask = context.control_mask(control_qbits);
-    let mask = context.mask(target);
+pub fn apply_controlled_hadamard(control_qbits: &[u8], target: u8, state: &mut QuantumState) {
+    let control_mask = state.control_mask(control_qbits);
+    let mask = state.mask(target);
     let not_mask = !mask;
 
-    let mut result = QuantumState::nil(context.nb_qbits());
+    let mut result = state.nil_like();
 
-    let len = context.nb_amplitudes();
+    let len = state.len();
     for src in 0..len {
-        let amplitude = context.current_amplitude_at(src);
+        let amplitude = state[src];
         let control_set = (src & control_mask) == control_mask;
         if control_set {
             let amplitude = amplitude * FRAC_1_SQRT_2;
@@ -33,5 +32,5 @@ pub fn apply_controlled_hadamard(control_qbits: &[u8], target: u8, context: &mut
         }
     };
 
-    context.set_current_state(result)
+    *state = result
 }
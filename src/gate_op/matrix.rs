@@ -0,0 +1,26 @@
+use num_complex::Complex64;
+
+use crate::state::QuantumState;
+
+/// Apply a single qbit gate given by its matrix (`matrix[row][column]`)
+pub fn apply_controlled_matrix(target: u8, control_qbits: &[u8], matrix: [[Complex64; 2]; 2], state: &mut QuantumState) {
+    let control_mask = state.control_mask(control_qbits);
+    let mask = state.mask(target);
+
+    let mut result = state.nil_like();
+
+    let len = state.len();
+    for src in 0..len {
+        let amplitude = state[src];
+        let control_set = (src & control_mask) == control_mask;
+        if !control_set {
+            result[src] += amplitude;
+            continue;
+        }
+        let column = if (src & mask) != 0 { 1 } else { 0 };
+        result[src & !mask] += matrix[0][column] * amplitude;
+        result[src | mask] += matrix[1][column] * amplitude;
+    }
+
+    *state = result;
+}
@@ -1,3 +1,6 @@
 pub mod hadamard;
+pub mod matrix;
 pub mod pauli;
+pub mod phase;
+pub mod sparse;
 pub mod swap;
\ No newline at end of file
@@ -2,18 +2,17 @@ use std::ops::Mul;
 
 use num_complex::Complex64;
 
-use crate::execution::ExecutionContext;
 use crate::state::QuantumState;
 
-pub fn apply_controlled_pauli_z(target_qbit: u8, control_qbits: &[u8], context: &mut ExecutionContext) {
-    let control_mask = context.control_mask(control_qbits);
-    let target_mask = context.mask(target_qbit);
+pub fn apply_controlled_pauli_z(target_qbit: u8, control_qbits: &[u8], state: &mut QuantumState) {
+    let control_mask = state.control_mask(control_qbits);
+    let target_mask = state.mask(target_qbit);
 
-    let mut result = QuantumState::nil(context.nb_qbits());
+    let mut result = state.nil_like();
 
-    let len = context.nb_amplitudes();
+    let len = state.len();
     for src in 0..len {
-        let amplitude = context.current_amplitude_at(src);
+        let amplitude = state[src];
 
         let control_set = (src & control_mask) == control_mask;
         let bit_set = (src & target_mask) != 0;
@@ -26,21 +25,21 @@ pub fn apply_controlled_pauli_z(target_qbit: u8, control_qbits: &[u8], context:
         result[src] = amplitude.clone();
     }
 
-    context.set_current_state(result);
+    *state = result;
 }
 
-pub fn apply_controlled_pauli_y(target_qbit: u8, control_qbits: &[u8], context: &mut ExecutionContext) {
-    let control_mask = context.control_mask(control_qbits);
-    let target_mask = context.mask(target_qbit);
+pub fn apply_controlled_pauli_y(target_qbit: u8, control_qbits: &[u8], state: &mut QuantumState) {
+    let control_mask = state.control_mask(control_qbits);
+    let target_mask = state.mask(target_qbit);
 
-    let mut result = QuantumState::nil(context.nb_qbits());
+    let mut result = state.nil_like();
 
     let i = Complex64::i();
     let minus_i = Complex64::new(0.0, -1.0);
 
-    let len = context.nb_amplitudes();
+    let len = state.len();
     for src in 0..len {
-        let amplitude = context.current_amplitude_at(src);
+        let amplitude = state[src];
 
         let control_set = (src & control_mask) == control_mask;
         let bit_set = (src & target_mask) != 0;
@@ -55,26 +54,26 @@ pub fn apply_controlled_pauli_y(target_qbit: u8, control_qbits: &[u8], context:
         result[dst] = amplitude;
     }
 
-    context.set_current_state(result)
+    *state = result
 }
 
-pub fn apply_controlled_not(target_qbit: u8, control_qbits: &[u8], context: &mut ExecutionContext) {
-    apply_controlled_pauli_x(target_qbit, control_qbits, context)
+pub fn apply_controlled_not(target_qbit: u8, control_qbits: &[u8], state: &mut QuantumState) {
+    apply_controlled_pauli_x(target_qbit, control_qbits, state)
 }
 
-pub fn apply_controlled_pauli_x(target_qbit: u8, control_qbits: &[u8], context: &mut ExecutionContext) {
-    let control_mask = context.control_mask(control_qbits);
-    let target_mask = context.mask(target_qbit);
+pub fn apply_controlled_pauli_x(target_qbit: u8, control_qbits: &[u8], state: &mut QuantumState) {
+    let control_mask = state.control_mask(control_qbits);
+    let target_mask = state.mask(target_qbit);
 
-    let mut result = QuantumState::nil(context.nb_qbits());
+    let mut result = state.nil_like();
 
-    let len = context.nb_amplitudes();
+    let len = state.len();
     for i in 0..len {
         let control_set = (i & control_mask) == control_mask;
         let source = if control_set { i ^ target_mask } else { i };
-        result[i] = context.current_amplitude_at(source)
+        result[i] = state[source]
     }
-    context.set_current_state(result);
+    *state = result;
 }
 
 #[cfg(test)]
@@ -88,20 +87,20 @@ mod tests_pauli {
 
     #[test]
     fn pauli_y_test_on_0() {
-        let mut context = ExecutionContext::initialize(&QuantumState::same_amplitude(1, &[0]));
-        apply_controlled_pauli_y(0, &[], &mut context);
+        let mut state = QuantumState::same_amplitude(1, &[0]);
+        apply_controlled_pauli_y(0, &[], &mut state);
 
-        assert!(context._norm_of_diff(0, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(1, Complex64::i()) < 1e-6);
+        assert!(state._norm_of_diff(0, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(1, Complex64::i()) < 1e-6);
     }
 
     #[test]
     fn pauli_y_test_on_1() {
-        let mut context = ExecutionContext::initialize(&QuantumState::same_amplitude(1, &[1]));
-        apply_controlled_pauli_y(0, &[], &mut context);
+        let mut state = QuantumState::same_amplitude(1, &[1]);
+        apply_controlled_pauli_y(0, &[], &mut state);
 
-        assert!(context._norm_of_diff(0, Complex64::new(0.0, -1.0)) < 1e-6);
-        assert!(context._norm_of_diff(1, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(0, Complex64::new(0.0, -1.0)) < 1e-6);
+        assert!(state._norm_of_diff(1, Complex64::zero()) < 1e-6);
     }
 
     #[test]
@@ -111,30 +110,28 @@ mod tests_pauli {
         let mut state = QuantumState::zero(1);
         state[0] = c1;
         state[1] = c2;
+        apply_controlled_pauli_y(0, &[], &mut state);
 
-        let mut context = ExecutionContext::initialize(&state);
-        apply_controlled_pauli_y(0, &[], &mut context);
-
-        assert!(context._norm_of_diff(0, c2.mul(Complex64::i().neg())) < 1e-6);
-        assert!(context._norm_of_diff(1, c1.mul(Complex64::i())) < 1e-6);
+        assert!(state._norm_of_diff(0, c2.mul(Complex64::i().neg())) < 1e-6);
+        assert!(state._norm_of_diff(1, c1.mul(Complex64::i())) < 1e-6);
     }
 
     #[test]
     fn pauli_z_test_on_0() {
-        let mut context = ExecutionContext::initialize(&QuantumState::same_amplitude(1, &[0]));
-        apply_controlled_pauli_z(0, &[], &mut context);
+        let mut state = QuantumState::same_amplitude(1, &[0]);
+        apply_controlled_pauli_z(0, &[], &mut state);
 
-        assert!(context._norm_of_diff(0, Complex64::one()) < 1e-6);
-        assert!(context._norm_of_diff(1, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(0, Complex64::one()) < 1e-6);
+        assert!(state._norm_of_diff(1, Complex64::zero()) < 1e-6);
     }
 
     #[test]
     fn pauli_z_test_on_1() {
-        let mut context = ExecutionContext::initialize(&QuantumState::same_amplitude(1, &[1]));
-        apply_controlled_pauli_z(0, &[], &mut context);
+        let mut state = QuantumState::same_amplitude(1, &[1]);
+        apply_controlled_pauli_z(0, &[], &mut state);
 
-        assert!(context._norm_of_diff(0, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(1, Complex64::new(-1.0, 0.0)) < 1e-6);
+        assert!(state._norm_of_diff(0, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(1, Complex64::new(-1.0, 0.0)) < 1e-6);
     }
 
     #[test]
@@ -144,12 +141,10 @@ mod tests_pauli {
         let c2 = Complex64::new(3.0, 4.0);
         state[0] = c1;
         state[1] = c2;
+        apply_controlled_pauli_z(0, &[], &mut state);
 
-        let mut context = ExecutionContext::initialize(&state);
-        apply_controlled_pauli_z(0, &[], &mut context);
-
-        assert!(context._norm_of_diff(0, c1) < 1e-6);
-        assert!(context._norm_of_diff(1, c2.neg()) < 1e-6);
+        assert!(state._norm_of_diff(0, c1) < 1e-6);
+        assert!(state._norm_of_diff(1, c2.neg()) < 1e-6);
     }
 }
 
@@ -159,39 +154,38 @@ mod tests_not {
     use num_traits::identities::One;
     use num_traits::Zero;
 
-    use crate::execution::ExecutionContext;
     use crate::gate_op::pauli::apply_controlled_pauli_x;
     use crate::state::QuantumState;
 
     #[test]
     fn not_test_on_zero() {
-        let mut context = ExecutionContext::initialize(&QuantumState::zero(3));
-        apply_controlled_pauli_x(2, &[], &mut context);
-
-        assert!(context._norm_of_diff(0, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(1, Complex64::one()) < 1e-6);
-        assert!(context._norm_of_diff(2, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(3, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(4, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(5, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(6, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(7, Complex64::zero()) < 1e-6);
+        let mut state = QuantumState::zero(3);
+        apply_controlled_pauli_x(2, &[], &mut state);
+
+        assert!(state._norm_of_diff(0, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(1, Complex64::one()) < 1e-6);
+        assert!(state._norm_of_diff(2, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(3, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(4, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(5, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(6, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(7, Complex64::zero()) < 1e-6);
     }
 
     #[test]
     fn not_test_on_one() {
-        let mut context = ExecutionContext::initialize(&QuantumState::zero(3));
-        apply_controlled_pauli_x(2, &[], &mut context);
-        apply_controlled_pauli_x(1, &[], &mut context);
-
-        assert!(context._norm_of_diff(0, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(1, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(2, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(3, Complex64::one()) < 1e-6);
-        assert!(context._norm_of_diff(4, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(5, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(6, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(7, Complex64::zero()) < 1e-6);
+        let mut state = QuantumState::zero(3);
+        apply_controlled_pauli_x(2, &[], &mut state);
+        apply_controlled_pauli_x(1, &[], &mut state);
+
+        assert!(state._norm_of_diff(0, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(1, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(2, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(3, Complex64::one()) < 1e-6);
+        assert!(state._norm_of_diff(4, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(5, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(6, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(7, Complex64::zero()) < 1e-6);
     }
 
     #[test]
@@ -200,18 +194,16 @@ mod tests_not {
         state[0] = Complex::zero();
         state[1] = Complex::one();
         state[6] = Complex::one();
-
-        let mut context = ExecutionContext::initialize(&state);
-        apply_controlled_pauli_x(1, &[], &mut context);
-
-        assert!(context._norm_of_diff(0, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(1, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(2, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(3, Complex64::one()) < 1e-6);
-        assert!(context._norm_of_diff(4, Complex64::one()) < 1e-6);
-        assert!(context._norm_of_diff(5, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(6, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(7, Complex64::zero()) < 1e-6);
+        apply_controlled_pauli_x(1, &[], &mut state);
+
+        assert!(state._norm_of_diff(0, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(1, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(2, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(3, Complex64::one()) < 1e-6);
+        assert!(state._norm_of_diff(4, Complex64::one()) < 1e-6);
+        assert!(state._norm_of_diff(5, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(6, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(7, Complex64::zero()) < 1e-6);
     }
 }
 
@@ -221,24 +213,22 @@ mod tests_toffoli {
     use num_traits::identities::One;
     use num_traits::Zero;
 
-    use crate::execution::ExecutionContext;
     use crate::gate_op::pauli::apply_controlled_pauli_x;
     use crate::state::QuantumState;
 
     #[test]
     fn toffoli_test_on_zero() {
-        let state = QuantumState::zero(3);
-        let mut context = ExecutionContext::initialize(&state);
-        apply_controlled_pauli_x(2, &[0, 1], &mut context);
-
-        assert!(context._norm_of_diff(0, Complex64::one()) < 1e-6);
-        assert!(context._norm_of_diff(1, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(2, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(3, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(4, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(5, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(6, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(7, Complex64::zero()) < 1e-6);
+        let mut state = QuantumState::zero(3);
+        apply_controlled_pauli_x(2, &[0, 1], &mut state);
+
+        assert!(state._norm_of_diff(0, Complex64::one()) < 1e-6);
+        assert!(state._norm_of_diff(1, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(2, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(3, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(4, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(5, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(6, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(7, Complex64::zero()) < 1e-6);
     }
 
     #[test]
@@ -248,16 +238,14 @@ mod tests_toffoli {
         state[2] = Complex::one();
         state[6] = Complex::new(2.0, 0.0);
         state[7] = Complex::new(3.0, 0.0);
-
-        let mut context = ExecutionContext::initialize(&state);
-        apply_controlled_pauli_x(2, &[0, 1], &mut context);
-        assert!(context._norm_of_diff(0, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(1, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(2, Complex64::one()) < 1e-6);
-        assert!(context._norm_of_diff(3, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(4, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(5, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(6, Complex64::new(3.0, 0.0)) < 1e-6);
-        assert!(context._norm_of_diff(7, Complex64::new(2.0, 0.0)) < 1e-6);
+        apply_controlled_pauli_x(2, &[0, 1], &mut state);
+        assert!(state._norm_of_diff(0, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(1, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(2, Complex64::one()) < 1e-6);
+        assert!(state._norm_of_diff(3, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(4, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(5, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(6, Complex64::new(3.0, 0.0)) < 1e-6);
+        assert!(state._norm_of_diff(7, Complex64::new(2.0, 0.0)) < 1e-6);
     }
 }
@@ -0,0 +1,54 @@
+use num_complex::Complex64;
+
+use crate::state::QuantumState;
+
+pub fn apply_controlled_s(target_qbit: u8, control_qbits: &[u8], state: &mut QuantumState) {
+    let control_mask = state.control_mask(control_qbits);
+    let target_mask = state.mask(target_qbit);
+
+    let mut result = state.nil_like();
+
+    let i = Complex64::i();
+
+    let len = state.len();
+    for src in 0..len {
+        let amplitude = state[src];
+
+        let control_set = (src & control_mask) == control_mask;
+        let bit_set = (src & target_mask) != 0;
+
+        result[src] = match (control_set, bit_set) {
+            (true, true) => i * amplitude,
+            (_, _) => amplitude
+        };
+    }
+
+    *state = result;
+}
+
+#[cfg(test)]
+mod tests_s {
+    use num_traits::One;
+    use num_traits::Zero;
+
+    use super::*;
+
+    #[test]
+    fn s_test_on_1() {
+        let mut state = QuantumState::same_amplitude(1, &[1]);
+        apply_controlled_s(0, &[], &mut state);
+
+        assert!(state._norm_of_diff(0, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(1, Complex64::i()) < 1e-6);
+    }
+
+    #[test]
+    fn s_twice_is_z() {
+        let mut state = QuantumState::same_amplitude(2, &[0, 3]);
+        apply_controlled_s(1, &[0], &mut state);
+        apply_controlled_s(1, &[0], &mut state);
+
+        assert!(state._norm_of_diff(0, Complex64::one() * std::f64::consts::FRAC_1_SQRT_2) < 1e-6);
+        assert!(state._norm_of_diff(3, -Complex64::one() * std::f64::consts::FRAC_1_SQRT_2) < 1e-6);
+    }
+}
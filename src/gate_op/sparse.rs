@@ -0,0 +1,49 @@
+use num_complex::Complex64;
+
+use crate::base_gate::BaseGate;
+use crate::sparse::SparseState;
+
+/// Apply a gate to a sparse state. Only the populated basis states are visited
+/// so the cost depends on the size of the support, not on the number of qbits.
+pub fn apply_controlled_gate_sparse(gate: &BaseGate, control_qbits: &[u8], state: &mut SparseState) {
+    match (gate, gate.matrix()) {
+        (BaseGate::Swap(target1, target2), _) => apply_controlled_swap_sparse(control_qbits, *target1, *target2, state),
+        (_, Some(matrix)) => apply_controlled_matrix(gate.get_involved_qbits(&[])[0], control_qbits, matrix, state),
+        (_, None) => unreachable!(),
+    }
+}
+
+/// Apply a single qbit gate given by its matrix (`matrix[row][column]`)
+pub fn apply_controlled_matrix(target: u8, control_qbits: &[u8], matrix: [[Complex64; 2]; 2], state: &mut SparseState) {
+    let control_mask = state.control_mask(control_qbits);
+    let mask = state.mask(target);
+
+    let mut result = state.nil_like();
+    for (src, amplitude) in state.iter() {
+        let control_set = (src & control_mask) == control_mask;
+        if !control_set {
+            result.add_to(src, amplitude);
+            continue;
+        }
+        let column = if (src & mask) != 0 { 1 } else { 0 };
+        result.add_to(src & !mask, matrix[0][column] * amplitude);
+        result.add_to(src | mask, matrix[1][column] * amplitude);
+    }
+    result.prune();
+    *state = result;
+}
+
+pub fn apply_controlled_swap_sparse(control_qbits: &[u8], target1: u8, target2: u8, state: &mut SparseState) {
+    let control_mask = state.control_mask(control_qbits);
+    let mask1 = state.mask(target1);
+    let mask2 = state.mask(target2);
+
+    let mut result = state.nil_like();
+    for (src, amplitude) in state.iter() {
+        let control_set = (src & control_mask) == control_mask;
+        let bits_differ = ((src & mask1) != 0) != ((src & mask2) != 0);
+        let dst = if control_set && bits_differ { src ^ mask1 ^ mask2 } else { src };
+        result.add_to(dst, amplitude);
+    }
+    *state = result;
+}
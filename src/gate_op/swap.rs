@@ -1,14 +1,13 @@
-use crate::execution::ExecutionContext;
 use crate::state::QuantumState;
 
-pub fn apply_controlled_swap(control_qbits: &[u8], target1: u8, target2: u8, context: &mut ExecutionContext) {
-    let control_mask = context.control_mask(control_qbits);
-    let mask1 = context.mask(target1);
-    let mask2 = context.mask(target2);
+pub fn apply_controlled_swap(control_qbits: &[u8], target1: u8, target2: u8, state: &mut QuantumState) {
+    let control_mask = state.control_mask(control_qbits);
+    let mask1 = state.mask(target1);
+    let mask2 = state.mask(target2);
     let not_mask = !(mask1 | mask2);
 
-    let mut result = QuantumState::nil(context.nb_qbits());
-    let len = context.nb_amplitudes();
+    let mut result = state.nil_like();
+    let len = state.len();
     for src in 0..len {
         let control_set = (src & control_mask) == control_mask;
 
@@ -19,9 +18,9 @@ pub fn apply_controlled_swap(control_qbits: &[u8], target1: u8, target2: u8, con
         } else {
             src
         };
-        result[target] = context.current_amplitude_at(src);
+        result[target] = state[src];
     }
-    context.set_current_state(result);
+    *state = result;
 }
 
 
@@ -37,45 +36,45 @@ mod tests_not {
 
     #[test]
     fn swap_test_on_00() {
-        let mut context = ExecutionContext::initialize(&QuantumState::same_amplitude(2, &[0]));
-        apply_controlled_swap(&[], 0, 1, &mut context);
+        let mut state = QuantumState::same_amplitude(2, &[0]);
+        apply_controlled_swap(&[], 0, 1, &mut state);
 
-        assert!(context._norm_of_diff(0, Complex64::one()) < 1e-6);
-        assert!(context._norm_of_diff(1, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(2, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(3, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(0, Complex64::one()) < 1e-6);
+        assert!(state._norm_of_diff(1, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(2, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(3, Complex64::zero()) < 1e-6);
     }
 
     #[test]
     fn swap_test_on_01() {
-        let mut context = ExecutionContext::initialize(&QuantumState::same_amplitude(2, &[1]));
-        apply_controlled_swap(&[], 0, 1, &mut context);
+        let mut state = QuantumState::same_amplitude(2, &[1]);
+        apply_controlled_swap(&[], 0, 1, &mut state);
 
-        assert!(context._norm_of_diff(0, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(1, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(2, Complex64::one()) < 1e-6);
-        assert!(context._norm_of_diff(3, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(0, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(1, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(2, Complex64::one()) < 1e-6);
+        assert!(state._norm_of_diff(3, Complex64::zero()) < 1e-6);
     }
 
     #[test]
     fn swap_test_on_10() {
-        let mut context = ExecutionContext::initialize(&QuantumState::same_amplitude(2, &[2]));
-        apply_controlled_swap(&[], 0, 1, &mut context);
+        let mut state = QuantumState::same_amplitude(2, &[2]);
+        apply_controlled_swap(&[], 0, 1, &mut state);
 
-        assert!(context._norm_of_diff(0, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(1, Complex64::one()) < 1e-6);
-        assert!(context._norm_of_diff(2, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(3, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(0, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(1, Complex64::one()) < 1e-6);
+        assert!(state._norm_of_diff(2, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(3, Complex64::zero()) < 1e-6);
     }
 
     #[test]
     fn swap_test_on_11() {
-        let mut context = ExecutionContext::initialize(&QuantumState::same_amplitude(2, &[3]));
-        apply_controlled_swap(&[], 0, 1, &mut context);
+        let mut state = QuantumState::same_amplitude(2, &[3]);
+        apply_controlled_swap(&[], 0, 1, &mut state);
 
-        assert!(context._norm_of_diff(0, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(1, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(2, Complex64::zero()) < 1e-6);
-        assert!(context._norm_of_diff(3, Complex64::one()) < 1e-6);
+        assert!(state._norm_of_diff(0, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(1, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(2, Complex64::zero()) < 1e-6);
+        assert!(state._norm_of_diff(3, Complex64::one()) < 1e-6);
     }
 }
@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use std::f64::consts::{FRAC_PI_2, SQRT_2};
+
+use num_complex::Complex64;
+use num_traits::Zero;
+
+use crate::backend::Backend;
+use crate::base_gate::BaseGate;
+use crate::circuit::Circuit;
+use crate::gate::Gate;
+use crate::gate_op::matrix::apply_controlled_matrix;
+use crate::hamiltonian::Hamiltonian;
+use crate::operation::CircuitElement;
+use crate::pauli::Pauli;
+use crate::state::QuantumState;
+
+/// Gradient of an expectation value: the partial derivative for each parameter of the circuit
+pub type Gradient = HashMap<String, f64>;
+
+impl Circuit {
+    /// Return the expectation value of the Hamiltonian on the final state of the circuit bound with the values
+    pub fn expectation<B: Backend>(&self, hamiltonian: &Hamiltonian, values: &HashMap<String, f64>, initial_state: &B) -> Result<f64, String> {
+        self.bind(values)?.bound_expectation(hamiltonian, initial_state)
+    }
+
+    fn bound_expectation<B: Backend>(&self, hamiltonian: &Hamiltonian, initial_state: &B) -> Result<f64, String> {
+        let initial_state = initial_state.snapshot().ok_or("Gradients are not supported by this backend")?;
+        let result = self.execute_on(initial_state)?;
+        hamiltonian.expectation(result.current_state())
+    }
+
+    /// Return the gradient of the expectation value of the Hamiltonian with the parameter-shift rule:
+    /// each derivative is computed exactly from the expectation values of the circuit with a shifted
+    /// angle, so it can be used with any backend. Two executions are needed per parameterized gate,
+    /// four for the controlled rotations.
+    ///
+    /// The circuit must only contain gates, the calls of sub-circuits are replaced by their gates.
+    pub fn parameter_shift_gradient<B: Backend>(&self, hamiltonian: &Hamiltonian, values: &HashMap<String, f64>, initial_state: &B) -> Result<Gradient, String> {
+        if !self.definitions.is_empty() {
+            return self.flatten()?.parameter_shift_gradient(hamiltonian, values, initial_state);
+        }
+        let gates = self.gates()?;
+        let bound = self.bind(values)?;
+        let bound_gates = bound.gates()?;
+        let mut gradient: Gradient = self.parameters().into_iter().map(|p| (p, 0.0)).collect();
+
+        for (index, gate) in gates.iter().enumerate() {
+            if gate.get_parameter().is_none() {
+                continue;
+            }
+            let bound_gate = bound_gates[index].get_gate();
+            let angle = parameterized_angle(&bound_gate)?;
+            let shifted = |shift: f64| {
+                let mut circuit = bound.clone();
+                circuit.elements[index] = Gate::new(bound_gate.with_angle(angle + shift), gate.get_control_bits().clone()).into();
+                circuit.bound_expectation(hamiltonian, initial_state)
+            };
+
+            let derivative = if has_three_eigenvalues(gate) {
+                let (d_plus, d_minus) = ((SQRT_2 + 1.0) / (4.0 * SQRT_2), (SQRT_2 - 1.0) / (4.0 * SQRT_2));
+                d_plus * (shifted(FRAC_PI_2)? - shifted(-FRAC_PI_2)?) - d_minus * (shifted(3.0 * FRAC_PI_2)? - shifted(-3.0 * FRAC_PI_2)?)
+            } else {
+                0.5 * (shifted(FRAC_PI_2)? - shifted(-FRAC_PI_2)?)
+            };
+            add_derivative(&mut gradient, gate, derivative);
+        }
+        Ok(gradient)
+    }
+
+    /// Return the exact gradient of the expectation value of the Hamiltonian with the adjoint method:
+    /// the state is computed once, then the gates are undone one by one from the last to the first.
+    /// The cost is about three executions of the circuit, whatever the number of parameters.
+    ///
+    /// The circuit must only contain gates, the calls of sub-circuits are replaced by their gates.
+    pub fn adjoint_gradient(&self, hamiltonian: &Hamiltonian, values: &HashMap<String, f64>, initial_state: &QuantumState) -> Result<Gradient, String> {
+        if !self.definitions.is_empty() {
+            return self.flatten()?.adjoint_gradient(hamiltonian, values, initial_state);
+        }
+        let gates = self.gates()?;
+        let bound = self.bind(values)?;
+        let bound_gates = bound.gates()?;
+        let mut gradient: Gradient = self.parameters().into_iter().map(|p| (p, 0.0)).collect();
+
+        let mut state = QuantumState::from(initial_state);
+        for gate in bound_gates.iter() {
+            state.apply_gate(&gate.get_gate(), gate.get_control_bits())?;
+        }
+        let mut lambda = apply_hamiltonian(hamiltonian, &state)?;
+
+        for (gate, bound_gate) in gates.iter().zip(bound_gates.iter()).rev() {
+            undo_gate(bound_gate, &mut state)?;
+            if gate.get_parameter().is_some() {
+                parameterized_angle(&bound_gate.get_gate())?;
+                let derivative = apply_derivative(bound_gate, &state);
+                add_derivative(&mut gradient, gate, 2.0 * lambda.inner_product(&derivative)?.re);
+            }
+            undo_gate(bound_gate, &mut lambda)?;
+        }
+        Ok(gradient)
+    }
+
+    fn gates(&self) -> Result<Vec<&Gate>, String> {
+        self.elements.iter()
+            .map(|element| match element {
+                CircuitElement::Gate(gate) => Ok(gate),
+                _ => Err("Gradients are only available for circuits made of gates".to_string()),
+            })
+            .collect()
+    }
+}
+
+/// Return the angle of a parameterized gate. A deserialized circuit is not checked,
+/// its parameters may be on gates without angle.
+fn parameterized_angle(gate: &BaseGate) -> Result<f64, String> {
+    gate.angle().ok_or_else(|| "Only the phase and rotation gates can be parameterized".to_string())
+}
+
+/// The controlled rotations have a generator with the eigenvalues 0 and ±1/2
+/// and need the four-term shift rule
+fn has_three_eigenvalues(gate: &Gate) -> bool {
+    !gate.get_control_bits().is_empty() && matches!(gate.get_gate(), BaseGate::Rx(_, _) | BaseGate::Ry(_, _) | BaseGate::Rz(_, _))
+}
+
+/// Add the derivative with respect to the angle of the gate to the derivatives of its parameters
+fn add_derivative(gradient: &mut Gradient, gate: &Gate, derivative: f64) {
+    if let Some(parameter) = gate.get_parameter() {
+        for name in parameter.parameters() {
+            *gradient.entry(name.to_string()).or_insert(0.0) += parameter.coefficient(name) * derivative;
+        }
+    }
+}
+
+/// Return H|ψ>
+fn apply_hamiltonian(hamiltonian: &Hamiltonian, state: &QuantumState) -> Result<QuantumState, String> {
+    let mut result = state.nil_like();
+    for (coefficient, pauli_string) in hamiltonian.terms.iter() {
+        if pauli_string.0.len() > state.nb_qbits() as usize {
+            return Err(format!("Pauli string {} is too long for a state of {} qbits", pauli_string, state.nb_qbits()));
+        }
+        let mut term = QuantumState::from(state);
+        for (qbit, pauli) in pauli_string.product() {
+            let gate = match pauli {
+                Pauli::X => BaseGate::X(qbit),
+                Pauli::Y => BaseGate::Y(qbit),
+                Pauli::Z => BaseGate::Z(qbit),
+                Pauli::I => continue,
+            };
+            term.apply_gate(&gate, &[])?;
+        }
+        for (r, t) in result.iter_mut().zip(term.iter()) {
+            *r += t * coefficient;
+        }
+    }
+    Ok(result)
+}
+
+/// Apply the inverse of the gate
+fn undo_gate(gate: &Gate, state: &mut QuantumState) -> Result<(), String> {
+    let base_gate = gate.get_gate();
+    match base_gate.matrix() {
+        None => state.apply_gate(&base_gate, gate.get_control_bits()),
+        Some(matrix) => {
+            let adjoint = [[matrix[0][0].conj(), matrix[1][0].conj()], [matrix[0][1].conj(), matrix[1][1].conj()]];
+            apply_controlled_matrix(base_gate.max_qbit_idx(), gate.get_control_bits(), adjoint, state);
+            Ok(())
+        }
+    }
+}
+
+/// Return dU/dangle |ψ> where U is the (controlled) gate: the amplitudes
+/// where a control qbit is not set do not depend on the angle
+fn apply_derivative(gate: &Gate, state: &QuantumState) -> QuantumState {
+    let base_gate = gate.get_gate();
+    let control_mask = state.control_mask(gate.get_control_bits());
+    let mut result = QuantumState::from(state);
+    for (index, amplitude) in result.iter_mut().enumerate() {
+        if (index & control_mask) != control_mask {
+            *amplitude = Complex64::zero();
+        }
+    }
+    apply_controlled_matrix(base_gate.max_qbit_idx(), &[], matrix_derivative(&base_gate), &mut result);
+    result
+}
+
+fn matrix_derivative(gate: &BaseGate) -> [[Complex64; 2]; 2] {
+    let zero = Complex64::zero();
+    let i = Complex64::i();
+    match gate {
+        BaseGate::Phase(angle, _) => [[zero, zero], [zero, i * Complex64::from_polar(1.0, *angle)]],
+        BaseGate::Rx(angle, _) => {
+            let (sin, cos) = (angle * 0.5).sin_cos();
+            [[Complex64::new(-0.5 * sin, 0.0), -0.5 * cos * i], [-0.5 * cos * i, Complex64::new(-0.5 * sin, 0.0)]]
+        }
+        BaseGate::Ry(angle, _) => {
+            let (sin, cos) = (angle * 0.5).sin_cos();
+            [[Complex64::new(-0.5 * sin, 0.0), Complex64::new(-0.5 * cos, 0.0)], [Complex64::new(0.5 * cos, 0.0), Complex64::new(-0.5 * sin, 0.0)]]
+        }
+        BaseGate::Rz(angle, _) => [[-0.5 * i * Complex64::from_polar(1.0, -angle * 0.5), zero], [zero, 0.5 * i * Complex64::from_polar(1.0, angle * 0.5)]],
+        _ => [[zero, zero], [zero, zero]],
+    }
+}
+
+
+#[cfg(test)]
+mod tests_gradient {
+    use crate::common_gate::CommonGate::{CNot, Hadamard};
+    use crate::computer::QuantumComputer;
+    use crate::sparse::SparseState;
+
+    use super::*;
+
+    fn values(values: &[(&str, f64)]) -> HashMap<String, f64> {
+        values.iter().map(|(name, value)| (name.to_string(), *value)).collect()
+    }
+
+    fn ansatz(computer: &QuantumComputer) -> Circuit {
+        computer.new_circuit_builder()
+            .add_operation(BaseGate::Ry(0.0, 0).with_parameter("2*a").unwrap())
+            .add_operation(Hadamard(1))
+            .add_operation(Gate::new(BaseGate::Rx(0.0, 1), vec![0]).with_parameter("b - a").unwrap())
+            .add_operation(CNot(2, [1]))
+            .add_operation(Gate::new(BaseGate::Phase(0.0, 2), vec![0]).with_parameter("b/2").unwrap())
+            .add_operation(BaseGate::Rz(0.0, 2).with_parameter("c").unwrap())
+            .add_operation(Hadamard(2))
+            .build()
+            .unwrap()
+    }
+
+    /// Central finite differences, only used to check the exact gradients
+    fn finite_differences(circuit: &Circuit, hamiltonian: &Hamiltonian, point: &HashMap<String, f64>, state: &QuantumState) -> Gradient {
+        let epsilon = 1e-5;
+        point.keys()
+            .map(|name| {
+                let mut plus = point.clone();
+                let mut minus = point.clone();
+                *plus.get_mut(name).unwrap() += epsilon;
+                *minus.get_mut(name).unwrap() -= epsilon;
+                let derivative = (circuit.expectation(hamiltonian, &plus, state).unwrap() - circuit.expectation(hamiltonian, &minus, state).unwrap()) / (2.0 * epsilon);
+                (name.clone(), derivative)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn gradients_match_finite_differences() {
+        let computer = QuantumComputer::new(3);
+        let circuit = ansatz(&computer);
+        let hamiltonian = Hamiltonian::parse(&[(1.0, "ZZI"), (0.5, "IXX"), (-0.7, "YIZ"), (0.3, "IIX")]).unwrap();
+        let point = values(&[("a", 0.4), ("b", -1.3), ("c", 0.9)]);
+        let state = computer.zero_state();
+
+        let expected = finite_differences(&circuit, &hamiltonian, &point, &state);
+        let shift = circuit.parameter_shift_gradient(&hamiltonian, &point, &state).unwrap();
+        let sparse_shift = circuit.parameter_shift_gradient(&hamiltonian, &point, &SparseState::zero(3)).unwrap();
+        let adjoint = circuit.adjoint_gradient(&hamiltonian, &point, &state).unwrap();
+        for name in ["a", "b", "c"] {
+            assert!((shift[name] - expected[name]).abs() < 1e-6, "{} {} {}", name, shift[name], expected[name]);
+            assert!((sparse_shift[name] - expected[name]).abs() < 1e-6);
+            assert!((adjoint[name] - expected[name]).abs() < 1e-6, "{} {} {}", name, adjoint[name], expected[name]);
+        }
+    }
+
+    #[test]
+    fn only_gates_are_supported() {
+        let computer = QuantumComputer::new(1);
+        let circuit = computer.new_circuit_builder()
+            .add_operation(BaseGate::Rx(0.0, 0).with_parameter("a").unwrap())
+            .add_measure("m", 0)
+            .build()
+            .unwrap();
+        let hamiltonian = Hamiltonian::parse(&[(1.0, "Z")]).unwrap();
+        let point = values(&[("a", 0.1)]);
+        assert!(circuit.parameter_shift_gradient(&hamiltonian, &point, &computer.zero_state()).is_err());
+        assert!(circuit.adjoint_gradient(&hamiltonian, &point, &computer.zero_state()).is_err());
+    }
+
+    #[test]
+    fn deserialized_parameters_without_angle_are_rejected() {
+        let computer = QuantumComputer::new(1);
+        let circuit = computer.new_circuit_builder()
+            .add_operation(BaseGate::Rx(0.0, 0).with_parameter("a").unwrap())
+            .build()
+            .unwrap();
+        let json = circuit.to_string().unwrap().replace(r#"{"Rx":[0.0,0]}"#, r#"{"Hadamard":0}"#);
+        assert!(json.contains("Hadamard"));
+        let circuit = Circuit::from_string(&json).unwrap();
+        assert_eq!(circuit.parameters(), vec!["a"]);
+
+        let hamiltonian = Hamiltonian::parse(&[(1.0, "Z")]).unwrap();
+        let point = values(&[("a", 0.1)]);
+        assert!(circuit.parameter_shift_gradient(&hamiltonian, &point, &computer.zero_state()).is_err());
+        assert!(circuit.adjoint_gradient(&hamiltonian, &point, &computer.zero_state()).is_err());
+    }
+}
@@ -94,7 +94,10 @@ impl BaseGate {
             BaseGate::X(_) => 1.0,
             BaseGate::Y(_) => 1.0,
             BaseGate::Z(_) => 1.0,
-            BaseGate::Hadamard(_) => 1.0
+            BaseGate::Hadamard(_) => 1.0,
+            BaseGate::S(_) => 1.0,
+            BaseGate::T(_) => 1.0,
+            BaseGate::Phase(_, _) | BaseGate::Rx(_, _) | BaseGate::Ry(_, _) | BaseGate::Rz(_, _) => 1.0,
         };
         return style.register_spacing * factor * HEIGHT_SPACING_RATIO;
     }
@@ -106,7 +109,13 @@ impl BaseGate {
             BaseGate::Y(_) => Some(String::from("Y")),
             BaseGate::Z(_) => Some(String::from("Z")),
             BaseGate::Swap(_, _) => None,
-            BaseGate::Hadamard(_) => Some(String::from("H"))
+            BaseGate::Hadamard(_) => Some(String::from("H")),
+            BaseGate::S(_) => Some(String::from("S")),
+            BaseGate::T(_) => Some(String::from("T")),
+            BaseGate::Phase(_, _) => Some(String::from("P")),
+            BaseGate::Rx(_, _) => Some(String::from("Rx")),
+            BaseGate::Ry(_, _) => Some(String::from("Ry")),
+            BaseGate::Rz(_, _) => Some(String::from("Rz")),
         }
     }
 
@@ -118,6 +127,9 @@ impl BaseGate {
             BaseGate::Z(t) => parameter.qbit_y_offset(*t),
             BaseGate::Swap(t1, t2) => (parameter.qbit_y_offset(*t1) + parameter.qbit_y_offset(*t2)) * 0.5,
             BaseGate::Hadamard(t) => parameter.qbit_y_offset(*t),
+            BaseGate::S(t) => parameter.qbit_y_offset(*t),
+            BaseGate::T(t) => parameter.qbit_y_offset(*t),
+            BaseGate::Phase(_, t) | BaseGate::Rx(_, t) | BaseGate::Ry(_, t) | BaseGate::Rz(_, t) => parameter.qbit_y_offset(*t),
         }
     }
 
@@ -127,6 +139,11 @@ impl BaseGate {
             BaseGate::Y(target) => draw_gate_with_text(drawer, parameter, gui_data).then(|| { *target }),
             BaseGate::Z(target) => draw_gate_with_text(drawer, parameter, gui_data).then(|| { *target }),
             BaseGate::Hadamard(target) => draw_gate_with_text(drawer, parameter, gui_data).then(|| { *target }),
+            BaseGate::S(target) => draw_gate_with_text(drawer, parameter, gui_data).then(|| { *target }),
+            BaseGate::T(target) => draw_gate_with_text(drawer, parameter, gui_data).then(|| { *target }),
+            BaseGate::Phase(_, target) | BaseGate::Rx(_, target) | BaseGate::Ry(_, target) | BaseGate::Rz(_, target) => {
+                draw_gate_with_text(drawer, parameter, gui_data).then(|| { *target })
+            }
 
             BaseGate::Not(target) => draw_not_gate(drawer, parameter, gui_data).then(|| { *target }),
             BaseGate::Swap(target1, target2) => draw_swap_gate(drawer, parameter, gui_data, target1, target2),
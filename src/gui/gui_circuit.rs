@@ -1,4 +1,5 @@
 
+use std::collections::BTreeMap;
 use std::fmt::{Display, Formatter};
 use std::ops::{Deref, DerefMut};
 
@@ -15,8 +16,10 @@ use crate::gate::Gate;
 use crate::base_gate::BaseGate;
 use crate::gui::{Drawable, Style};
 use crate::gui::gui_drawer::GuiDrawer;
-use crate::measure::Measure;
-use crate::operation::CircuitElement;
+use crate::measure::{Measure, RegisterMeasure};
+use crate::postselect::Postselect;
+use crate::sub_circuit::SubCircuit;
+use crate::operation::{CircuitElement, QuantumOperation};
 use crate::gui::id_generator::IdGenerator;
 
 
@@ -25,6 +28,7 @@ pub enum HoverData {
     Loop(u32),
     Measure(u32),
     Gate(u32, Option<u8>, Option<usize>),
+    SubCircuit(u32),
 }
 
 impl Display for HoverData {
@@ -33,6 +37,7 @@ impl Display for HoverData {
             HoverData::Loop(index) => write!(f, "Loop {:?}", index),
             HoverData::Measure(index) => write!(f, "Measure {:?}", index),
             HoverData::Gate(index, target, control) => write!(f, "Gate {:?} t:{:?} c:{:?}", index, target, control),
+            HoverData::SubCircuit(index) => write!(f, "SubCircuit {:?}", index),
         }
     }
 }
@@ -46,6 +51,10 @@ impl HoverData {
         HoverData::Loop(id)
     }
 
+    pub fn for_sub_circuit(id: u32) -> Self {
+        HoverData::SubCircuit(id)
+    }
+
     pub fn for_gate_on_target_qbit(id: u32, target: u8) -> Self {
         HoverData::Gate(id, Some(target), None)
     }
@@ -98,6 +107,20 @@ pub struct GuiGateData {
     pub text_position: Vector2,
 }
 
+///Graphical data for a sub-circuit element
+#[derive(Clone, Default)]
+pub struct GuiSubCircuitData {
+    ///the common data (width and position)
+    pub common: CommonGuiData,
+    ///the box of the call, or the outline of the expanded content
+    pub outline: Rectangle,
+    pub margin: f32,
+    ///the size of the name
+    pub text_size: Size,
+    ///the position of the name
+    pub text_position: Vector2,
+}
+
 #[derive(Clone, Default)]
 pub struct GuiMeasureData {
     pub common: CommonGuiData,
@@ -138,7 +161,7 @@ impl Deref for DrawableParameter {
 impl GuiRoot {
     pub fn new(circuit: &Circuit, reference: &Style) -> Self {
         let mut id_generator= IdGenerator::default();
-        let gui_circuit = GuiCircuit::new(circuit, &mut id_generator);
+        let gui_circuit = GuiCircuit::new(circuit, &circuit.definitions, &mut id_generator);
 
         let parameter = DrawableParameter { nb_qbits: circuit.nb_qbits, style: reference.clone() };
         let root = GuiRoot { position: Vector2::default(), parameter, circuit:gui_circuit };
@@ -146,6 +169,11 @@ impl GuiRoot {
         root
     }
 
+    /// Expand or collapse the sub-circuit with the given id.
+    /// Return false if there is no such sub-circuit. The layout must be done again.
+    pub fn toggle_sub_circuit(&mut self, id: u32) -> bool {
+        self.circuit.toggle_sub_circuit(id)
+    }
 }
 
 #[derive(Clone)]
@@ -172,14 +200,28 @@ pub struct GuiGate {
     pub control_bits: Vec<u8>,
 }
 
+#[derive(Clone)]
+pub struct GuiSubCircuit {
+    pub id: u32,
+    pub gui_data: GuiSubCircuitData,
+    ///the name of the definition
+    pub name: String,
+    pub qbits: Vec<u8>,
+    pub control_bits: Vec<u8>,
+    ///true to draw the content instead of a box
+    pub expanded: bool,
+    ///the content of the definition on the qbits of the call
+    pub circuit: GuiCircuit,
+}
+
 #[derive(Clone)]
 pub struct GuiMeasure {
     pub id: u32,
     pub gui_data: GuiMeasureData,
     ///A uniq identifier of the measurement
     pub measure_id: String,
-    ///the target qbit for the measurement
-    pub target: u8,
+    ///the measured qbits (several for a register or a Pauli product measurement)
+    pub targets: Vec<u8>,
 }
 
 
@@ -188,6 +230,7 @@ pub enum GuiCircuitElement {
     GuiLoop(GuiLoop),
     GuiGate(GuiGate),
     GuiMeasure(GuiMeasure),
+    GuiSubCircuit(GuiSubCircuit),
 }
 
 impl Display for GuiCircuitElement {
@@ -196,6 +239,7 @@ impl Display for GuiCircuitElement {
             GuiCircuitElement::GuiLoop(_) => f.write_str("GuiLoop"),
             GuiCircuitElement::GuiGate(_p) => f.write_str("GuiGate "),
             GuiCircuitElement::GuiMeasure(_) => f.write_str("GuiMeasure"),
+            GuiCircuitElement::GuiSubCircuit(_) => f.write_str("GuiSubCircuit"),
         }
     }
 }
@@ -206,6 +250,7 @@ impl GuiCircuitElement {
             GuiCircuitElement::GuiLoop(p) => p.gui_data.common.width,
             GuiCircuitElement::GuiGate(p) => p.gui_data.common.width,
             GuiCircuitElement::GuiMeasure(p) => p.gui_data.common.width,
+            GuiCircuitElement::GuiSubCircuit(p) => p.gui_data.common.width,
         }
     }
 }
@@ -249,6 +294,19 @@ impl DerefMut for GuiLoopData {
     }
 }
 
+impl Deref for GuiSubCircuitData {
+    type Target = CommonGuiData;
+    fn deref(&self) -> &Self::Target {
+        &self.common
+    }
+}
+
+impl DerefMut for GuiSubCircuitData {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.common
+    }
+}
+
 impl Deref for GuiMeasureData {
     type Target = CommonGuiData;
     fn deref(&self) -> &Self::Target {
@@ -284,10 +342,11 @@ impl Drawable for GuiCircuit {
 
 
 impl GuiCircuit {
-    pub fn new(circuit: &Circuit, id_generator: &mut IdGenerator) -> Self {
+    /// `definitions` are the sub-circuits of the drawn circuit, used to draw the expanded calls
+    pub fn new(circuit: &Circuit, definitions: &BTreeMap<String, Circuit>, id_generator: &mut IdGenerator) -> Self {
         let id = id_generator.get_and_increment();
         let gui_elements = circuit.elements.iter()
-            .map(|element| { to_gui(element,id_generator)}).collect();
+            .map(|element| { to_gui(element, definitions, id_generator)}).collect();
 
         GuiCircuit {
             id,
@@ -296,20 +355,35 @@ impl GuiCircuit {
             gui_elements
         }
     }
+
+    fn toggle_sub_circuit(&mut self, id: u32) -> bool {
+        self.gui_elements.iter_mut().any(|element| match element {
+            GuiCircuitElement::GuiSubCircuit(s) if s.id == id => {
+                s.expanded = !s.expanded;
+                true
+            }
+            GuiCircuitElement::GuiSubCircuit(s) => s.circuit.toggle_sub_circuit(id),
+            GuiCircuitElement::GuiLoop(l) => l.circuit.toggle_sub_circuit(id),
+            GuiCircuitElement::GuiGate(_) | GuiCircuitElement::GuiMeasure(_) => false,
+        })
+    }
 }
 
-fn to_gui(element: &CircuitElement, id_generator: &mut IdGenerator) -> GuiCircuitElement {
+fn to_gui(element: &CircuitElement, definitions: &BTreeMap<String, Circuit>, id_generator: &mut IdGenerator) -> GuiCircuitElement {
     match element {
-        CircuitElement::Loop(l) => GuiLoop::new(l, id_generator),
+        CircuitElement::Loop(l) => GuiLoop::new(l, definitions, id_generator),
         CircuitElement::Gate(g) => GuiGate::new(g, id_generator),
-        CircuitElement::Measure(m) => GuiMeasure::new(m, id_generator)
+        CircuitElement::Measure(m) => GuiMeasure::new(m, id_generator),
+        CircuitElement::RegisterMeasure(m) => GuiMeasure::for_register(m, id_generator),
+        CircuitElement::Postselect(p) => GuiMeasure::for_postselection(p, id_generator),
+        CircuitElement::SubCircuit(s) => GuiSubCircuit::new(s, definitions, id_generator),
     }
 }
 
 impl GuiLoop {
-    pub fn new(loop_element: &Loop, id_generator: &mut IdGenerator) -> GuiCircuitElement {
+    pub fn new(loop_element: &Loop, definitions: &BTreeMap<String, Circuit>, id_generator: &mut IdGenerator) -> GuiCircuitElement {
         let stop_condition = loop_element.stop_condition.clone();
-        let circuit = GuiCircuit::new(&loop_element.circuit, id_generator);
+        let circuit = GuiCircuit::new(&loop_element.circuit, definitions, id_generator);
         let gui_loop = GuiLoop { id:id_generator.get_and_increment(), stop_condition, circuit, gui_data: GuiLoopData::default() };
         GuiCircuitElement::GuiLoop(gui_loop)
     }
@@ -322,9 +396,42 @@ impl GuiGate {
     }
 }
 
+impl GuiSubCircuit {
+    /// The call is collapsed, the content of an unknown sub-circuit is empty
+    pub fn new(call: &SubCircuit, definitions: &BTreeMap<String, Circuit>, id_generator: &mut IdGenerator) -> GuiCircuitElement {
+        let nb_qbits = call.max_qbit_idx() + 1;
+        let elements = definitions.get(&call.name)
+            .and_then(|definition| call.instantiate(definition, nb_qbits).ok())
+            .unwrap_or_default();
+        let instance = Circuit { nb_qbits, elements, definitions: BTreeMap::new() };
+        let circuit = GuiCircuit::new(&instance, definitions, id_generator);
+        let gui_sub_circuit = GuiSubCircuit {
+            id: id_generator.get_and_increment(),
+            gui_data: GuiSubCircuitData::default(),
+            name: call.name.clone(),
+            qbits: call.qbits.clone(),
+            control_bits: call.control_bits.clone(),
+            expanded: false,
+            circuit,
+        };
+        GuiCircuitElement::GuiSubCircuit(gui_sub_circuit)
+    }
+}
+
 impl GuiMeasure {
     pub fn new(measure: &Measure, id_generator: &mut IdGenerator) -> GuiCircuitElement {
-        let gui_measure = GuiMeasure { id:id_generator.get_and_increment(), target: measure.qbit_target, measure_id: measure.id.clone(), gui_data: GuiMeasureData::default() };
+        let targets = measure.product().iter().map(|(qbit, _)| *qbit).collect();
+        let gui_measure = GuiMeasure { id:id_generator.get_and_increment(), targets, measure_id: measure.id.clone(), gui_data: GuiMeasureData::default() };
+        GuiCircuitElement::GuiMeasure(gui_measure)
+    }
+
+    pub fn for_register(measure: &RegisterMeasure, id_generator: &mut IdGenerator) -> GuiCircuitElement {
+        let gui_measure = GuiMeasure { id:id_generator.get_and_increment(), targets: measure.qbits.clone(), measure_id: measure.id.clone(), gui_data: GuiMeasureData::default() };
+        GuiCircuitElement::GuiMeasure(gui_measure)
+    }
+
+    pub fn for_postselection(postselect: &Postselect, id_generator: &mut IdGenerator) -> GuiCircuitElement {
+        let gui_measure = GuiMeasure { id:id_generator.get_and_increment(), targets: vec![postselect.qbit_target], measure_id: postselect.id.clone(), gui_data: GuiMeasureData::default() };
         GuiCircuitElement::GuiMeasure(gui_measure)
     }
 }
@@ -14,10 +14,13 @@ impl Drawable for GuiMeasure {
         let width = gate_width + parameter.margin * 2.0;
 
         self.gui_data.width = width;
+        let top = self.targets.iter().map(|t| parameter.qbit_y_offset(*t)).fold(f32::MAX, f32::min);
+        let bottom = self.targets.iter().map(|t| parameter.qbit_y_offset(*t)).fold(f32::MIN, f32::max);
+
         self.gui_data.outline.x = parameter.margin;
-        self.gui_data.outline.y = parameter.qbit_y_offset(self.target) - gate_height * 0.5;
+        self.gui_data.outline.y = top - gate_height * 0.5;
         self.gui_data.outline.width = gate_width;
-        self.gui_data.outline.height = gate_height;
+        self.gui_data.outline.height = bottom - top + gate_height;
 
         width
     }
@@ -9,6 +9,7 @@ mod circuit_drawer;
 mod gate_drawer;
 mod loop_drawer;
 mod measure_drawer;
+mod sub_circuit_drawer;
 pub mod gui_circuit;
 pub mod camera_manager;
 pub mod gui_drawer;
@@ -69,7 +70,8 @@ impl Drawable for GuiCircuitElement {
         match self {
             GuiCircuitElement::GuiLoop(p) => p.layout(parameter),
             GuiCircuitElement::GuiGate(p) => p.layout( parameter),
-            GuiCircuitElement::GuiMeasure(p) => p.layout( parameter)
+            GuiCircuitElement::GuiMeasure(p) => p.layout( parameter),
+            GuiCircuitElement::GuiSubCircuit(p) => p.layout(parameter),
         }
     }
 
@@ -78,6 +80,7 @@ impl Drawable for GuiCircuitElement {
             GuiCircuitElement::GuiLoop(p) => p.draw(drawer, parameter),
             GuiCircuitElement::GuiGate(p) => p.draw(drawer, parameter),
             GuiCircuitElement::GuiMeasure(p) => p.draw(drawer, parameter),
+            GuiCircuitElement::GuiSubCircuit(p) => p.draw(drawer, parameter),
         }
     }
 }
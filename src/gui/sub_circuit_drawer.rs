@@ -0,0 +1,85 @@
+use raylib::drawing::RaylibDraw;
+use raylib::math::Vector2;
+
+use crate::gui::{Drawable, HEIGHT_SPACING_RATIO, Style};
+use crate::gui::gui_circuit::{DrawableParameter, GuiSubCircuit, HoverData};
+use crate::gui::gui_drawer::GuiDrawer;
+
+impl Drawable for GuiSubCircuit {
+    /// A collapsed call is a box with the name of the sub-circuit over its qbits,
+    /// an expanded one is the content of the sub-circuit with the name above
+    fn layout(&mut self, parameter: &DrawableParameter) -> f32 {
+        let gate_height = parameter.register_spacing * HEIGHT_SPACING_RATIO;
+        let margin = parameter.margin;
+        let top = self.qbits.iter().map(|t| parameter.qbit_y_offset(*t)).fold(f32::MAX, f32::min);
+        let bottom = self.qbits.iter().map(|t| parameter.qbit_y_offset(*t)).fold(f32::MIN, f32::max);
+        let text_size = parameter.font.measure_text(&self.name, 0.0);
+
+        let content_width = if self.expanded {
+            self.circuit.layout(parameter)
+        } else {
+            (text_size.width() + margin).max(gate_height)
+        };
+
+        self.gui_data.margin = margin;
+        self.gui_data.outline.x = margin;
+        self.gui_data.outline.y = top - gate_height * 0.5;
+        self.gui_data.outline.width = content_width;
+        self.gui_data.outline.height = bottom - top + gate_height;
+
+        self.gui_data.text_size = text_size;
+        self.gui_data.text_position.x = margin + (content_width - text_size.width()) * 0.5;
+        self.gui_data.text_position.y = if self.expanded {
+            self.gui_data.outline.y - text_size.height()
+        } else {
+            self.gui_data.outline.y + (self.gui_data.outline.height - text_size.height()) * 0.5
+        };
+        self.gui_data.center = Vector2::new(margin + content_width * 0.5, (top + bottom) * 0.5);
+
+        let width = content_width + margin * 2.0;
+        self.gui_data.width = width;
+        width
+    }
+
+    fn draw<T: RaylibDraw>(&self, drawer: &mut GuiDrawer<T>, parameter: &DrawableParameter) -> Option<HoverData> {
+        drawer.draw_all_registers(parameter, self.gui_data.width);
+
+        let mut hover_result = None;
+        if self.expanded {
+            drawer.push_offset();
+            drawer.shift_by(self.gui_data.margin);
+            hover_result = self.circuit.draw(drawer, parameter);
+            drawer.pop_offset();
+        } else {
+            self.draw_control_qbits(drawer, parameter);
+            drawer.draw_rectangle_rec(&self.gui_data.outline, parameter.background_color);
+        }
+
+        let transformed_outline = drawer.transform_rectangle(&self.gui_data.outline);
+        let mouse_position = drawer.mouse_info.world_pos;
+        let hover = hover_result.is_none() && transformed_outline.check_collision_point_rec(mouse_position);
+
+        let color = if hover { parameter.hover_color } else { parameter.foreground_color };
+        drawer.draw_rectangle_lines_ex(&self.gui_data.outline, parameter.register_thickness as i32, color);
+        drawer.draw_text(&parameter.font, &self.name, &self.gui_data.text_position, &self.gui_data.text_size, parameter.foreground_color);
+
+        if hover {
+            return Some(HoverData::for_sub_circuit(self.id));
+        }
+        hover_result
+    }
+}
+
+impl GuiSubCircuit {
+    /// Once expanded, the gates of the content show the controls themselves
+    fn draw_control_qbits<T: RaylibDraw>(&self, drawer: &mut GuiDrawer<T>, parameter: &Style) {
+        let radius = parameter.register_spacing * 0.06;
+        let mut cpos_end = self.gui_data.center.clone();
+
+        for control_bit in self.control_bits.iter() {
+            cpos_end.y = parameter.qbit_y_offset(*control_bit);
+            drawer.draw_line_ex(&self.gui_data.center, &cpos_end, parameter.register_thickness, parameter.foreground_color);
+            drawer.draw_circle_v(&cpos_end, radius, parameter.foreground_color);
+        }
+    }
+}
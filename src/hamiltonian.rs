@@ -0,0 +1,175 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::Backend;
+use crate::pauli::{rotate_to_z, Pauli};
+
+/// Product of Pauli operators written one letter per qbit, qbit 0 first: "XZIY"
+/// is X on qbit 0, Z on qbit 1 and Y on qbit 3. The string may be shorter than
+/// the state, the missing qbits get the identity.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct PauliString(pub Vec<Pauli>);
+
+impl PauliString {
+    /// Return the non identity operators as (qbit, operator) pairs
+    pub fn product(&self) -> Vec<(u8, Pauli)> {
+        self.0.iter()
+            .enumerate()
+            .filter(|(_, p)| **p != Pauli::I)
+            .map(|(qbit, p)| (qbit as u8, *p))
+            .collect()
+    }
+
+    fn check_size<B: Backend>(&self, state: &B) -> Result<(), String> {
+        if self.0.len() > state.nb_qbits() {
+            return Err(format!("Pauli string {} is too long for a state of {} qbits", self, state.nb_qbits()));
+        }
+        Ok(())
+    }
+
+    /// Return the exact expectation value <ψ|P|ψ>
+    pub fn expectation<B: Backend>(&self, state: &B) -> Result<f64, String> {
+        self.check_size(state)?;
+        state.pauli_expectation(&self.product())
+    }
+
+    /// Estimate the expectation value by measuring `nb_shots` copies of the state,
+    /// like a real device would. The state itself is not modified.
+    pub fn estimate<B: Backend>(&self, state: &B, nb_shots: u32) -> Result<f64, String> {
+        self.check_size(state)?;
+        if nb_shots == 0 {
+            return Err("The estimation needs at least one shot".to_string());
+        }
+        let mut rotated = state.snapshot().ok_or("Expectation estimation is not supported by this backend")?;
+        let qbit = match rotate_to_z(&mut rotated, &self.product())? {
+            None => return Ok(1.0),
+            Some(qbit) => qbit as usize,
+        };
+        let nb_ones = (0..nb_shots).filter(|_| rotated.sample()[qbit]).count();
+        Ok(1.0 - 2.0 * nb_ones as f64 / nb_shots as f64)
+    }
+}
+
+impl FromStr for PauliString {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.chars()
+            .map(|c| match c.to_ascii_uppercase() {
+                'I' => Ok(Pauli::I),
+                'X' => Ok(Pauli::X),
+                'Y' => Ok(Pauli::Y),
+                'Z' => Ok(Pauli::Z),
+                _ => Err(format!("Invalid Pauli operator '{}' in {}", c, s)),
+            })
+            .collect::<Result<Vec<Pauli>, String>>()
+            .map(PauliString)
+    }
+}
+
+impl Display for PauliString {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for pauli in self.0.iter() {
+            write!(f, "{:?}", pauli)?;
+        }
+        Ok(())
+    }
+}
+
+/// Weighted sum of Pauli strings
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Hamiltonian {
+    pub terms: Vec<(f64, PauliString)>,
+}
+
+impl Hamiltonian {
+    pub fn new() -> Self {
+        Hamiltonian { terms: vec![] }
+    }
+
+    /// Create the Hamiltonian from (coefficient, Pauli string) pairs
+    pub fn parse(terms: &[(f64, &str)]) -> Result<Self, String> {
+        let mut hamiltonian = Hamiltonian::new();
+        for (coefficient, pauli_string) in terms.iter() {
+            hamiltonian.add_term(*coefficient, pauli_string.parse()?);
+        }
+        Ok(hamiltonian)
+    }
+
+    pub fn add_term(&mut self, coefficient: f64, pauli_string: PauliString) -> &mut Self {
+        self.terms.push((coefficient, pauli_string));
+        self
+    }
+
+    /// Return the exact expectation value <ψ|H|ψ>
+    pub fn expectation<B: Backend>(&self, state: &B) -> Result<f64, String> {
+        self.terms.iter().try_fold(0.0, |sum, (coefficient, pauli_string)| {
+            Ok(sum + coefficient * pauli_string.expectation(state)?)
+        })
+    }
+
+    /// Estimate the expectation value with `nb_shots` measurements per term
+    pub fn estimate<B: Backend>(&self, state: &B, nb_shots: u32) -> Result<f64, String> {
+        self.terms.iter().try_fold(0.0, |sum, (coefficient, pauli_string)| {
+            Ok(sum + coefficient * pauli_string.estimate(state, nb_shots)?)
+        })
+    }
+}
+
+impl Display for Hamiltonian {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let terms: Vec<String> = self.terms.iter().map(|(c, p)| format!("{} {}", c, p)).collect();
+        write!(f, "{}", terms.join(" + "))
+    }
+}
+
+
+#[cfg(test)]
+mod tests_hamiltonian {
+    use crate::base_gate::BaseGate;
+    use crate::computer::QuantumComputer;
+    use crate::stabilizer::Tableau;
+
+    use super::*;
+
+    #[test]
+    fn parse_pauli_strings() {
+        let pauli_string: PauliString = "XzIY".parse().unwrap();
+        assert_eq!(pauli_string.product(), vec![(0, Pauli::X), (1, Pauli::Z), (3, Pauli::Y)]);
+        assert_eq!(pauli_string.to_string(), "XZIY");
+        assert!("XA".parse::<PauliString>().is_err());
+    }
+
+    #[test]
+    fn hamiltonian_expectation() {
+        let computer = QuantumComputer::new(2);
+        let bell = computer.bell_state().build().unwrap();
+        let dense = bell.execute(&computer.zero_state()).unwrap();
+        let dense = dense.current_state();
+        let tableau = bell.execute_on(Tableau::new(2)).unwrap();
+        let tableau = tableau.current_state();
+        let hamiltonian = Hamiltonian::parse(&[(0.5, "ZZ"), (0.25, "XX"), (2.0, "YY"), (3.0, "ZI"), (-1.0, "")]).unwrap();
+
+        assert!((hamiltonian.expectation(dense).unwrap() - (0.5 + 0.25 - 2.0 - 1.0)).abs() < 1e-9);
+        assert!((hamiltonian.expectation(tableau).unwrap() - (0.5 + 0.25 - 2.0 - 1.0)).abs() < 1e-9);
+        assert!("ZZZ".parse::<PauliString>().unwrap().expectation(dense).is_err());
+    }
+
+    #[test]
+    fn estimate_converges_to_expectation() {
+        let computer = QuantumComputer::new(2);
+        let mut state = computer.zero_state();
+        state.apply_gate(&BaseGate::Hadamard(0), &[]).unwrap();
+        state.apply_gate(&BaseGate::S(0), &[]).unwrap();
+        state.apply_gate(&BaseGate::Hadamard(1), &[]).unwrap();
+        let hamiltonian = Hamiltonian::parse(&[(1.0, "YX"), (0.5, "ZI")]).unwrap();
+
+        let exact = hamiltonian.expectation(&state).unwrap();
+        let estimate = hamiltonian.estimate(&state, 2000).unwrap();
+        assert!((exact - 1.0).abs() < 1e-9);
+        assert!((estimate - exact).abs() < 0.15);
+        assert!((state.probability_of_one(1) - 0.5).abs() < 1e-9);
+    }
+}
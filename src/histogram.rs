@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+use std::f64::consts::{FRAC_PI_3, FRAC_PI_4, PI};
+
+use crate::ket::TermOrder;
+use crate::measure::MeasureCount;
+use crate::state::{QbitOrdering, QuantumState};
+
+/// Bars narrower than this are not drawn, the labels are truncated instead
+const MIN_BAR_WIDTH: usize = 10;
+const PARTIAL_BLOCKS: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+const PHASE_GLYPHS: [char; 8] = ['→', '↗', '↑', '↖', '←', '↙', '↓', '↘'];
+/// ANSI colors around the hue circle: red, yellow, green, cyan, blue, magenta
+const PHASE_COLORS: [u8; 6] = [31, 33, 32, 36, 34, 35];
+
+/// How the phase of the amplitudes is shown next to the probabilities
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PhaseStyle {
+    Hidden,
+    /// an arrow pointing in the direction of the amplitude in the complex plane
+    Glyph,
+    /// the bar is colored with ANSI codes, red for the real positive amplitudes
+    Color,
+}
+
+/// Draw horizontal bar charts of probabilities or measurement counts for the terminal:
+///
+/// ```text
+/// 00 │██████████████████████     │ 0.5000 →
+/// 11 │██████████████████████     │ 0.5000 ↑
+/// ```
+///
+/// The bars are scaled so that the largest one fills the available width,
+/// and the labels are truncated when they leave no room for the bars.
+#[derive(Clone, Debug)]
+pub struct Histogram {
+    width: usize,
+    order: TermOrder,
+    top: Option<usize>,
+    threshold: f64,
+    phase: PhaseStyle,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram { width: 80, order: TermOrder::Index, top: None, threshold: 1e-12, phase: PhaseStyle::Glyph }
+    }
+}
+
+struct Row {
+    label: String,
+    value: f64,
+    text: String,
+    phase: Option<f64>,
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Histogram::default()
+    }
+
+    /// Use the width of the terminal given by the `COLUMNS` environment variable, 80 if it is not set
+    pub fn for_terminal() -> Self {
+        let width = std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok()).unwrap_or(80);
+        Histogram::default().with_width(width)
+    }
+
+    /// Total width of the lines, in characters
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn sorted_by(mut self, order: TermOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Only draw the `top` largest bars
+    pub fn with_top(mut self, top: usize) -> Self {
+        self.top = Some(top);
+        self
+    }
+
+    /// Hide the bars whose probability or frequency is below the threshold
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn with_phase(mut self, phase: PhaseStyle) -> Self {
+        self.phase = phase;
+        self
+    }
+
+    /// Draw the probability of each basis state of the state, labelled with its bitstring
+    pub fn probabilities(&self, state: &QuantumState) -> String {
+        let rows = state.iter()
+            .enumerate()
+            .map(|(index, amplitude)| Row {
+                label: state.ordering().bitstring(state.nb_qbits(), index),
+                value: amplitude.norm_sqr(),
+                text: format!("{:.4}", amplitude.norm_sqr()),
+                phase: Some(amplitude.arg()),
+            })
+            .collect();
+        self.render(rows)
+    }
+
+    /// Draw the number of occurrences of each label
+    pub fn counts<L: ToString>(&self, counts: impl IntoIterator<Item=(L, u32)>) -> String {
+        let counts: Vec<(String, u32)> = counts.into_iter().map(|(label, count)| (label.to_string(), count)).collect();
+        let total = counts.iter().map(|(_, count)| *count as f64).sum::<f64>().max(1.0);
+        let rows = counts.into_iter()
+            .map(|(label, count)| Row {
+                label,
+                value: count as f64 / total,
+                text: format!("{} ({:.1}%)", count, 100.0 * count as f64 / total),
+                phase: None,
+            })
+            .collect();
+        self.render(rows)
+    }
+
+    /// Draw the outcomes of a measurement
+    pub fn measure_count(&self, count: &MeasureCount) -> String {
+        self.counts([("0", count.nb_zero), ("1", count.nb_one)])
+    }
+
+    /// Draw the values of a register measurement, written in binary with `nb_bits` bits
+    pub fn register_counts(&self, counts: &HashMap<u64, u32>, nb_bits: u8) -> String {
+        self.counts(counts.iter().map(|(value, count)| (format!("{:0width$b}", value, width = nb_bits as usize), *count)))
+    }
+
+    /// Draw the outcomes of several shots, as given by [`Backend::sample`](crate::backend::Backend::sample),
+    /// labelled with the bitstrings of the basis states in the given ordering
+    pub fn shots(&self, samples: &[Vec<bool>], ordering: QbitOrdering) -> String {
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        for sample in samples.iter() {
+            let nb_qbits = sample.len() as u8;
+            let index = sample.iter()
+                .enumerate()
+                .filter(|(_, one)| **one)
+                .map(|(qbit, _)| ordering.qbit_mask(nb_qbits, qbit as u8))
+                .sum();
+            *counts.entry(ordering.bitstring(nb_qbits, index)).or_insert(0) += 1;
+        }
+        self.counts(counts)
+    }
+
+    fn render(&self, mut rows: Vec<Row>) -> String {
+        rows.retain(|r| r.value > self.threshold);
+        let nb_rows = rows.len();
+        rows.sort_by(|a, b| b.value.total_cmp(&a.value).then_with(|| a.label.cmp(&b.label)));
+        if let Some(top) = self.top {
+            rows.truncate(top);
+        }
+        if self.order == TermOrder::Index {
+            rows.sort_by(|a, b| a.label.len().cmp(&b.label.len()).then_with(|| a.label.cmp(&b.label)));
+        }
+
+        let text_width = rows.iter().map(|r| r.text.chars().count()).max().unwrap_or(0);
+        let phase_width = if self.phase == PhaseStyle::Glyph && rows.iter().any(|r| r.phase.is_some()) { 2 } else { 0 };
+        let decoration_width = 4 + text_width + phase_width;
+        let mut label_width = rows.iter().map(|r| r.label.chars().count()).max().unwrap_or(0);
+        if label_width + decoration_width + MIN_BAR_WIDTH > self.width {
+            label_width = self.width.saturating_sub(decoration_width + MIN_BAR_WIDTH).max(2);
+        }
+        let bar_width = self.width.saturating_sub(label_width + decoration_width).max(MIN_BAR_WIDTH);
+        let max_value = rows.iter().map(|r| r.value).fold(0.0, f64::max);
+
+        let mut result = String::new();
+        for row in rows.iter() {
+            let bar = bar(row.value / max_value, bar_width);
+            let bar = match (self.phase, row.phase) {
+                (PhaseStyle::Color, Some(phase)) => format!("\x1b[{}m{}\x1b[0m", phase_color(phase), bar),
+                _ => bar,
+            };
+            let glyph = match (phase_width, row.phase) {
+                (0, _) => "".to_string(),
+                (_, Some(phase)) => format!(" {}", phase_glyph(phase)),
+                (_, None) => "  ".to_string(),
+            };
+            result.push_str(&format!("{:<lw$} │{}│ {:>tw$}{}\n", truncate(&row.label, label_width), bar, row.text, glyph,
+                                     lw = label_width, tw = text_width));
+        }
+        if rows.len() < nb_rows {
+            result.push_str(&format!("… {} more\n", nb_rows - rows.len()));
+        }
+        result
+    }
+}
+
+/// Return a bar of `width` characters filled at `ratio`, with a precision of one eighth of character
+fn bar(ratio: f64, width: usize) -> String {
+    let eighths = (ratio * (width * 8) as f64).round() as usize;
+    let mut result = "█".repeat(eighths / 8);
+    let partial = eighths % 8;
+    if partial != 0 {
+        result.push(PARTIAL_BLOCKS[partial]);
+    }
+    let filled = result.chars().count();
+    result.push_str(&" ".repeat(width.saturating_sub(filled)));
+    result
+}
+
+fn truncate(label: &str, width: usize) -> String {
+    if label.chars().count() <= width {
+        return label.to_string();
+    }
+    let mut result: String = label.chars().take(width - 1).collect();
+    result.push('…');
+    result
+}
+
+fn phase_glyph(phase: f64) -> char {
+    PHASE_GLYPHS[sector(phase, FRAC_PI_4, 8)]
+}
+
+fn phase_color(phase: f64) -> u8 {
+    PHASE_COLORS[sector(phase, FRAC_PI_3, 6)]
+}
+
+/// Return the sector of the circle of angle `size` containing the phase
+fn sector(phase: f64, size: f64, nb_sectors: usize) -> usize {
+    (phase.rem_euclid(2.0 * PI) / size).round() as usize % nb_sectors
+}
+
+
+#[cfg(test)]
+mod tests_histogram {
+    use num_complex::Complex64;
+
+    use super::*;
+
+    #[test]
+    fn probabilities_with_phase() {
+        let state = QuantumState::from_amplitudes(vec![Complex64::new(1.0, 0.0), Complex64::new(0.0, 0.0),
+                                                       Complex64::new(0.0, 0.0), Complex64::new(0.0, 1.0)]).unwrap();
+        let histogram = Histogram::new().with_width(30).probabilities(&state);
+        assert_eq!(histogram, "00 │████████████████│ 0.5000 →\n11 │████████████████│ 0.5000 ↑\n");
+
+        let minus = QuantumState::from_amplitudes(vec![Complex64::new(1.0, 0.0), Complex64::new(-1.0, 0.0)]).unwrap();
+        let colored = Histogram::new().with_width(30).with_phase(PhaseStyle::Color).probabilities(&minus);
+        assert!(colored.starts_with("0 │\x1b[31m"), "{}", colored);
+        assert!(colored.contains("1 │\x1b[36m"), "{}", colored);
+    }
+
+    #[test]
+    fn counts_are_sorted_and_truncated() {
+        let counts: HashMap<u64, u32> = vec![(0, 10), (1, 30), (2, 0), (3, 60)].into_iter().collect();
+        let histogram = Histogram::new().with_width(40).sorted_by(TermOrder::Probability).with_top(2).register_counts(&counts, 2);
+        let lines: Vec<&str> = histogram.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("11 │████████████████████████│ 60 (60.0%)"), "{}", lines[0]);
+        assert!(lines[1].starts_with("01 │████████████            │ 30 (30.0%)"), "{}", lines[1]);
+        assert_eq!(lines[2], "… 1 more");
+
+        let measure = Histogram::new().with_width(30).measure_count(&MeasureCount { nb_zero: 3, nb_one: 1 });
+        assert_eq!(measure.lines().count(), 2);
+        assert!(measure.lines().all(|l| l.chars().count() == 30));
+    }
+
+    #[test]
+    fn labels_are_truncated_to_the_width() {
+        let state = QuantumState::from_bitstring("0110011001").unwrap();
+        let histogram = Histogram::new().with_width(24).with_phase(PhaseStyle::Hidden).probabilities(&state);
+        assert_eq!(histogram, "011… │██████████│ 1.0000\n");
+    }
+
+    #[test]
+    fn shots_in_qbit_ordering() {
+        let samples = vec![vec![true, false], vec![true, false], vec![false, false]];
+        let msb = Histogram::new().with_width(40).shots(&samples, QbitOrdering::MsbFirst);
+        let lsb = Histogram::new().with_width(40).shots(&samples, QbitOrdering::LsbFirst);
+        assert!(msb.starts_with("00 ") && msb.contains("\n10 │"), "{}", msb);
+        assert!(lsb.starts_with("00 ") && lsb.contains("\n01 │"), "{}", lsb);
+    }
+}
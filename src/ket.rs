@@ -0,0 +1,307 @@
+use std::f64::consts::FRAC_PI_4;
+use std::fmt::{Display, Formatter};
+
+use num_complex::Complex64;
+
+use crate::state::QuantumState;
+
+/// Tolerance used to recognize the symbolic values
+const TOLERANCE: f64 = 1e-9;
+/// Largest denominator of the squared magnitudes written symbolically
+const MAX_DENOMINATOR: u64 = 32;
+
+/// Order of the terms of the formatted state
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TermOrder {
+    /// by increasing basis state index
+    Index,
+    /// by decreasing probability
+    Probability,
+}
+
+/// Format a state in ket notation: `1/√2 (|00> + |11>)`.
+///
+/// The magnitudes whose square is a simple fraction and the phases that are multiples
+/// of π/4 are written symbolically, and the terms that share the same magnitude are grouped.
+/// The basis states are written in the ordering of the state.
+#[derive(Clone, Debug)]
+pub struct KetFormatter {
+    order: TermOrder,
+    top: Option<usize>,
+    threshold: f64,
+    latex: bool,
+    precision: usize,
+}
+
+impl Default for KetFormatter {
+    fn default() -> Self {
+        KetFormatter { order: TermOrder::Index, top: None, threshold: 1e-12, latex: false, precision: 4 }
+    }
+}
+
+/// Coefficient of a term: symbolic (squared magnitude k/n and phase m.π/4) or numeric
+#[derive(Clone, Copy, PartialEq)]
+enum Coefficient {
+    Symbolic { k: u64, n: u64, phase: i32 },
+    Numeric(Complex64),
+}
+
+struct Term {
+    index: usize,
+    probability: f64,
+    coefficient: Coefficient,
+}
+
+impl KetFormatter {
+    pub fn new() -> Self {
+        KetFormatter::default()
+    }
+
+    pub fn sorted_by(mut self, order: TermOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Only show the `top` most probable basis states
+    pub fn with_top(mut self, top: usize) -> Self {
+        self.top = Some(top);
+        self
+    }
+
+    /// Hide the basis states whose probability is below the threshold
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Number of decimals of the amplitudes that are not written symbolically
+    pub fn with_precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Write LaTeX instead of plain text
+    pub fn latex(mut self) -> Self {
+        self.latex = true;
+        self
+    }
+
+    pub fn format(&self, state: &QuantumState) -> String {
+        let mut terms: Vec<Term> = state.iter()
+            .enumerate()
+            .map(|(index, a)| Term { index, probability: a.norm_sqr(), coefficient: recognize(*a) })
+            .filter(|t| t.probability > self.threshold)
+            .collect();
+        let nb_terms = terms.len();
+
+        terms.sort_by(|a, b| b.probability.total_cmp(&a.probability).then(a.index.cmp(&b.index)));
+        if let Some(top) = self.top {
+            terms.truncate(top);
+        }
+        if self.order == TermOrder::Index {
+            terms.sort_by_key(|t| t.index);
+        }
+
+        let mut groups: Vec<Vec<&Term>> = vec![];
+        for term in terms.iter() {
+            let group = match term.coefficient {
+                Coefficient::Symbolic { k, n, .. } => groups.iter_mut().find(|g| matches!(g[0].coefficient, Coefficient::Symbolic { k: gk, n: gn, .. } if gk == k && gn == n)),
+                Coefficient::Numeric(_) => None,
+            };
+            match group {
+                Some(group) => group.push(term),
+                None => groups.push(vec![term]),
+            }
+        }
+
+        let parts: Vec<String> = groups.iter().map(|group| self.format_group(state, group)).collect();
+        let mut result = join_terms(&parts);
+        if result.is_empty() {
+            result = "0".to_string();
+        }
+        if terms.len() < nb_terms {
+            result.push_str(if self.latex { " + \\dots" } else { " + …" });
+        }
+        result
+    }
+
+    fn format_group(&self, state: &QuantumState, group: &[&Term]) -> String {
+        match (group, group[0].coefficient) {
+            ([term], coefficient) => format!("{}{}", self.coefficient(coefficient), self.ket(state, term.index)),
+            (_, Coefficient::Symbolic { k, n, .. }) => {
+                let inner: Vec<String> = group.iter()
+                    .map(|t| match t.coefficient {
+                        Coefficient::Symbolic { phase, .. } => format!("{}{}", self.phase(phase), self.ket(state, t.index)),
+                        Coefficient::Numeric(_) => self.ket(state, t.index),
+                    })
+                    .collect();
+                let magnitude = self.coefficient(Coefficient::Symbolic { k, n, phase: 0 });
+                let separator = if self.latex { "\\left(" } else { "(" };
+                let closing = if self.latex { "\\right)" } else { ")" };
+                format!("{}{}{}{}", magnitude, separator, join_terms(&inner), closing)
+            }
+            (_, Coefficient::Numeric(_)) => unreachable!("numeric terms are not grouped"),
+        }
+    }
+
+    fn ket(&self, state: &QuantumState, index: usize) -> String {
+        let bits = state.ordering().bitstring(state.nb_qbits(), index);
+        if self.latex { format!("|{}\\rangle", bits) } else { format!("|{}>", bits) }
+    }
+
+    /// Return the phase m.π/4 as a factor, empty for 1
+    fn phase(&self, phase: i32) -> String {
+        let pi = if self.latex { "\\pi" } else { "π" };
+        match phase {
+            0 => "".to_string(),
+            4 => "-".to_string(),
+            2 => "i".to_string(),
+            -2 => "-i".to_string(),
+            1 => format!("e^{{i{}/4}}", pi),
+            -1 => format!("e^{{-i{}/4}}", pi),
+            m => format!("e^{{{}i{}/4}}", m, pi),
+        }
+    }
+
+    /// Return the coefficient followed by a space, or only the phase for a magnitude of 1
+    fn coefficient(&self, coefficient: Coefficient) -> String {
+        let text = match coefficient {
+            Coefficient::Symbolic { k: 1, n: 1, phase } => return self.phase(phase),
+            Coefficient::Symbolic { k, n, phase } => {
+                let numerator = match (self.root(k), self.phase(phase)) {
+                    (root, phase) if root == "1" => if phase.is_empty() { "1".to_string() } else { phase },
+                    (root, phase) => format!("{}{}", phase, root),
+                };
+                match (self.root(n), self.latex) {
+                    (denominator, _) if denominator == "1" => numerator,
+                    (denominator, true) => {
+                        let (sign, numerator) = match numerator.strip_prefix('-') {
+                            Some(rest) => ("-", rest.to_string()),
+                            None => ("", numerator),
+                        };
+                        format!("{}\\frac{{{}}}{{{}}}", sign, numerator, denominator)
+                    }
+                    (denominator, false) => format!("{}/{}", numerator, denominator),
+                }
+            }
+            Coefficient::Numeric(a) => self.numeric(a),
+        };
+        format!("{} ", text)
+    }
+
+    /// Return √value, or the integer when value is a perfect square
+    fn root(&self, value: u64) -> String {
+        let root = (value as f64).sqrt().round() as u64;
+        match (root * root == value, self.latex) {
+            (true, _) => root.to_string(),
+            (false, true) => format!("\\sqrt{{{}}}", value),
+            (false, false) => format!("√{}", value),
+        }
+    }
+
+    fn numeric(&self, a: Complex64) -> String {
+        let precision = self.precision;
+        let tolerance = 0.5 * 10f64.powi(-(precision as i32));
+        match (a.re.abs() < tolerance, a.im.abs() < tolerance) {
+            (_, true) => format!("{:.*}", precision, a.re),
+            (true, false) => format!("{:.*}i", precision, a.im),
+            (false, false) => format!("({:.*}{:+.*}i)", precision, a.re, precision, a.im),
+        }
+    }
+}
+
+/// Join the terms with + or - depending on their sign
+fn join_terms(terms: &[String]) -> String {
+    let mut result = String::new();
+    for (i, term) in terms.iter().enumerate() {
+        match (i, term.strip_prefix('-')) {
+            (0, _) => result.push_str(term),
+            (_, Some(rest)) => {
+                result.push_str(" - ");
+                result.push_str(rest);
+            }
+            (_, None) => {
+                result.push_str(" + ");
+                result.push_str(term);
+            }
+        }
+    }
+    result
+}
+
+/// Return the symbolic form of the amplitude if its squared magnitude
+/// is a simple fraction and its phase a multiple of π/4
+fn recognize(amplitude: Complex64) -> Coefficient {
+    let squared = amplitude.norm_sqr();
+    let phase = amplitude.arg() / FRAC_PI_4;
+    let rounded_phase = phase.round();
+    if (phase - rounded_phase).abs() > TOLERANCE {
+        return Coefficient::Numeric(amplitude);
+    }
+    let phase = match rounded_phase as i32 {
+        -4 => 4,
+        m => m,
+    };
+    for n in 1..=MAX_DENOMINATOR {
+        let k = (squared * n as f64).round() as u64;
+        if k > 0 && (squared - k as f64 / n as f64).abs() < TOLERANCE {
+            return Coefficient::Symbolic { k, n, phase };
+        }
+    }
+    Coefficient::Numeric(amplitude)
+}
+
+/// Plain text ket notation with the default [`KetFormatter`]
+impl Display for QuantumState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&KetFormatter::default().format(self))
+    }
+}
+
+
+#[cfg(test)]
+mod tests_ket {
+    use std::f64::consts::FRAC_1_SQRT_2;
+
+    use crate::state::QbitOrdering;
+
+    use super::*;
+
+    fn state(amplitudes: &[(f64, f64)]) -> QuantumState {
+        QuantumState::from_amplitudes(amplitudes.iter().map(|(re, im)| Complex64::new(*re, *im)).collect()).unwrap()
+    }
+
+    #[test]
+    fn symbolic_amplitudes_are_grouped() {
+        assert_eq!(QuantumState::from_bitstrings(&["00", "11"]).unwrap().to_string(), "1/√2 (|00> + |11>)");
+        assert_eq!(state(&[(1.0, 0.0), (-1.0, 0.0)]).to_string(), "1/√2 (|0> - |1>)");
+        assert_eq!(state(&[(1.0, 0.0), (0.0, 1.0)]).to_string(), "1/√2 (|0> + i|1>)");
+        assert_eq!(state(&[(1.0, 0.0), (FRAC_1_SQRT_2, FRAC_1_SQRT_2)]).to_string(), "1/√2 (|0> + e^{iπ/4}|1>)");
+        assert_eq!(state(&[(0.0, 0.0), (0.0, -1.0)]).to_string(), "-i|1>");
+        assert_eq!(state(&[(0.5, 0.0), (0.0, 0.0), (-0.75f64.sqrt(), 0.0), (0.0, 0.0)]).to_string(), "1/2 |00> - √3/2 |10>");
+        assert_eq!(QuantumState::from_bitstring("01").unwrap().to_ordering(QbitOrdering::LsbFirst).to_string(), "|10>");
+    }
+
+    #[test]
+    fn numeric_amplitudes() {
+        let formatted = KetFormatter::new().with_precision(3).format(&state(&[(0.6, 0.0), (0.48, 0.64)]));
+        assert_eq!(formatted, "3/5 |0> + (0.480+0.640i) |1>");
+        let formatted = KetFormatter::new().with_precision(2).format(&state(&[(0.3, 0.1), (0.0, 0.9)]));
+        assert!(formatted.starts_with("(0.31+0.10i) |0>"), "{}", formatted);
+    }
+
+    #[test]
+    fn top_and_probability_order() {
+        let state = state(&[(0.1, 0.0), (0.7, 0.0), (0.0, 0.0), (0.3, 0.0)]);
+        let formatter = KetFormatter::new().with_precision(2).sorted_by(TermOrder::Probability).with_top(2);
+        assert_eq!(formatter.format(&state), "0.91 |01> + 0.39 |11> + …");
+        assert_eq!(KetFormatter::new().with_precision(2).with_threshold(0.05).format(&state), "0.91 |01> + 0.39 |11>");
+    }
+
+    #[test]
+    fn latex_output() {
+        let formatter = KetFormatter::new().latex();
+        assert_eq!(formatter.format(&QuantumState::from_bitstrings(&["00", "11"]).unwrap()), "\\frac{1}{\\sqrt{2}} \\left(|00\\rangle + |11\\rangle\\right)");
+        assert_eq!(formatter.format(&state(&[(0.0, 0.0), (-1.0, 0.0)])), "-|1\\rangle");
+    }
+}
@@ -1,4 +1,6 @@
 pub mod state;
+pub mod state_io;
+pub mod backend;
 pub mod builder;
 pub mod computer;
 pub mod gate_op;
@@ -14,6 +16,24 @@ pub mod measure;
 pub mod execution;
 pub mod base_gate;
 pub mod common_gate;
+pub mod stabilizer;
+pub mod sparse;
+pub mod mps;
+pub mod linalg;
+pub mod debugger;
+pub mod observer;
+pub mod pauli;
+pub mod hamiltonian;
+pub mod entanglement;
+pub mod ket;
+pub mod histogram;
+pub mod postselect;
+pub mod parameter;
+pub mod gradient;
+pub mod optimizer;
+pub mod variational;
+pub mod compose;
+pub mod sub_circuit;
 mod _loop;
 
 // with 16 qbits, one state holds 1MBytes of data
@@ -0,0 +1,228 @@
+use std::fmt::{Debug, Formatter, Result};
+
+use num_complex::Complex64;
+use num_traits::{One, Zero};
+
+/// Maximal number of sweeps of the Jacobi algorithm
+const MAX_SWEEPS: usize = 100;
+const EPSILON: f64 = 1e-14;
+
+/// Dense complex matrix stored row by row
+#[derive(Clone)]
+pub struct Matrix {
+    nb_rows: usize,
+    nb_columns: usize,
+    data: Vec<Complex64>,
+}
+
+impl Matrix {
+    pub fn zero(nb_rows: usize, nb_columns: usize) -> Self {
+        Matrix { nb_rows, nb_columns, data: vec![Complex64::zero(); nb_rows * nb_columns] }
+    }
+
+    pub fn identity(size: usize) -> Self {
+        let mut result = Matrix::zero(size, size);
+        for i in 0..size {
+            result.set(i, i, Complex64::one());
+        }
+        result
+    }
+
+    /// Create a matrix from its elements given row by row
+    pub fn from_rows(nb_rows: usize, nb_columns: usize, data: Vec<Complex64>) -> Self {
+        assert_eq!(data.len(), nb_rows * nb_columns, "Invalid number of elements for a {}x{} matrix", nb_rows, nb_columns);
+        Matrix { nb_rows, nb_columns, data }
+    }
+
+    pub fn nb_rows(&self) -> usize {
+        self.nb_rows
+    }
+
+    pub fn nb_columns(&self) -> usize {
+        self.nb_columns
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> Complex64 {
+        self.data[row * self.nb_columns + column]
+    }
+
+    pub fn set(&mut self, row: usize, column: usize, value: Complex64) {
+        self.data[row * self.nb_columns + column] = value;
+    }
+
+    /// Return the conjugate transpose of this matrix
+    pub fn adjoint(&self) -> Matrix {
+        let mut result = Matrix::zero(self.nb_columns, self.nb_rows);
+        for row in 0..self.nb_rows {
+            for column in 0..self.nb_columns {
+                result.set(column, row, self.get(row, column).conj());
+            }
+        }
+        result
+    }
+
+    pub fn mul(&self, other: &Matrix) -> Matrix {
+        assert_eq!(self.nb_columns, other.nb_rows, "Incompatible matrix sizes");
+        let mut result = Matrix::zero(self.nb_rows, other.nb_columns);
+        for row in 0..self.nb_rows {
+            for k in 0..self.nb_columns {
+                let lhs = self.get(row, k);
+                if lhs.is_zero() {
+                    continue;
+                }
+                for column in 0..other.nb_columns {
+                    result.data[row * other.nb_columns + column] += lhs * other.get(k, column);
+                }
+            }
+        }
+        result
+    }
+
+    pub fn trace(&self) -> Complex64 {
+        (0..self.nb_rows.min(self.nb_columns)).map(|i| self.get(i, i)).sum()
+    }
+
+    fn column_dot(&self, p: usize, q: usize) -> Complex64 {
+        (0..self.nb_rows).map(|row| self.get(row, p).conj() * self.get(row, q)).sum()
+    }
+
+    fn column_norm_sqr(&self, p: usize) -> f64 {
+        (0..self.nb_rows).map(|row| self.get(row, p).norm_sqr()).sum()
+    }
+
+    /// Replace the columns p and q by c.p - s.q and s.p + c.q
+    fn rotate_columns(&mut self, p: usize, q: usize, c: f64, s: Complex64) {
+        for row in 0..self.nb_rows {
+            let ap = self.get(row, p);
+            let aq = self.get(row, q);
+            self.set(row, p, ap * c - aq * s);
+            self.set(row, q, ap * s.conj() + aq * c);
+        }
+    }
+}
+
+impl Debug for Matrix {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(f, "Matrix {}x{}", self.nb_rows, self.nb_columns)?;
+        for row in 0..self.nb_rows {
+            let line: Vec<String> = (0..self.nb_columns)
+                .map(|column| {
+                    let value = self.get(row, column);
+                    format!("({:.6},{:.6})", value.re, value.im)
+                })
+                .collect();
+            writeln!(f, "{}", line.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Singular value decomposition `A = U.diag(S).V†`,
+/// singular values sorted in decreasing order
+pub struct Svd {
+    pub u: Matrix,
+    pub singular_values: Vec<f64>,
+    pub v: Matrix,
+}
+
+/// Compute the singular value decomposition of a matrix with the
+/// one-sided Jacobi algorithm. If the matrix is `m x n`, U is `m x k`,
+/// V is `n x k` with `k = min(m,n)`.
+pub fn svd(matrix: &Matrix) -> Svd {
+    if matrix.nb_rows < matrix.nb_columns {
+        let Svd { u, singular_values, v } = svd(&matrix.adjoint());
+        return Svd { u: v, singular_values, v: u };
+    }
+
+    let nb_columns = matrix.nb_columns;
+    let mut work = matrix.clone();
+    let mut v = Matrix::identity(nb_columns);
+
+    for _ in 0..MAX_SWEEPS {
+        let mut rotated = false;
+        for p in 0..nb_columns {
+            for q in p + 1..nb_columns {
+                let alpha = work.column_norm_sqr(p);
+                let beta = work.column_norm_sqr(q);
+                let gamma = work.column_dot(p, q);
+                let gamma_norm = gamma.norm();
+                if gamma_norm <= EPSILON * (alpha * beta).sqrt() || gamma_norm < f64::MIN_POSITIVE {
+                    continue;
+                }
+                rotated = true;
+                let phase = gamma / gamma_norm;
+                let zeta = (beta - alpha) / (2.0 * gamma_norm);
+                let t = zeta.signum() / (zeta.abs() + (1.0 + zeta * zeta).sqrt());
+                let c = 1.0 / (1.0 + t * t).sqrt();
+                let s = phase.conj() * (c * t);
+                work.rotate_columns(p, q, c, s);
+                v.rotate_columns(p, q, c, s);
+            }
+        }
+        if !rotated {
+            break;
+        }
+    }
+
+    let mut order: Vec<(usize, f64)> = (0..nb_columns).map(|j| (j, work.column_norm_sqr(j).sqrt())).collect();
+    order.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+
+    let mut u = Matrix::zero(matrix.nb_rows, nb_columns);
+    let mut sorted_v = Matrix::zero(nb_columns, nb_columns);
+    let mut singular_values = Vec::with_capacity(nb_columns);
+    for (k, (j, sigma)) in order.into_iter().enumerate() {
+        singular_values.push(sigma);
+        for row in 0..matrix.nb_rows {
+            let value = if sigma > f64::MIN_POSITIVE { work.get(row, j) / sigma } else { Complex64::zero() };
+            u.set(row, k, value);
+        }
+        for row in 0..nb_columns {
+            sorted_v.set(row, k, v.get(row, j));
+        }
+    }
+
+    Svd { u, singular_values, v: sorted_v }
+}
+
+
+#[cfg(test)]
+mod tests_linalg {
+    use super::*;
+
+    fn reconstruct(svd: &Svd) -> Matrix {
+        let mut us = svd.u.clone();
+        for row in 0..us.nb_rows() {
+            for column in 0..us.nb_columns() {
+                us.set(row, column, us.get(row, column) * svd.singular_values[column]);
+            }
+        }
+        us.mul(&svd.v.adjoint())
+    }
+
+    #[test]
+    fn svd_of_rectangular_complex_matrices() {
+        let data: Vec<Complex64> = (0..12).map(|i| Complex64::new((i as f64).sin(), (i as f64 * 0.7).cos())).collect();
+        for (nb_rows, nb_columns) in [(3, 4), (4, 3), (2, 6)] {
+            let matrix = Matrix::from_rows(nb_rows, nb_columns, data.clone());
+            let svd = svd(&matrix);
+            let result = reconstruct(&svd);
+
+            assert!(svd.singular_values.windows(2).all(|w| w[0] >= w[1]));
+            for row in 0..nb_rows {
+                for column in 0..nb_columns {
+                    assert!((result.get(row, column) - matrix.get(row, column)).norm() < 1e-10);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn svd_of_rank_one_matrix() {
+        let a = Complex64::new(0.5, 0.5);
+        let matrix = Matrix::from_rows(2, 2, vec![a, a, a, a]);
+        let svd = svd(&matrix);
+
+        assert!((svd.singular_values[0] - 2.0 * a.norm()).abs() < 1e-12);
+        assert!(svd.singular_values[1].abs() < 1e-12);
+    }
+}
@@ -1,7 +1,11 @@
 use serde::{Deserialize, Serialize};
 
-use crate::execution::ExecutionContext;
+use crate::backend::Backend;
+use crate::debugger::ElementPath;
+use crate::execution::{ExecutionContext, ExecutionError};
+use crate::gate::check_for_no_duplicate;
 use crate::operation::{CircuitElement, QuantumOperation};
+use crate::pauli::{rotate_back, rotate_to_z, Pauli};
 
 #[derive(Debug, Copy, Clone)]
 pub struct MeasureCount {
@@ -9,12 +13,96 @@ pub struct MeasureCount {
     pub nb_one: u32,
 }
 
+/// Result of one measurement
+#[derive(Clone, PartialEq, Debug)]
+pub struct MeasureRecord {
+    /// id of the measurement
+    pub id: String,
+    /// path of the measurement in the circuit
+    pub path: ElementPath,
+    /// current iteration (starting at 0) of each loop enclosing the measurement
+    pub iterations: Vec<u32>,
+    /// true if |1> has been measured (for a register, true if the value is not 0)
+    pub outcome: bool,
+    /// the measured value for a register measurement
+    pub register_value: Option<u64>,
+    /// probability of the outcome before the measurement
+    pub probability: f64,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Measure {
     /// Uniq value used to identify the measurement
     pub id: String,
-    /// the qbit to measure
+    /// the qbit to measure, the first qbit of a Pauli product measurement
     pub qbit_target: u8,
+    /// the basis used to measure the target
+    #[serde(default = "z_basis")]
+    pub basis: Pauli,
+    /// the measured Pauli product, None for a single qbit measurement
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pauli_product: Option<Vec<(u8, Pauli)>>,
+}
+
+fn z_basis() -> Pauli {
+    Pauli::Z
+}
+
+impl Measure {
+    /// Measurement of one qbit in the Z basis
+    pub fn new(id: &str, qbit_target: u8) -> Self {
+        Measure::in_basis(id, qbit_target, Pauli::Z)
+    }
+
+    /// Measurement of one qbit in the given basis
+    pub fn in_basis(id: &str, qbit_target: u8, basis: Pauli) -> Self {
+        Measure { id: id.to_string(), qbit_target, basis, pauli_product: None }
+    }
+
+    /// Measurement of a product of Pauli operators. The outcome is
+    /// 'zero' for the eigenvalue +1 and 'one' for the eigenvalue -1.
+    /// An empty product is rejected when the circuit is built.
+    pub fn pauli_product(id: &str, product: &[(u8, Pauli)]) -> Self {
+        let (qbit_target, basis) = product.first().cloned().unwrap_or((0, Pauli::Z));
+        Measure { id: id.to_string(), qbit_target, basis, pauli_product: Some(product.to_vec()) }
+    }
+
+    /// Return all the measured qbits with their operator, the target first
+    pub fn product(&self) -> Vec<(u8, Pauli)> {
+        match &self.pauli_product {
+            Some(product) => product.clone(),
+            None => vec![(self.qbit_target, self.basis)],
+        }
+    }
+
+    /// Measure and return the outcome and its probability
+    fn measure<B: Backend>(&self, state: &mut B) -> Result<(bool, f64), String> {
+        let (measured_one, probability_of_one) = match (self.basis, self.pauli_product.is_none()) {
+            (Pauli::Z, true) => {
+                let probability_of_one = state.probability_of_one(self.qbit_target);
+                (state.measure(self.qbit_target), probability_of_one)
+            }
+            (_, true) => {
+                let product = self.product();
+                let result = match rotate_to_z(state, &product)? {
+                    Some(qbit) => {
+                        let probability_of_one = state.probability_of_one(qbit);
+                        (state.measure(qbit), probability_of_one)
+                    }
+                    None => (false, 0.0),
+                };
+                rotate_back(state, &product)?;
+                result
+            }
+            (_, false) => {
+                let product = self.product();
+                let probability_of_one = (1.0 - state.pauli_expectation(&product)?) / 2.0;
+                (state.measure_pauli_product(&product)?, probability_of_one)
+            }
+        };
+        let probability = if measured_one { probability_of_one } else { 1.0 - probability_of_one };
+        Ok((measured_one, probability))
+    }
 }
 
 impl Into<CircuitElement> for Measure {
@@ -24,27 +112,172 @@ impl Into<CircuitElement> for Measure {
 }
 
 impl QuantumOperation for Measure {
-    fn apply(&self, context: &mut ExecutionContext) {
-        let mask = context.mask(self.qbit_target);
-        let select_state = context.pick_on_state();
-
-        context.set_measurement(select_state);
-        let measured_one = (select_state & mask) == mask;
+    fn apply<B: Backend>(&self, context: &mut ExecutionContext<B>) -> Result<(), ExecutionError> {
+        let (measured_one, probability) = self.measure(context.current_state_mut())?;
 
         match measured_one {
             true => context.increase_one(&self.id),
             false => context.increase_zero(&self.id),
         }
+        context.record_measure(&self.id, measured_one, probability);
+        context.notify(|o, path| o.measurement(path, &self.id, self.qbit_target, measured_one))?;
+        Ok(())
     }
 
     fn max_qbit_idx(&self) -> u8 {
-        self.qbit_target
+        self.product().iter().map(|(qbit, _)| *qbit).max().unwrap_or(self.qbit_target)
     }
 
     fn check_validity(&self, nb_qbits: u8) -> Result<(), String> {
-        if self.qbit_target >= nb_qbits {
-            return Err(format!("Index to high {}", self.qbit_target));
+        if matches!(&self.pauli_product, Some(product) if product.is_empty()) {
+            return Err(format!("The Pauli product measured by {} is empty", self.id));
+        }
+        let qbits: Vec<u8> = self.product().iter().map(|(qbit, _)| *qbit).collect();
+        for qbit in qbits.iter() {
+            if *qbit >= nb_qbits {
+                return Err(format!("Index to high {}", qbit));
+            }
+        }
+        check_for_no_duplicate(qbits)
+    }
+}
+
+
+/// Measurement of several qbits whose result is recorded as an integer
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RegisterMeasure {
+    /// Uniq value used to identify the measurement
+    pub id: String,
+    /// the qbits to measure, the first one gives the most significant bit of the value
+    pub qbits: Vec<u8>,
+}
+
+impl Into<CircuitElement> for RegisterMeasure {
+    fn into(self) -> CircuitElement {
+        CircuitElement::RegisterMeasure(self)
+    }
+}
+
+impl RegisterMeasure {
+    /// Measure the qbits one after the other and return the value and its probability.
+    /// Each qbit is projected with [`postselect`](Backend::postselect) because [`measure`](Backend::measure)
+    /// may collapse the whole register, which would hide the probabilities of the next qbits.
+    fn measure<B: Backend>(&self, state: &mut B) -> Result<(u64, f64), String> {
+        let mut value = 0;
+        let mut probability = 1.0;
+        for qbit in self.qbits.iter() {
+            let measured_one = rand::random::<f64>() < state.probability_of_one(*qbit);
+            probability *= state.postselect(*qbit, measured_one)?;
+            value = (value << 1) | (measured_one as u64);
         }
+        Ok((value, probability))
+    }
+}
+
+impl QuantumOperation for RegisterMeasure {
+    fn apply<B: Backend>(&self, context: &mut ExecutionContext<B>) -> Result<(), ExecutionError> {
+        let (value, probability) = self.measure(context.current_state_mut())?;
+        context.record_register_measure(&self.id, value, probability);
+        context.notify(|o, path| o.register_measurement(path, &self.id, &self.qbits, value))?;
         Ok(())
     }
+
+    fn max_qbit_idx(&self) -> u8 {
+        self.qbits.iter().max().cloned().unwrap_or(0)
+    }
+
+    fn check_validity(&self, nb_qbits: u8) -> Result<(), String> {
+        if self.qbits.is_empty() || self.qbits.len() > 64 {
+            return Err(format!("A register measurement must have between 1 and 64 qbits, got {}", self.qbits.len()));
+        }
+        for qbit in self.qbits.iter() {
+            if *qbit >= nb_qbits {
+                return Err(format!("Index to high {}", qbit));
+            }
+        }
+        check_for_no_duplicate(self.qbits.clone())
+    }
+}
+
+
+#[cfg(test)]
+mod tests_measure {
+    use crate::common_gate::CommonGate::{Hadamard, Not};
+    use crate::computer::QuantumComputer;
+    use crate::condition::StopCondition;
+    use crate::mps::MpsState;
+    use crate::sparse::SparseState;
+
+    #[test]
+    fn register_value_uses_first_qbit_as_msb() {
+        let computer = QuantumComputer::new(3);
+        let circuit = computer.new_circuit_builder()
+            .add_operation(Not(0))
+            .add_operation(Not(2))
+            .add_measure_register("r", &[0, 1, 2])
+            .add_measure_register("r2", &[2, 1])
+            .build()
+            .unwrap();
+
+        let result = circuit.execute(&computer.zero_state()).unwrap();
+        assert_eq!(result.last_register_value("r"), Some(0b101));
+        assert_eq!(result.last_register_value("r2"), Some(0b10));
+        assert_eq!(result.get_register_count("r", 0b101), 1);
+        assert!((result.history()[0].probability - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn probability_of_a_product_state_value() {
+        let computer = QuantumComputer::new(2);
+        let circuit = computer.new_circuit_builder()
+            .add_operation(Hadamard(0))
+            .add_operation(Hadamard(1))
+            .add_measure_register("r", &[0, 1])
+            .build()
+            .unwrap();
+
+        let dense = circuit.execute(&computer.zero_state()).unwrap();
+        let sparse = circuit.execute_on(SparseState::zero(2)).unwrap();
+        let mps = circuit.execute_on(MpsState::zero(2, 4)).unwrap();
+        let stabilizer = circuit.execute_stabilizer().unwrap();
+        for probability in [dense.history()[0].probability, sparse.history()[0].probability,
+                            mps.history()[0].probability, stabilizer.history()[0].probability] {
+            assert!((probability - 0.25).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn bell_register_only_gives_correlated_values() {
+        let computer = QuantumComputer::new(2);
+        let circuit = computer.bell_state()
+            .add_measure_register("bell", &[0, 1])
+            .build()
+            .unwrap();
+
+        for _ in 0..20 {
+            let result = circuit.execute(&computer.zero_state()).unwrap();
+            let value = result.last_register_value("bell").unwrap();
+            assert!(value == 0 || value == 3);
+            assert!((result.history()[0].probability - 0.5).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn loop_stops_on_register_value() {
+        let computer = QuantumComputer::new(2);
+        let body = computer.new_circuit_builder()
+            .add_operation(Hadamard(0))
+            .add_operation(Hadamard(1))
+            .add_measure_register("r", &[0, 1])
+            .build()
+            .unwrap();
+        let circuit = computer.new_circuit_builder()
+            .add_loop(body, StopCondition::RegisterValue { id: "r".to_string(), value: 3 })
+            .build()
+            .unwrap();
+
+        let result = circuit.execute(&computer.zero_state()).unwrap();
+        assert_eq!(result.last_register_value("r"), Some(3));
+        assert_eq!(result.get_register_count("r", 3), 1);
+    }
 }
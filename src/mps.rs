@@ -0,0 +1,563 @@
+use std::fmt::{Debug, Formatter, Result};
+
+use num_complex::Complex64;
+use num_traits::{One, Zero};
+
+use crate::backend::Backend;
+use crate::base_gate::BaseGate;
+use crate::linalg::{Matrix, svd};
+use crate::pauli::{rotate_back, rotate_to_z, Pauli};
+use crate::power_of_two;
+use crate::state::QuantumState;
+
+/// Singular values below this value are always dropped
+const ZERO_SINGULAR_VALUE: f64 = 1e-14;
+
+/// Tensor of one qbit, with indices (left bond, physical, right bond)
+#[derive(Clone)]
+struct SiteTensor {
+    left: usize,
+    right: usize,
+    data: Vec<Complex64>,
+}
+
+impl SiteTensor {
+    fn zero(left: usize, right: usize) -> Self {
+        SiteTensor { left, right, data: vec![Complex64::zero(); left * 2 * right] }
+    }
+
+    /// Tensor of a qbit in the state |bit> with bond dimensions of 1
+    fn basis(bit: bool) -> Self {
+        let mut tensor = SiteTensor::zero(1, 1);
+        tensor.set(0, bit as usize, 0, Complex64::one());
+        tensor
+    }
+
+    fn get(&self, l: usize, s: usize, r: usize) -> Complex64 {
+        self.data[(l * 2 + s) * self.right + r]
+    }
+
+    fn set(&mut self, l: usize, s: usize, r: usize, value: Complex64) {
+        self.data[(l * 2 + s) * self.right + r] = value;
+    }
+}
+
+/// Matrix product state: the amplitudes are obtained by contracting one
+/// small tensor per qbit. The memory grows with the entanglement (the bond
+/// dimension) and not exponentially with the number of qbits, which makes
+/// it possible to simulate low entanglement circuits on many qbits.
+///
+/// The bond dimension is limited to `max_bond_dimension`. When a gate creates
+/// more entanglement, the smallest singular values are discarded and their
+/// weight is added to the [`truncation_error`](MpsState::truncation_error).
+#[derive(Clone)]
+pub struct MpsState {
+    sites: Vec<SiteTensor>,
+    /// the site used as orthogonality center, all sites on its left are left-orthonormal
+    /// and all sites on its right are right-orthonormal
+    center: usize,
+    max_bond_dimension: usize,
+    cutoff: f64,
+    truncation_error: f64,
+}
+
+impl MpsState {
+    /// Create the state |0...0>
+    pub fn zero(nb_qbits: u8, max_bond_dimension: usize) -> Self {
+        assert!(max_bond_dimension >= 1, "The bond dimension must be at least 1");
+        MpsState {
+            sites: (0..nb_qbits).map(|_| SiteTensor::basis(false)).collect(),
+            center: 0,
+            max_bond_dimension,
+            cutoff: 0.0,
+            truncation_error: 0.0,
+        }
+    }
+
+    /// Singular values whose squared value is below the cutoff are discarded
+    /// even if the maximal bond dimension is not reached
+    pub fn with_cutoff(mut self, cutoff: f64) -> Self {
+        self.cutoff = cutoff;
+        self
+    }
+
+    pub fn max_bond_dimension(&self) -> usize {
+        self.max_bond_dimension
+    }
+
+    /// Return the dimension of the bond between each pair of neighbour qbits
+    pub fn bond_dimensions(&self) -> Vec<usize> {
+        self.sites.iter().skip(1).map(|s| s.left).collect()
+    }
+
+    /// Return the sum of the weights (squared singular values) discarded
+    /// by the truncations since the creation of the state.
+    /// Zero means the simulation is exact.
+    pub fn truncation_error(&self) -> f64 {
+        self.truncation_error
+    }
+
+    /// Contract all the tensors to get the amplitudes of the state.
+    /// This should only be used with small registers.
+    pub fn to_dense(&self) -> QuantumState {
+        let mut amplitudes = vec![Complex64::one()];
+        let mut bond = 1;
+        for site in &self.sites {
+            let mut next = vec![Complex64::zero(); amplitudes.len() / bond * 2 * site.right];
+            for prefix in 0..amplitudes.len() / bond {
+                for l in 0..bond {
+                    let amplitude = amplitudes[prefix * bond + l];
+                    if amplitude.is_zero() {
+                        continue;
+                    }
+                    for s in 0..2 {
+                        for r in 0..site.right {
+                            next[(prefix * 2 + s) * site.right + r] += amplitude * site.get(l, s, r);
+                        }
+                    }
+                }
+            }
+            amplitudes = next;
+            bond = site.right;
+        }
+        let mut result = QuantumState::nil(self.sites.len() as u8);
+        for (index, amplitude) in amplitudes.into_iter().enumerate() {
+            result[index] = amplitude;
+        }
+        result
+    }
+
+    fn apply_single_qbit(&mut self, qbit: usize, matrix: &[[Complex64; 2]; 2]) {
+        let site = &mut self.sites[qbit];
+        for l in 0..site.left {
+            for r in 0..site.right {
+                let a0 = site.get(l, 0, r);
+                let a1 = site.get(l, 1, r);
+                site.set(l, 0, r, matrix[0][0] * a0 + matrix[0][1] * a1);
+                site.set(l, 1, r, matrix[1][0] * a0 + matrix[1][1] * a1);
+            }
+        }
+    }
+
+    /// Apply a two qbits gate. The gate matrix is indexed by `2*s_a+s_b`.
+    /// Swaps are used to bring the qbits next to each other.
+    fn apply_two_qbits(&mut self, qbit_a: usize, qbit_b: usize, gate: &Matrix) {
+        let (low, high, gate) = if qbit_a < qbit_b { (qbit_a, qbit_b, gate.clone()) } else { (qbit_b, qbit_a, exchange_qbits(gate)) };
+        let swap = swap_matrix();
+        for site in (low + 1..high).rev() {
+            self.apply_neighbours(site, &swap);
+        }
+        self.apply_neighbours(low, &gate);
+        for site in low + 1..high {
+            self.apply_neighbours(site, &swap);
+        }
+    }
+
+    /// Apply a two qbits gate on the sites `site` and `site+1`
+    fn apply_neighbours(&mut self, site: usize, gate: &Matrix) {
+        self.move_center(site);
+        let left_tensor = &self.sites[site];
+        let right_tensor = &self.sites[site + 1];
+        let (left, bond, right) = (left_tensor.left, left_tensor.right, right_tensor.right);
+
+        let mut theta = Matrix::zero(left * 2, 2 * right);
+        for l in 0..left {
+            for r in 0..right {
+                let mut pair = [Complex64::zero(); 4];
+                for (s1, s2) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+                    pair[s1 * 2 + s2] = (0..bond).map(|k| left_tensor.get(l, s1, k) * right_tensor.get(k, s2, r)).sum();
+                }
+                for output in 0..4 {
+                    let value: Complex64 = (0..4).map(|input| gate.get(output, input) * pair[input]).sum();
+                    theta.set(l * 2 + output / 2, (output % 2) * right + r, value);
+                }
+            }
+        }
+
+        let decomposition = svd(&theta);
+        let total: f64 = decomposition.singular_values.iter().map(|s| s * s).sum();
+        let kept = decomposition.singular_values.iter()
+            .take(self.max_bond_dimension)
+            .take_while(|s| **s > ZERO_SINGULAR_VALUE && (*s) * (*s) >= self.cutoff * total)
+            .count()
+            .max(1);
+        let kept_weight: f64 = decomposition.singular_values.iter().take(kept).map(|s| s * s).sum();
+        // a state of norm zero has nothing to truncate
+        if total > 0.0 {
+            self.truncation_error += (total - kept_weight).max(0.0) / total;
+        }
+        let normalization = if kept_weight > 0.0 { kept_weight.sqrt() } else { 1.0 };
+
+        let mut new_left = SiteTensor::zero(left, kept);
+        let mut new_right = SiteTensor::zero(kept, right);
+        for k in 0..kept {
+            for l in 0..left {
+                for s in 0..2 {
+                    new_left.set(l, s, k, decomposition.u.get(l * 2 + s, k));
+                }
+            }
+            let sigma = decomposition.singular_values[k] / normalization;
+            for s in 0..2 {
+                for r in 0..right {
+                    new_right.set(k, s, r, decomposition.v.get(s * right + r, k).conj() * sigma);
+                }
+            }
+        }
+        self.sites[site] = new_left;
+        self.sites[site + 1] = new_right;
+        self.center = site + 1;
+    }
+
+    /// Move the orthogonality center to the given site (without truncation)
+    fn move_center(&mut self, target: usize) {
+        while self.center < target {
+            let site = &self.sites[self.center];
+            let mut matrix = Matrix::zero(site.left * 2, site.right);
+            for l in 0..site.left {
+                for s in 0..2 {
+                    for r in 0..site.right {
+                        matrix.set(l * 2 + s, r, site.get(l, s, r));
+                    }
+                }
+            }
+            let decomposition = svd(&matrix);
+            let kept = decomposition.singular_values.iter().take_while(|s| **s > ZERO_SINGULAR_VALUE).count().max(1);
+
+            let mut new_site = SiteTensor::zero(site.left, kept);
+            for l in 0..site.left {
+                for s in 0..2 {
+                    for k in 0..kept {
+                        new_site.set(l, s, k, decomposition.u.get(l * 2 + s, k));
+                    }
+                }
+            }
+            let next = &self.sites[self.center + 1];
+            let mut new_next = SiteTensor::zero(kept, next.right);
+            for k in 0..kept {
+                let sigma = decomposition.singular_values[k];
+                for s in 0..2 {
+                    for r in 0..next.right {
+                        let value: Complex64 = (0..next.left).map(|m| decomposition.v.get(m, k).conj() * next.get(m, s, r)).sum();
+                        new_next.set(k, s, r, value * sigma);
+                    }
+                }
+            }
+            self.sites[self.center] = new_site;
+            self.sites[self.center + 1] = new_next;
+            self.center += 1;
+        }
+        while self.center > target {
+            let site = &self.sites[self.center];
+            let mut matrix = Matrix::zero(site.left, 2 * site.right);
+            for l in 0..site.left {
+                for s in 0..2 {
+                    for r in 0..site.right {
+                        matrix.set(l, s * site.right + r, site.get(l, s, r));
+                    }
+                }
+            }
+            let decomposition = svd(&matrix);
+            let kept = decomposition.singular_values.iter().take_while(|s| **s > ZERO_SINGULAR_VALUE).count().max(1);
+
+            let mut new_site = SiteTensor::zero(kept, site.right);
+            for k in 0..kept {
+                for s in 0..2 {
+                    for r in 0..site.right {
+                        new_site.set(k, s, r, decomposition.v.get(s * site.right + r, k).conj());
+                    }
+                }
+            }
+            let previous = &self.sites[self.center - 1];
+            let mut new_previous = SiteTensor::zero(previous.left, kept);
+            for k in 0..kept {
+                let sigma = decomposition.singular_values[k];
+                for l in 0..previous.left {
+                    for s in 0..2 {
+                        let value: Complex64 = (0..previous.right).map(|m| previous.get(l, s, m) * decomposition.u.get(m, k)).sum();
+                        new_previous.set(l, s, k, value * sigma);
+                    }
+                }
+            }
+            self.sites[self.center] = new_site;
+            self.sites[self.center - 1] = new_previous;
+            self.center -= 1;
+        }
+    }
+
+    /// Contract the environment `environment(bra,ket)` with one site,
+    /// keeping only the physical values accepted by the filter
+    fn transfer(environment: &Matrix, site: &SiteTensor, filter: Option<usize>) -> Matrix {
+        let mut result = Matrix::zero(site.right, site.right);
+        for s in 0..2 {
+            if filter.map(|f| f != s).unwrap_or(false) {
+                continue;
+            }
+            for l in 0..site.left {
+                for l2 in 0..site.left {
+                    let e = environment.get(l, l2);
+                    if e.is_zero() {
+                        continue;
+                    }
+                    for r in 0..site.right {
+                        let bra = site.get(l, s, r).conj() * e;
+                        for r2 in 0..site.right {
+                            let value = result.get(r, r2) + bra * site.get(l2, s, r2);
+                            result.set(r, r2, value);
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Return the right environments of the norm: `environments[k]` contracts the sites `k..n`
+    fn right_environments(&self) -> Vec<Matrix> {
+        let n = self.sites.len();
+        let mut environments = vec![Matrix::identity(1); n + 1];
+        for k in (0..n).rev() {
+            let site = &self.sites[k];
+            let next = &environments[k + 1];
+            let mut environment = Matrix::zero(site.left, site.left);
+            for l in 0..site.left {
+                for l2 in 0..site.left {
+                    let mut value = Complex64::zero();
+                    for s in 0..2 {
+                        for r in 0..site.right {
+                            for r2 in 0..site.right {
+                                value += site.get(l, s, r).conj() * next.get(r, r2) * site.get(l2, s, r2);
+                            }
+                        }
+                    }
+                    environment.set(l, l2, value);
+                }
+            }
+            environments[k] = environment;
+        }
+        environments
+    }
+
+    fn sample_bits(&self) -> Vec<bool> {
+        let right_environments = self.right_environments();
+        let mut environment = Matrix::identity(1);
+        let mut bits = Vec::with_capacity(self.sites.len());
+        for (k, site) in self.sites.iter().enumerate() {
+            let with_zero = MpsState::transfer(&environment, site, Some(0));
+            let with_one = MpsState::transfer(&environment, site, Some(1));
+            let p0 = contract(&with_zero, &right_environments[k + 1]);
+            let p1 = contract(&with_one, &right_environments[k + 1]);
+            let bit = rand::random::<f64>() * (p0 + p1) < p1;
+            bits.push(bit);
+            environment = if bit { with_one } else { with_zero };
+        }
+        bits
+    }
+}
+
+fn contract(lhs: &Matrix, rhs: &Matrix) -> f64 {
+    let mut result = Complex64::zero();
+    for i in 0..lhs.nb_rows() {
+        for j in 0..lhs.nb_columns() {
+            result += lhs.get(i, j) * rhs.get(i, j);
+        }
+    }
+    result.re
+}
+
+fn swap_matrix() -> Matrix {
+    let mut result = Matrix::zero(4, 4);
+    for (output, input) in [(0, 0), (1, 2), (2, 1), (3, 3)] {
+        result.set(output, input, Complex64::one());
+    }
+    result
+}
+
+/// Return the matrix of the same gate when the order of the two qbits is exchanged
+fn exchange_qbits(gate: &Matrix) -> Matrix {
+    let exchange = |i: usize| (i % 2) * 2 + i / 2;
+    let mut result = Matrix::zero(4, 4);
+    for output in 0..4 {
+        for input in 0..4 {
+            result.set(exchange(output), exchange(input), gate.get(output, input));
+        }
+    }
+    result
+}
+
+/// Matrix of a single qbit gate on the second qbit controlled by the first one
+fn controlled_matrix(matrix: &[[Complex64; 2]; 2]) -> Matrix {
+    let mut result = Matrix::identity(4);
+    for (output, row) in matrix.iter().enumerate() {
+        for (input, value) in row.iter().enumerate() {
+            result.set(2 + output, 2 + input, *value);
+        }
+    }
+    result
+}
+
+impl Debug for MpsState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "MPS {} qbits : bond dimensions {:?}, truncation error {:e}", self.sites.len(), self.bond_dimensions(), self.truncation_error)
+    }
+}
+
+impl Backend for MpsState {
+    fn nb_qbits(&self) -> usize {
+        self.sites.len()
+    }
+
+    fn apply_gate(&mut self, gate: &BaseGate, control_qbits: &[u8]) -> std::result::Result<(), String> {
+        match (gate, gate.matrix(), control_qbits) {
+            (BaseGate::Swap(t1, t2), _, []) => self.apply_two_qbits(*t1 as usize, *t2 as usize, &swap_matrix()),
+            (_, Some(matrix), []) => self.apply_single_qbit(gate.get_involved_qbits(&[])[0] as usize, &matrix),
+            (_, Some(matrix), [control]) => {
+                let target = gate.get_involved_qbits(&[])[0] as usize;
+                self.apply_two_qbits(*control as usize, target, &controlled_matrix(&matrix))
+            }
+            (_, _, _) => return Err("The MPS simulator only supports gates acting on one or two qbits".to_string()),
+        }
+        Ok(())
+    }
+
+    fn probability_of_one(&self, qbit: u8) -> f64 {
+        let mut norm = Matrix::identity(1);
+        let mut projected = Matrix::identity(1);
+        for (k, site) in self.sites.iter().enumerate() {
+            norm = MpsState::transfer(&norm, site, None);
+            let filter = if k == qbit as usize { Some(1) } else { None };
+            projected = MpsState::transfer(&projected, site, filter);
+        }
+        projected.get(0, 0).re / norm.get(0, 0).re
+    }
+
+    /// Like the dense state, the state is collapsed to one basis state
+    fn measure(&mut self, qbit: u8) -> bool {
+        let bits = self.sample_bits();
+        self.sites = bits.iter().map(|b| SiteTensor::basis(*b)).collect();
+        self.center = 0;
+        bits[qbit as usize]
+    }
+
+    /// The projector is applied on the orthogonality center so that
+    /// the norm of the state is the norm of the center tensor
+    fn postselect(&mut self, qbit: u8, one: bool) -> std::result::Result<f64, String> {
+        let probability_of_one = self.probability_of_one(qbit);
+        let probability = if one { probability_of_one } else { 1.0 - probability_of_one };
+        if probability <= 0.0 {
+            return Ok(0.0);
+        }
+        let (zero, unit) = (Complex64::zero(), Complex64::one());
+        let projector = if one { [[zero, zero], [zero, unit]] } else { [[unit, zero], [zero, zero]] };
+        self.move_center(qbit as usize);
+        self.apply_single_qbit(qbit as usize, &projector);
+        let site = &mut self.sites[qbit as usize];
+        let norm = site.data.iter().map(|a| a.norm_sqr()).sum::<f64>().sqrt();
+        site.data.iter_mut().for_each(|a| *a /= norm);
+        Ok(probability)
+    }
+
+    /// The product is mapped to Z on one qbit and projected with [`postselect`](Backend::postselect):
+    /// unlike [`measure`](Backend::measure), the other qbits are not collapsed
+    fn measure_pauli_product(&mut self, product: &[(u8, Pauli)]) -> std::result::Result<bool, String> {
+        let qbit = match rotate_to_z(self, product)? {
+            None => return Ok(false),
+            Some(qbit) => qbit,
+        };
+        let measured_one = rand::random::<f64>() < self.probability_of_one(qbit);
+        self.postselect(qbit, measured_one)?;
+        rotate_back(self, product)?;
+        Ok(measured_one)
+    }
+
+    /// The result has 2^nb_qbits elements, this should only be used with small registers
+    fn probabilities(&self) -> Vec<f64> {
+        let dense = self.to_dense();
+        let mut result = Vec::with_capacity(power_of_two(self.sites.len() as u8));
+        result.extend(dense.iter().map(|a| a.norm_sqr()));
+        result
+    }
+
+    fn sample(&self) -> Vec<bool> {
+        self.sample_bits()
+    }
+
+    fn snapshot(&self) -> Option<Self> {
+        Some(self.clone())
+    }
+}
+
+
+#[cfg(test)]
+mod tests_mps {
+    use crate::common_gate::CommonGate::{CNot, Hadamard};
+    use crate::computer::QuantumComputer;
+
+    use super::*;
+
+    #[test]
+    fn mps_matches_dense_simulation() {
+        let computer = QuantumComputer::new(5);
+        let circuit = computer.new_circuit_builder()
+            .add_operation(Hadamard(0))
+            .add_operation(CNot(4, [0]))
+            .add_operation(BaseGate::S(4))
+            .add_operation(Hadamard(2))
+            .add_operation(BaseGate::Y(1).with_one_control(2))
+            .add_operation(BaseGate::Swap(0, 3))
+            .add_operation(Hadamard(4))
+            .add_operation(CNot(1, [3]))
+            .build()
+            .unwrap();
+
+        let dense = circuit.execute(&computer.zero_state()).unwrap();
+        let mps = circuit.execute_on(MpsState::zero(5, 16)).unwrap();
+
+        let expected = dense.current_state();
+        let actual = mps.current_state().to_dense();
+        for index in 0..expected.len() {
+            assert!((expected[index] - actual[index]).norm() < 1e-9);
+        }
+        assert!(mps.current_state().truncation_error() < 1e-12);
+        assert!((mps.current_state().probability_of_one(4) - expected.probability_of_one(4)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn chain_of_100_qbits() {
+        let computer = QuantumComputer::new(100);
+        let circuit = computer.bell_state().build().unwrap();
+
+        let result = circuit.execute_on(MpsState::zero(100, 4)).unwrap();
+        let state = result.current_state();
+
+        assert!(state.bond_dimensions().iter().all(|d| *d <= 2));
+        assert!((state.probability_of_one(57) - 0.5).abs() < 1e-9);
+        let bits = state.sample();
+        assert!(bits.iter().all(|b| *b == bits[0]));
+    }
+
+    #[test]
+    fn truncation_is_reported() {
+        let mut state = MpsState::zero(4, 1);
+        state.apply_gate(&BaseGate::Hadamard(0), &[]).unwrap();
+        state.apply_gate(&BaseGate::Not(1), &[0]).unwrap();
+
+        assert!((state.truncation_error() - 0.5).abs() < 1e-9);
+        assert!((state.probabilities().iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn state_of_norm_zero_has_no_truncation() {
+        let mut state = MpsState::zero(2, 2);
+        state.sites[0] = SiteTensor::zero(1, 1);
+        state.apply_gate(&BaseGate::Not(1), &[0]).unwrap();
+
+        assert_eq!(state.truncation_error(), 0.0);
+        assert!(state.probabilities().iter().all(|p| *p == 0.0));
+    }
+
+    #[test]
+    fn multi_controlled_gates_are_rejected() {
+        let mut state = MpsState::zero(3, 4);
+        assert!(state.apply_gate(&BaseGate::Not(2), &[0, 1]).is_err());
+    }
+}
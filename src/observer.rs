@@ -0,0 +1,240 @@
+use std::io::Write;
+use std::time::Instant;
+
+use serde_json::json;
+
+use crate::debugger::ElementPath;
+use crate::operation::CircuitElement;
+
+/// Receive the events of an execution. The observers are added to the
+/// execution context and notified by the circuits, loops and measurements.
+///
+/// All methods do nothing by default. An error returned by an observer
+/// stops the execution.
+pub trait ExecutionObserver {
+    /// Called before the execution of the element at the given path
+    fn element_entered(&mut self, _path: &ElementPath, _element: &CircuitElement) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Called after the execution of the element at the given path
+    fn element_exited(&mut self, _path: &ElementPath, _element: &CircuitElement) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Called after each iteration of the loop at the given path,
+    /// `iteration` is the number of completed iterations
+    fn loop_iteration(&mut self, _path: &ElementPath, _iteration: u32) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Called after each measurement
+    fn measurement(&mut self, _path: &ElementPath, _id: &str, _qbit: u8, _outcome: bool) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Called after each register measurement
+    fn register_measurement(&mut self, _path: &ElementPath, _id: &str, _qbits: &[u8], _value: u64) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Called after each postselection with the probability of the selected outcome
+    fn postselection(&mut self, _path: &ElementPath, _id: &str, _qbit: u8, _probability: f64) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+fn element_kind(element: &CircuitElement) -> &'static str {
+    match element {
+        CircuitElement::Loop(_) => "loop",
+        CircuitElement::Gate(_) => "gate",
+        CircuitElement::Measure(_) => "measure",
+        CircuitElement::RegisterMeasure(_) => "register_measure",
+        CircuitElement::Postselect(_) => "postselect",
+        CircuitElement::SubCircuit(_) => "sub_circuit",
+    }
+}
+
+/// Observer that writes the execution trace in JSON lines, one event per line.
+/// The times are in microseconds since the creation of the recorder, the exit
+/// and iteration events also give the duration of the element or of the iteration.
+///
+/// ```text
+/// {"event":"enter","path":"/1","kind":"loop","time_us":3}
+/// {"event":"measure","path":"/1/1","id":"q0","qbit":0,"outcome":1,"time_us":10}
+/// {"event":"iteration","path":"/1","iteration":1,"time_us":11,"duration_us":8}
+/// ```
+pub struct TraceRecorder<W: Write> {
+    writer: W,
+    start: Instant,
+    /// start time of the elements being executed
+    entered: Vec<Instant>,
+    /// start time of the current iteration of the loops being executed
+    iterations: Vec<Instant>,
+}
+
+impl<W: Write> TraceRecorder<W> {
+    pub fn new(writer: W) -> Self {
+        TraceRecorder { writer, start: Instant::now(), entered: vec![], iterations: vec![] }
+    }
+
+    fn elapsed_us(&self, since: Instant) -> u128 {
+        since.elapsed().as_micros()
+    }
+
+    fn write_event(&mut self, event: serde_json::Value) -> Result<(), String> {
+        writeln!(self.writer, "{}", event).map_err(|e| format!("Cannot write the execution trace : {}", e))
+    }
+}
+
+impl<W: Write> ExecutionObserver for TraceRecorder<W> {
+    fn element_entered(&mut self, path: &ElementPath, element: &CircuitElement) -> Result<(), String> {
+        let now = Instant::now();
+        self.entered.push(now);
+        if let CircuitElement::Loop(_) = element {
+            self.iterations.push(now);
+        }
+        let time_us = self.elapsed_us(self.start);
+        self.write_event(json!({"event": "enter", "path": path.to_string(), "kind": element_kind(element), "time_us": time_us}))
+    }
+
+    fn element_exited(&mut self, path: &ElementPath, element: &CircuitElement) -> Result<(), String> {
+        let entered = self.entered.pop().unwrap_or(self.start);
+        if let CircuitElement::Loop(_) = element {
+            self.iterations.pop();
+        }
+        let time_us = self.elapsed_us(self.start);
+        let duration_us = self.elapsed_us(entered);
+        self.write_event(json!({"event": "exit", "path": path.to_string(), "kind": element_kind(element), "time_us": time_us, "duration_us": duration_us}))
+    }
+
+    fn loop_iteration(&mut self, path: &ElementPath, iteration: u32) -> Result<(), String> {
+        let now = Instant::now();
+        let started = self.iterations.last_mut().map(|s| std::mem::replace(s, now)).unwrap_or(self.start);
+        let time_us = self.elapsed_us(self.start);
+        let duration_us = self.elapsed_us(started);
+        self.write_event(json!({"event": "iteration", "path": path.to_string(), "iteration": iteration, "time_us": time_us, "duration_us": duration_us}))
+    }
+
+    fn measurement(&mut self, path: &ElementPath, id: &str, qbit: u8, outcome: bool) -> Result<(), String> {
+        let time_us = self.elapsed_us(self.start);
+        self.write_event(json!({"event": "measure", "path": path.to_string(), "id": id, "qbit": qbit, "outcome": outcome as u8, "time_us": time_us}))
+    }
+
+    fn register_measurement(&mut self, path: &ElementPath, id: &str, qbits: &[u8], value: u64) -> Result<(), String> {
+        let time_us = self.elapsed_us(self.start);
+        self.write_event(json!({"event": "register_measure", "path": path.to_string(), "id": id, "qbits": qbits, "value": value, "time_us": time_us}))
+    }
+
+    fn postselection(&mut self, path: &ElementPath, id: &str, qbit: u8, probability: f64) -> Result<(), String> {
+        let time_us = self.elapsed_us(self.start);
+        self.write_event(json!({"event": "postselect", "path": path.to_string(), "id": id, "qbit": qbit, "probability": probability, "time_us": time_us}))
+    }
+}
+
+
+#[cfg(test)]
+mod tests_observer {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::common_gate::CommonGate::{Hadamard, Not};
+    use crate::computer::QuantumComputer;
+    use crate::condition::StopCondition;
+
+    use super::*;
+
+    /// Writer that can still be read once the recorder has been given to the execution
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct EventLog(Rc<RefCell<Vec<String>>>);
+
+    impl ExecutionObserver for EventLog {
+        fn element_entered(&mut self, path: &ElementPath, element: &CircuitElement) -> Result<(), String> {
+            self.0.borrow_mut().push(format!("enter {} {}", element_kind(element), path));
+            Ok(())
+        }
+
+        fn element_exited(&mut self, path: &ElementPath, _element: &CircuitElement) -> Result<(), String> {
+            self.0.borrow_mut().push(format!("exit {}", path));
+            Ok(())
+        }
+
+        fn loop_iteration(&mut self, path: &ElementPath, iteration: u32) -> Result<(), String> {
+            self.0.borrow_mut().push(format!("iteration {} {}", path, iteration));
+            Ok(())
+        }
+
+        fn measurement(&mut self, path: &ElementPath, id: &str, _qbit: u8, outcome: bool) -> Result<(), String> {
+            self.0.borrow_mut().push(format!("measure {} {}={}", path, id, outcome as u8));
+            Ok(())
+        }
+    }
+
+    fn loop_circuit(computer: &QuantumComputer) -> crate::circuit::Circuit {
+        let inner = computer.new_circuit_builder()
+            .add_operation(Not(0))
+            .add_measure("q0", 0)
+            .build()
+            .unwrap();
+        computer.new_circuit_builder()
+            .add_operation(Hadamard(1))
+            .add_loop(inner, StopCondition::MaxIteration(2))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn events_are_notified_in_order() {
+        let computer = QuantumComputer::new(2);
+        let circuit = loop_circuit(&computer);
+        let events = Rc::new(RefCell::new(vec![]));
+
+        circuit.execute_observed(computer.zero_state(), vec![Box::new(EventLog(events.clone()))]).unwrap();
+
+        assert_eq!(*events.borrow(), vec![
+            "enter gate /0", "exit /0",
+            "enter loop /1",
+            "enter gate /1/0", "exit /1/0", "enter measure /1/1", "measure /1/1 q0=1", "exit /1/1",
+            "iteration /1 1",
+            "enter gate /1/0", "exit /1/0", "enter measure /1/1", "measure /1/1 q0=0", "exit /1/1",
+            "iteration /1 2",
+            "exit /1",
+        ]);
+    }
+
+    #[test]
+    fn recorder_writes_json_lines() {
+        let computer = QuantumComputer::new(2);
+        let circuit = loop_circuit(&computer);
+        let buffer = SharedBuffer::default();
+
+        circuit.execute_observed(computer.zero_state(), vec![Box::new(TraceRecorder::new(buffer.clone()))]).unwrap();
+
+        let trace = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        let events: Vec<serde_json::Value> = trace.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(events.len(), 16);
+
+        let measures: Vec<&serde_json::Value> = events.iter().filter(|e| e["event"] == "measure").collect();
+        assert_eq!(measures.len(), 2);
+        assert_eq!(measures[0]["path"], "/1/1");
+        assert_eq!(measures[0]["outcome"], 1);
+
+        let last = events.last().unwrap();
+        assert_eq!(last["event"], "exit");
+        assert_eq!(last["kind"], "loop");
+        assert!(last["duration_us"].is_u64());
+    }
+}
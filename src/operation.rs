@@ -1,21 +1,27 @@
 use serde::{Deserialize, Serialize};
 
 use crate::_loop::Loop;
-use crate::execution::ExecutionContext;
+use crate::backend::Backend;
+use crate::execution::{ExecutionContext, ExecutionError};
 use crate::gate::Gate;
-use crate::measure::Measure;
+use crate::measure::{Measure, RegisterMeasure};
+use crate::postselect::Postselect;
+use crate::sub_circuit::SubCircuit;
 
 #[derive(Clone, Serialize, Deserialize)]
 pub enum CircuitElement {
     Loop(Loop),
     Gate(Gate),
     Measure(Measure),
+    RegisterMeasure(RegisterMeasure),
+    Postselect(Postselect),
+    SubCircuit(SubCircuit),
 }
 
 
 pub trait QuantumOperation {
     /// Apply the quantum operation by using the provided context
-    fn apply(&self, context: &mut ExecutionContext);
+    fn apply<B: Backend>(&self, context: &mut ExecutionContext<B>) -> Result<(), ExecutionError>;
     fn max_qbit_idx(&self) -> u8;
     fn check_validity(&self, nb_qbits: u8) -> Result<(), String>;
 }
@@ -31,17 +37,23 @@ impl QuantumOperation for CircuitElement {
         match self {
             CircuitElement::Loop(p) => p.max_qbit_idx(),
             CircuitElement::Gate(p) => p.max_qbit_idx(),
-            CircuitElement::Measure(p) => p.max_qbit_idx()
+            CircuitElement::Measure(p) => p.max_qbit_idx(),
+            CircuitElement::RegisterMeasure(p) => p.max_qbit_idx(),
+            CircuitElement::Postselect(p) => p.max_qbit_idx(),
+            CircuitElement::SubCircuit(p) => p.max_qbit_idx(),
         }
     }
 
     /// Apply the current gate operation to the provided state
     /// and return the result.
-    fn apply(&self, context: &mut ExecutionContext) {
+    fn apply<B: Backend>(&self, context: &mut ExecutionContext<B>) -> Result<(), ExecutionError> {
         match self {
             CircuitElement::Loop(p) => p.apply(context),
             CircuitElement::Gate(p) => p.apply(context),
-            CircuitElement::Measure(p) => p.apply(context)
+            CircuitElement::Measure(p) => p.apply(context),
+            CircuitElement::RegisterMeasure(p) => p.apply(context),
+            CircuitElement::Postselect(p) => p.apply(context),
+            CircuitElement::SubCircuit(p) => p.apply(context),
         }
     }
 
@@ -49,7 +61,10 @@ impl QuantumOperation for CircuitElement {
         match self {
             CircuitElement::Loop(p) => p.check_validity(nb_qbits),
             CircuitElement::Gate(p) => p.check_validity(nb_qbits),
-            CircuitElement::Measure(p) => p.check_validity(nb_qbits)
+            CircuitElement::Measure(p) => p.check_validity(nb_qbits),
+            CircuitElement::RegisterMeasure(p) => p.check_validity(nb_qbits),
+            CircuitElement::Postselect(p) => p.check_validity(nb_qbits),
+            CircuitElement::SubCircuit(p) => p.check_validity(nb_qbits),
         }
     }
 }
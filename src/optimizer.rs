@@ -0,0 +1,297 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Minimizer of a function of several real variables, used by the variational algorithms
+#[derive(Clone, Debug)]
+pub enum Optimizer {
+    /// Derivative free simplex method
+    NelderMead { initial_step: f64, tolerance: f64, max_iterations: usize },
+    /// Derivative free method in the spirit of COBYLA (without constraints): a linear model of the
+    /// cost is built from the points around the current one and followed inside a trust region
+    /// whose radius goes from `rho_begin` to `rho_end`
+    Cobyla { rho_begin: f64, rho_end: f64, max_iterations: usize },
+    /// Simultaneous perturbation stochastic approximation: the gradient is estimated from two
+    /// evaluations whatever the number of variables, which makes it robust to noisy costs
+    Spsa { a: f64, c: f64, max_iterations: usize, seed: Option<u64> },
+    /// Follow the exact gradient
+    GradientDescent { learning_rate: f64, tolerance: f64, max_iterations: usize },
+}
+
+/// Result of a minimization
+#[derive(Clone, Debug)]
+pub struct OptimizationResult {
+    /// the best point found
+    pub parameters: Vec<f64>,
+    /// the cost at the best point
+    pub cost: f64,
+    /// the best cost at the start of each iteration
+    pub history: Vec<f64>,
+    /// the number of evaluations of the cost
+    pub nb_evaluations: usize,
+}
+
+impl Optimizer {
+    pub fn nelder_mead() -> Self {
+        Optimizer::NelderMead { initial_step: 0.5, tolerance: 1e-10, max_iterations: 1000 }
+    }
+
+    pub fn cobyla() -> Self {
+        Optimizer::Cobyla { rho_begin: 0.5, rho_end: 1e-6, max_iterations: 1000 }
+    }
+
+    pub fn spsa() -> Self {
+        Optimizer::Spsa { a: 0.2, c: 0.1, max_iterations: 300, seed: None }
+    }
+
+    pub fn gradient_descent(learning_rate: f64) -> Self {
+        Optimizer::GradientDescent { learning_rate, tolerance: 1e-8, max_iterations: 1000 }
+    }
+
+    pub fn with_max_iterations(mut self, value: usize) -> Self {
+        match &mut self {
+            Optimizer::NelderMead { max_iterations, .. }
+            | Optimizer::Cobyla { max_iterations, .. }
+            | Optimizer::Spsa { max_iterations, .. }
+            | Optimizer::GradientDescent { max_iterations, .. } => *max_iterations = value,
+        }
+        self
+    }
+
+    /// Return true if the optimizer uses the gradient of the cost
+    pub fn needs_gradient(&self) -> bool {
+        matches!(self, Optimizer::GradientDescent { .. })
+    }
+
+    /// Minimize the cost starting from `initial`. The gradient is only called by the
+    /// optimizers that need it (see [`needs_gradient`](Optimizer::needs_gradient)).
+    pub fn minimize<C, G>(&self, cost: C, gradient: G, initial: &[f64]) -> Result<OptimizationResult, String>
+        where C: FnMut(&[f64]) -> Result<f64, String>,
+              G: FnMut(&[f64]) -> Result<Vec<f64>, String> {
+        let mut counted = CountedCost { cost, nb_evaluations: 0 };
+        let (parameters, cost, history) = match self {
+            Optimizer::NelderMead { initial_step, tolerance, max_iterations } => {
+                nelder_mead(&mut counted, initial, *initial_step, *tolerance, *max_iterations)?
+            }
+            Optimizer::Cobyla { rho_begin, rho_end, max_iterations } => {
+                cobyla(&mut counted, initial, *rho_begin, *rho_end, *max_iterations)?
+            }
+            Optimizer::Spsa { a, c, max_iterations, seed } => {
+                let rng = match seed {
+                    Some(seed) => StdRng::seed_from_u64(*seed),
+                    None => StdRng::from_entropy(),
+                };
+                spsa(&mut counted, initial, *a, *c, *max_iterations, rng)?
+            }
+            Optimizer::GradientDescent { learning_rate, tolerance, max_iterations } => {
+                gradient_descent(&mut counted, gradient, initial, *learning_rate, *tolerance, *max_iterations)?
+            }
+        };
+        Ok(OptimizationResult { parameters, cost, history, nb_evaluations: counted.nb_evaluations })
+    }
+}
+
+type Minimum = (Vec<f64>, f64, Vec<f64>);
+
+struct CountedCost<C> {
+    cost: C,
+    nb_evaluations: usize,
+}
+
+impl<C: FnMut(&[f64]) -> Result<f64, String>> CountedCost<C> {
+    fn eval(&mut self, x: &[f64]) -> Result<f64, String> {
+        self.nb_evaluations += 1;
+        (self.cost)(x)
+    }
+}
+
+/// Return a + factor.(b - a)
+fn towards(a: &[f64], b: &[f64], factor: f64) -> Vec<f64> {
+    a.iter().zip(b.iter()).map(|(a, b)| a + factor * (b - a)).collect()
+}
+
+fn nelder_mead<C: FnMut(&[f64]) -> Result<f64, String>>(cost: &mut CountedCost<C>, initial: &[f64], step: f64, tolerance: f64, max_iterations: usize) -> Result<Minimum, String> {
+    let n = initial.len();
+    let mut simplex = vec![(initial.to_vec(), cost.eval(initial)?)];
+    for i in 0..n {
+        let mut vertex = initial.to_vec();
+        vertex[i] += step;
+        let value = cost.eval(&vertex)?;
+        simplex.push((vertex, value));
+    }
+
+    let mut history = vec![];
+    for _ in 0..max_iterations {
+        simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+        history.push(simplex[0].1);
+        if n == 0 || simplex[n].1 - simplex[0].1 <= tolerance {
+            break;
+        }
+        let centroid: Vec<f64> = (0..n).map(|k| simplex[..n].iter().map(|(v, _)| v[k]).sum::<f64>() / n as f64).collect();
+        let worst = simplex[n].clone();
+
+        let reflected = towards(&centroid, &worst.0, -1.0);
+        let reflected_value = cost.eval(&reflected)?;
+        if reflected_value < simplex[0].1 {
+            let expanded = towards(&centroid, &worst.0, -2.0);
+            let expanded_value = cost.eval(&expanded)?;
+            simplex[n] = if expanded_value < reflected_value { (expanded, expanded_value) } else { (reflected, reflected_value) };
+        } else if reflected_value < simplex[n - 1].1 {
+            simplex[n] = (reflected, reflected_value);
+        } else {
+            let outside = reflected_value < worst.1;
+            let contracted = if outside { towards(&centroid, &reflected, 0.5) } else { towards(&centroid, &worst.0, 0.5) };
+            let contracted_value = cost.eval(&contracted)?;
+            if contracted_value < reflected_value.min(worst.1) {
+                simplex[n] = (contracted, contracted_value);
+            } else {
+                let best = simplex[0].0.clone();
+                for vertex in simplex.iter_mut().skip(1) {
+                    vertex.0 = towards(&best, &vertex.0, 0.5);
+                    vertex.1 = cost.eval(&vertex.0)?;
+                }
+            }
+        }
+    }
+    simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+    let (parameters, value) = simplex.swap_remove(0);
+    Ok((parameters, value, history))
+}
+
+fn cobyla<C: FnMut(&[f64]) -> Result<f64, String>>(cost: &mut CountedCost<C>, initial: &[f64], rho_begin: f64, rho_end: f64, max_iterations: usize) -> Result<Minimum, String> {
+    let mut x = initial.to_vec();
+    let mut value = cost.eval(&x)?;
+    let mut rho = rho_begin;
+    let mut history = vec![];
+    for _ in 0..max_iterations {
+        history.push(value);
+        if rho < rho_end || x.is_empty() {
+            break;
+        }
+        // linear model from the points at distance rho along each axis
+        let mut best = (x.clone(), value);
+        let mut slope = vec![0.0; x.len()];
+        for i in 0..x.len() {
+            let mut vertex = x.clone();
+            vertex[i] += rho;
+            let vertex_value = cost.eval(&vertex)?;
+            slope[i] = (vertex_value - value) / rho;
+            if vertex_value < best.1 {
+                best = (vertex, vertex_value);
+            }
+        }
+        let norm = slope.iter().map(|s| s * s).sum::<f64>().sqrt();
+        if norm > 0.0 {
+            let candidate: Vec<f64> = x.iter().zip(slope.iter()).map(|(x, s)| x - rho * s / norm).collect();
+            let candidate_value = cost.eval(&candidate)?;
+            if candidate_value < best.1 {
+                best = (candidate, candidate_value);
+            }
+        }
+        if best.1 < value {
+            x = best.0;
+            value = best.1;
+        } else {
+            rho *= 0.5;
+        }
+    }
+    Ok((x, value, history))
+}
+
+fn spsa<C: FnMut(&[f64]) -> Result<f64, String>>(cost: &mut CountedCost<C>, initial: &[f64], a: f64, c: f64, max_iterations: usize, mut rng: StdRng) -> Result<Minimum, String> {
+    // usual values of the gain sequences exponents
+    let (alpha, gamma, stability) = (0.602, 0.101, 0.1 * max_iterations as f64);
+    let mut x = initial.to_vec();
+    let mut value = cost.eval(&x)?;
+    let mut best = (x.clone(), value);
+    let mut history = vec![];
+    for k in 0..max_iterations {
+        history.push(best.1);
+        let a_k = a / (k as f64 + 1.0 + stability).powf(alpha);
+        let c_k = c / (k as f64 + 1.0).powf(gamma);
+        let delta: Vec<f64> = x.iter().map(|_| if rng.gen::<bool>() { 1.0 } else { -1.0 }).collect();
+        let plus: Vec<f64> = x.iter().zip(delta.iter()).map(|(x, d)| x + c_k * d).collect();
+        let minus: Vec<f64> = x.iter().zip(delta.iter()).map(|(x, d)| x - c_k * d).collect();
+        let difference = (cost.eval(&plus)? - cost.eval(&minus)?) / (2.0 * c_k);
+        for (x, d) in x.iter_mut().zip(delta.iter()) {
+            *x -= a_k * difference / d;
+        }
+        value = cost.eval(&x)?;
+        if value < best.1 {
+            best = (x.clone(), value);
+        }
+    }
+    Ok((best.0, best.1, history))
+}
+
+fn gradient_descent<C, G>(cost: &mut CountedCost<C>, mut gradient: G, initial: &[f64], learning_rate: f64, tolerance: f64, max_iterations: usize) -> Result<Minimum, String>
+    where C: FnMut(&[f64]) -> Result<f64, String>,
+          G: FnMut(&[f64]) -> Result<Vec<f64>, String> {
+    let mut x = initial.to_vec();
+    let value = cost.eval(&x)?;
+    let mut best = (x.clone(), value);
+    let mut history = vec![];
+    for _ in 0..max_iterations {
+        history.push(best.1);
+        let g = gradient(&x)?;
+        if g.iter().map(|g| g * g).sum::<f64>().sqrt() < tolerance {
+            break;
+        }
+        for (x, g) in x.iter_mut().zip(g.iter()) {
+            *x -= learning_rate * g;
+        }
+        let value = cost.eval(&x)?;
+        if value < best.1 {
+            best = (x.clone(), value);
+        }
+    }
+    Ok((best.0, best.1, history))
+}
+
+
+#[cfg(test)]
+mod tests_optimizer {
+    use super::*;
+
+    /// Minimum 0 at (1, -2)
+    fn quadratic(x: &[f64]) -> Result<f64, String> {
+        Ok((x[0] - 1.0).powi(2) + 3.0 * (x[1] + 2.0).powi(2) + 0.5 * (x[0] - 1.0) * (x[1] + 2.0))
+    }
+
+    fn quadratic_gradient(x: &[f64]) -> Result<Vec<f64>, String> {
+        Ok(vec![2.0 * (x[0] - 1.0) + 0.5 * (x[1] + 2.0), 6.0 * (x[1] + 2.0) + 0.5 * (x[0] - 1.0)])
+    }
+
+    #[test]
+    fn all_optimizers_find_the_minimum() {
+        let optimizers = vec![
+            (Optimizer::nelder_mead(), 1e-4),
+            (Optimizer::cobyla(), 1e-4),
+            (Optimizer::Spsa { a: 0.5, c: 0.1, max_iterations: 2000, seed: Some(3) }, 0.05),
+            (Optimizer::gradient_descent(0.1), 1e-4),
+        ];
+        for (optimizer, tolerance) in optimizers {
+            let result = optimizer.minimize(quadratic, quadratic_gradient, &[0.0, 0.0]).unwrap();
+            assert!((result.parameters[0] - 1.0).abs() < tolerance, "{:?} {:?}", optimizer, result.parameters);
+            assert!((result.parameters[1] + 2.0).abs() < tolerance, "{:?} {:?}", optimizer, result.parameters);
+            assert!(result.history.first().unwrap() >= result.history.last().unwrap());
+            assert!(result.nb_evaluations > 0);
+        }
+    }
+
+    #[test]
+    fn gradient_descent_keeps_the_best_point() {
+        // the steps are too large, each iterate is further from the minimum
+        let result = Optimizer::gradient_descent(1.0).with_max_iterations(10)
+            .minimize(quadratic, quadratic_gradient, &[0.5, -1.5])
+            .unwrap();
+        assert_eq!(result.parameters, vec![0.5, -1.5]);
+        assert!((result.cost - quadratic(&[0.5, -1.5]).unwrap()).abs() < 1e-12);
+        assert!(result.history.windows(2).all(|w| w[0] >= w[1]));
+    }
+
+    #[test]
+    fn errors_are_propagated() {
+        let result = Optimizer::nelder_mead().minimize(|_| Err("failure".to_string()), quadratic_gradient, &[0.0]);
+        assert_eq!(result.unwrap_err(), "failure");
+    }
+}
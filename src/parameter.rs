@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::f64::consts::PI;
+use std::fmt::{Display, Formatter};
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// Linear combination of symbolic parameters used as the angle of a gate: `θ_0`, `2*θ_1`, `θ/2 - pi/4`.
+///
+/// The names start with a letter or '_' and may contain digits; `pi` and `π` are constants.
+/// It is serialized as a string to keep the symbols in the JSON of the circuit.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ParameterExpression {
+    /// (coefficient, parameter name) pairs, one per parameter
+    terms: Vec<(f64, String)>,
+    constant: f64,
+}
+
+impl ParameterExpression {
+    /// Return the expression made of a single parameter
+    pub fn symbol(name: &str) -> Self {
+        ParameterExpression { terms: vec![(1.0, name.to_string())], constant: 0.0 }
+    }
+
+    /// Return the names of the parameters used by the expression
+    pub fn parameters(&self) -> Vec<&str> {
+        self.terms.iter().map(|(_, name)| name.as_str()).collect()
+    }
+
+    /// Return the coefficient of the parameter in the expression, 0 if it is not used
+    pub fn coefficient(&self, parameter: &str) -> f64 {
+        self.terms.iter().find(|(_, name)| name == parameter).map(|(c, _)| *c).unwrap_or(0.0)
+    }
+
+    /// Return the value of the expression, all its parameters must be given
+    pub fn evaluate(&self, values: &HashMap<String, f64>) -> Result<f64, String> {
+        self.terms.iter().try_fold(self.constant, |sum, (coefficient, name)| {
+            values.get(name)
+                .map(|value| sum + coefficient * value)
+                .ok_or_else(|| format!("No value for the parameter {}", name))
+        })
+    }
+
+    fn add(&mut self, coefficient: f64, name: Option<String>) {
+        match name {
+            None => self.constant += coefficient,
+            Some(name) => match self.terms.iter_mut().find(|(_, n)| *n == name) {
+                Some(term) => term.0 += coefficient,
+                None => self.terms.push((coefficient, name)),
+            },
+        }
+    }
+}
+
+impl FromStr for ParameterExpression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { chars: s.chars().peekable(), text: s };
+        let mut expression = ParameterExpression { terms: vec![], constant: 0.0 };
+        let mut sign = parser.sign(1.0);
+        loop {
+            let (coefficient, name) = parser.term()?;
+            expression.add(sign * coefficient, name);
+            parser.skip_spaces();
+            sign = match parser.chars.next() {
+                None => break,
+                Some('+') => 1.0,
+                Some('-') => -1.0,
+                Some(c) => return Err(parser.error(&format!("unexpected '{}'", c))),
+            };
+        }
+        expression.terms.retain(|(c, _)| *c != 0.0);
+        Ok(expression)
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    text: &'a str,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("Invalid parameter expression '{}': {}", self.text, message)
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    /// Consume the leading signs and return the resulting sign
+    fn sign(&mut self, mut sign: f64) -> f64 {
+        self.skip_spaces();
+        while let Some(c) = self.chars.next_if(|c| *c == '+' || *c == '-') {
+            if c == '-' {
+                sign = -sign;
+            }
+            self.skip_spaces();
+        }
+        sign
+    }
+
+    /// Parse factors separated by '*' or '/', at most one of them being a parameter
+    fn term(&mut self) -> Result<(f64, Option<String>), String> {
+        let (mut coefficient, mut name) = self.factor()?;
+        loop {
+            self.skip_spaces();
+            let divide = match self.chars.peek() {
+                Some('*') => false,
+                Some('/') => true,
+                _ => return Ok((coefficient, name)),
+            };
+            self.chars.next();
+            let (value, other_name) = self.factor()?;
+            match (divide, &name, other_name) {
+                (_, _, None) if divide => coefficient /= value,
+                (_, _, None) => coefficient *= value,
+                (true, _, Some(_)) => return Err(self.error("cannot divide by a parameter")),
+                (false, None, Some(other)) => {
+                    coefficient *= value;
+                    name = Some(other);
+                }
+                (false, Some(_), Some(_)) => return Err(self.error("the expression must be linear in the parameters")),
+            }
+        }
+    }
+
+    /// Parse a number, a constant or a parameter name
+    fn factor(&mut self) -> Result<(f64, Option<String>), String> {
+        let sign = self.sign(1.0);
+        match self.chars.peek().copied() {
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let mut number = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+                    number.push(c);
+                }
+                number.parse::<f64>()
+                    .map(|value| (sign * value, None))
+                    .map_err(|_| self.error(&format!("invalid number {}", number)))
+            }
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let mut name = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                match name.as_str() {
+                    "pi" | "π" => Ok((sign * PI, None)),
+                    _ => Ok((sign, Some(name))),
+                }
+            }
+            Some(c) => Err(self.error(&format!("unexpected '{}'", c))),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+}
+
+impl Display for ParameterExpression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut terms: Vec<String> = self.terms.iter()
+            .map(|(coefficient, name)| match coefficient {
+                c if *c == 1.0 => name.clone(),
+                c if *c == -1.0 => format!("-{}", name),
+                c => format!("{}*{}", c, name),
+            })
+            .collect();
+        if self.constant != 0.0 || terms.is_empty() {
+            terms.push(self.constant.to_string());
+        }
+        for (i, term) in terms.iter().enumerate() {
+            match (i, term.strip_prefix('-')) {
+                (0, _) => write!(f, "{}", term)?,
+                (_, Some(rest)) => write!(f, " - {}", rest)?,
+                (_, None) => write!(f, " + {}", term)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for ParameterExpression {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ParameterExpression> for String {
+    fn from(expression: ParameterExpression) -> Self {
+        expression.to_string()
+    }
+}
+
+
+#[cfg(test)]
+mod tests_parameter {
+    use std::f64::consts::FRAC_PI_2;
+
+    use crate::backend::Backend;
+    use crate::base_gate::BaseGate;
+    use crate::circuit::Circuit;
+    use crate::common_gate::CommonGate;
+    use crate::computer::QuantumComputer;
+    use crate::condition::StopCondition;
+    use crate::mps::MpsState;
+    use crate::sparse::SparseState;
+
+    use super::*;
+
+    fn values(values: &[(&str, f64)]) -> HashMap<String, f64> {
+        values.iter().map(|(name, value)| (name.to_string(), *value)).collect()
+    }
+
+    #[test]
+    fn parse_and_evaluate() {
+        let expression: ParameterExpression = "2*θ_1 - θ_0/2 + pi/4".parse().unwrap();
+        assert_eq!(expression.parameters(), vec!["θ_1", "θ_0"]);
+        assert_eq!(expression.coefficient("θ_0"), -0.5);
+        let value = expression.evaluate(&values(&[("θ_0", 1.0), ("θ_1", 3.0)])).unwrap();
+        assert!((value - (6.0 - 0.5 + PI / 4.0)).abs() < 1e-12);
+        assert!(expression.evaluate(&values(&[("θ_0", 1.0)])).is_err());
+
+        let expression: ParameterExpression = "-a + 3 * a * 2".parse().unwrap();
+        assert_eq!(expression.to_string(), "5*a");
+        assert_eq!("θ".parse::<ParameterExpression>().unwrap(), ParameterExpression::symbol("θ"));
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        assert!("a*b".parse::<ParameterExpression>().is_err());
+        assert!("1/a".parse::<ParameterExpression>().is_err());
+        assert!("a +".parse::<ParameterExpression>().is_err());
+        assert!("a $ b".parse::<ParameterExpression>().is_err());
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for text in ["θ_0", "2*θ_1", "-x - 0.5*y + 1.5", "0"] {
+            let expression: ParameterExpression = text.parse().unwrap();
+            assert_eq!(expression.to_string(), text);
+        }
+    }
+
+    fn variational_circuit(computer: &QuantumComputer) -> Circuit {
+        let layer = computer.new_circuit_builder()
+            .add_operation(BaseGate::Rz(0.0, 1).with_parameter("θ_1").unwrap())
+            .build()
+            .unwrap();
+        computer.new_circuit_builder()
+            .add_operation(BaseGate::Ry(0.0, 0).with_parameter("2*θ_0").unwrap())
+            .add_operation(CommonGate::CNot(1, [0]))
+            .add_loop(layer, StopCondition::MaxIteration(2))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn bind_circuit() {
+        let computer = QuantumComputer::new(2);
+        let circuit = variational_circuit(&computer);
+        assert_eq!(circuit.parameters(), vec!["θ_0", "θ_1"]);
+        assert!(circuit.execute(&computer.zero_state()).is_err());
+        assert!(circuit.bind(&values(&[("θ_0", 1.0)])).is_err());
+
+        let bound = circuit.bind(&values(&[("θ_0", PI / 4.0), ("θ_1", FRAC_PI_2)])).unwrap();
+        assert!(bound.parameters().is_empty());
+        let result = bound.execute(&computer.zero_state()).unwrap();
+        let state = result.current_state();
+        assert!((state.probability_of_one(1) - 0.5).abs() < 1e-9);
+        // the two Rz(π/2) give a relative phase of π between |00> and |11>
+        assert!((state[0] / state[3] + 1.0).norm() < 1e-9);
+    }
+
+    #[test]
+    fn json_keeps_the_symbols() {
+        let computer = QuantumComputer::new(2);
+        let json = variational_circuit(&computer).to_string().unwrap();
+        assert!(json.contains("\"2*θ_0\""), "{}", json);
+        let circuit = Circuit::from_string(&json).unwrap();
+        assert_eq!(circuit.parameters(), vec!["θ_0", "θ_1"]);
+        assert!(BaseGate::Hadamard(0).with_parameter("θ").is_err());
+    }
+
+    #[test]
+    fn rotation_gates_on_all_backends() {
+        let gates = [
+            BaseGate::Hadamard(0).with_one_control(2),
+            BaseGate::Rx(0.3, 1).into(),
+            BaseGate::Ry(1.1, 2).with_one_control(1),
+            BaseGate::Rz(0.7, 0).into(),
+            BaseGate::T(1).with_one_control(0),
+            BaseGate::Phase(2.1, 2).into(),
+        ];
+        let mut dense = QuantumComputer::new(3).zero_state();
+        let mut sparse = SparseState::zero(3);
+        let mut mps = MpsState::zero(3, 8);
+        dense.apply_gate(&BaseGate::Hadamard(1), &[]).unwrap();
+        sparse.apply_gate(&BaseGate::Hadamard(1), &[]).unwrap();
+        mps.apply_gate(&BaseGate::Hadamard(1), &[]).unwrap();
+        for gate in gates.iter() {
+            dense.apply_gate(&gate.get_gate(), gate.get_control_bits()).unwrap();
+            sparse.apply_gate(&gate.get_gate(), gate.get_control_bits()).unwrap();
+            mps.apply_gate(&gate.get_gate(), gate.get_control_bits()).unwrap();
+        }
+        let dense_probabilities = dense.probabilities();
+        for (p, (q, r)) in dense_probabilities.iter().zip(sparse.probabilities().iter().zip(mps.probabilities().iter())) {
+            assert!((p - q).abs() < 1e-9 && (p - r).abs() < 1e-9);
+        }
+        assert!((dense_probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+
+        let mut state = QuantumComputer::new(1).zero_state();
+        state.apply_gate(&BaseGate::Rx(PI, 0), &[]).unwrap();
+        assert!((state[1] - num_complex::Complex64::new(0.0, -1.0)).norm() < 1e-9);
+    }
+}
@@ -0,0 +1,209 @@
+use num_complex::Complex64;
+use serde::{Deserialize, Serialize};
+
+use crate::backend::Backend;
+use crate::base_gate::BaseGate;
+
+/// Single qbit Pauli operator
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Pauli {
+    I,
+    X,
+    Y,
+    Z,
+}
+
+impl Pauli {
+    /// Gates that map the eigenstates of this operator to the ones of Z
+    /// (|+> to |0> for X). Y needs S† that is applied as three S gates.
+    fn z_basis_change(self, qbit: u8) -> Vec<BaseGate> {
+        match self {
+            Pauli::I | Pauli::Z => vec![],
+            Pauli::X => vec![BaseGate::Hadamard(qbit)],
+            Pauli::Y => vec![BaseGate::S(qbit), BaseGate::S(qbit), BaseGate::S(qbit), BaseGate::Hadamard(qbit)],
+        }
+    }
+
+    /// Gates that undo [`z_basis_change`](Pauli::z_basis_change)
+    fn undo_z_basis_change(self, qbit: u8) -> Vec<BaseGate> {
+        match self {
+            Pauli::I | Pauli::Z => vec![],
+            Pauli::X => vec![BaseGate::Hadamard(qbit)],
+            Pauli::Y => vec![BaseGate::Hadamard(qbit), BaseGate::S(qbit)],
+        }
+    }
+}
+
+/// Remove the identities of a Pauli product
+pub(crate) fn non_trivial(product: &[(u8, Pauli)]) -> Vec<(u8, Pauli)> {
+    product.iter().filter(|(_, p)| *p != Pauli::I).cloned().collect()
+}
+
+/// Apply the Clifford gates that map the Pauli product to Z on its last qbit
+/// (basis change of each qbit then a parity computed with CNots).
+/// Return the qbit that holds the parity, None if the product is the identity.
+pub(crate) fn rotate_to_z<B: Backend + ?Sized>(state: &mut B, product: &[(u8, Pauli)]) -> Result<Option<u8>, String> {
+    let product = non_trivial(product);
+    let target = match product.last() {
+        None => return Ok(None),
+        Some((qbit, _)) => *qbit,
+    };
+    for (qbit, pauli) in product.iter() {
+        for gate in pauli.z_basis_change(*qbit) {
+            state.apply_gate(&gate, &[])?;
+        }
+    }
+    for (qbit, _) in product.iter().filter(|(q, _)| *q != target) {
+        state.apply_gate(&BaseGate::Not(target), &[*qbit])?;
+    }
+    Ok(Some(target))
+}
+
+/// Undo [`rotate_to_z`]
+pub(crate) fn rotate_back<B: Backend + ?Sized>(state: &mut B, product: &[(u8, Pauli)]) -> Result<(), String> {
+    let product = non_trivial(product);
+    let target = match product.last() {
+        None => return Ok(()),
+        Some((qbit, _)) => *qbit,
+    };
+    for (qbit, _) in product.iter().filter(|(q, _)| *q != target).rev() {
+        state.apply_gate(&BaseGate::Not(target), &[*qbit])?;
+    }
+    for (qbit, pauli) in product.iter() {
+        for gate in pauli.undo_z_basis_change(*qbit) {
+            state.apply_gate(&gate, &[])?;
+        }
+    }
+    Ok(())
+}
+
+/// Action of a Pauli product on the basis states: P|i> = phase(i) |i ^ flip>
+pub(crate) struct PauliMasks {
+    /// qbits with X or Y
+    flip: usize,
+    /// qbits with Z or Y, each gives a -1 when the qbit is |1>
+    sign: usize,
+    /// i^(number of Y)
+    phase: Complex64,
+}
+
+impl PauliMasks {
+    /// `qbit_mask` gives the bit of each qbit in the index of the basis states
+    pub(crate) fn new(product: &[(u8, Pauli)], qbit_mask: impl Fn(u8) -> usize) -> Self {
+        let mut masks = PauliMasks { flip: 0, sign: 0, phase: Complex64::new(1.0, 0.0) };
+        for (qbit, pauli) in product.iter() {
+            let mask = qbit_mask(*qbit);
+            match pauli {
+                Pauli::I => {}
+                Pauli::X => masks.flip ^= mask,
+                Pauli::Z => masks.sign ^= mask,
+                Pauli::Y => {
+                    masks.flip ^= mask;
+                    masks.sign ^= mask;
+                    masks.phase *= Complex64::i();
+                }
+            }
+        }
+        masks
+    }
+
+    /// Return the image of the basis state and the phase
+    pub(crate) fn apply(&self, basis_state: usize) -> (usize, Complex64) {
+        let phase = if (basis_state & self.sign).count_ones() % 2 == 0 { self.phase } else { -self.phase };
+        (basis_state ^ self.flip, phase)
+    }
+}
+
+
+#[cfg(test)]
+mod tests_pauli {
+    use crate::common_gate::CommonGate::Hadamard;
+    use crate::computer::QuantumComputer;
+    use crate::mps::MpsState;
+    use crate::stabilizer::Tableau;
+
+    use super::*;
+
+    #[test]
+    fn bell_pair_expectations() {
+        let computer = QuantumComputer::new(2);
+        let bell = computer.bell_state().build().unwrap();
+        let dense = bell.execute(&computer.zero_state()).unwrap();
+        let tableau = bell.execute_on(Tableau::new(2)).unwrap();
+
+        for (product, expected) in [
+            (vec![(0, Pauli::Z), (1, Pauli::Z)], 1.0),
+            (vec![(0, Pauli::X), (1, Pauli::X)], 1.0),
+            (vec![(0, Pauli::Y), (1, Pauli::Y)], -1.0),
+            (vec![(0, Pauli::Z)], 0.0),
+            (vec![(0, Pauli::X), (1, Pauli::Z)], 0.0),
+        ] {
+            assert!((dense.current_state().pauli_expectation(&product).unwrap() - expected).abs() < 1e-9);
+            assert!((tableau.current_state().pauli_expectation(&product).unwrap() - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn parity_measurements_keep_the_bell_pair() {
+        let computer = QuantumComputer::new(2);
+        let circuit = computer.bell_state()
+            .add_pauli_measure("zz", &[(0, Pauli::Z), (1, Pauli::Z)])
+            .add_pauli_measure("xx", &[(0, Pauli::X), (1, Pauli::X)])
+            .add_pauli_measure("yy", &[(0, Pauli::Y), (1, Pauli::Y)])
+            .build()
+            .unwrap();
+
+        for _ in 0..10 {
+            let dense = circuit.execute(&computer.zero_state()).unwrap();
+            let stabilizer = circuit.execute_stabilizer().unwrap();
+            let mps = circuit.execute_on(MpsState::zero(2, 4)).unwrap();
+            for result in [(dense.get_nb_one("zz"), dense.get_nb_one("xx"), dense.get_nb_one("yy")),
+                           (stabilizer.get_nb_one("zz"), stabilizer.get_nb_one("xx"), stabilizer.get_nb_one("yy")),
+                           (mps.get_nb_one("zz"), mps.get_nb_one("xx"), mps.get_nb_one("yy"))] {
+                assert_eq!(result, (0, 0, 1));
+            }
+        }
+    }
+
+    #[test]
+    fn empty_product_is_rejected() {
+        let computer = QuantumComputer::new(2);
+        let result = computer.new_circuit_builder()
+            .add_pauli_measure("p", &[])
+            .build();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn parity_measurement_collapses_the_state() {
+        let computer = QuantumComputer::new(2);
+        let circuit = computer.new_circuit_builder()
+            .add_operation(Hadamard(0))
+            .add_operation(Hadamard(1))
+            .add_pauli_measure("zz", &[(0, Pauli::Z), (1, Pauli::Z)])
+            .build()
+            .unwrap();
+
+        let result = circuit.execute(&computer.zero_state()).unwrap();
+        let parity = result.last_outcome("zz").unwrap();
+        let state = result.current_state();
+        assert!((state.pauli_expectation(&[(0, Pauli::Z), (1, Pauli::Z)]).unwrap() - if parity { -1.0 } else { 1.0 }).abs() < 1e-9);
+        assert!((state.probability_of_one(0) - 0.5).abs() < 1e-9);
+        assert!((result.history()[0].probability - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn measure_in_x_basis() {
+        let computer = QuantumComputer::new(1);
+        let circuit = computer.new_circuit_builder()
+            .add_operation(Hadamard(0))
+            .add_measure_in_basis("x", 0, Pauli::X)
+            .build()
+            .unwrap();
+
+        let result = circuit.execute(&computer.zero_state()).unwrap();
+        assert_eq!(result.get_nb_zero("x"), 1);
+        assert!((result.history()[0].probability - 1.0).abs() < 1e-9);
+        assert!((result.current_state().probability_of_one(0) - 0.5).abs() < 1e-9);
+    }
+}
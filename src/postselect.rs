@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+
+use crate::backend::Backend;
+use crate::debugger::ElementPath;
+use crate::execution::{ExecutionContext, ExecutionError};
+use crate::operation::{CircuitElement, QuantumOperation};
+
+/// Result of one postselection
+#[derive(Clone, PartialEq, Debug)]
+pub struct PostselectionRecord {
+    /// id of the postselection
+    pub id: String,
+    /// path of the postselection in the circuit
+    pub path: ElementPath,
+    /// probability of the selected outcome before the projection
+    pub probability: f64,
+}
+
+/// Project a qbit on a chosen outcome and renormalize the state, as if
+/// only the shots giving this outcome were kept. Nothing is measured: the
+/// probability of the outcome is recorded in the execution context instead.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Postselect {
+    /// Uniq value used to identify the postselection
+    pub id: String,
+    /// the qbit to project
+    pub qbit_target: u8,
+    /// true to keep |1>, false to keep |0>
+    pub outcome: bool,
+    /// fail the execution with [`ExecutionError::PostselectionFailed`] when the outcome
+    /// cannot happen, otherwise the state is left unchanged and a probability of 0 is recorded
+    #[serde(default)]
+    pub abort_on_zero: bool,
+}
+
+impl Postselect {
+    pub fn new(id: &str, qbit_target: u8, outcome: bool) -> Self {
+        Postselect { id: id.to_string(), qbit_target, outcome, abort_on_zero: false }
+    }
+
+    /// Fail the execution when the selected outcome has a probability of zero
+    pub fn aborting_on_zero(mut self) -> Self {
+        self.abort_on_zero = true;
+        self
+    }
+}
+
+impl Into<CircuitElement> for Postselect {
+    fn into(self) -> CircuitElement {
+        CircuitElement::Postselect(self)
+    }
+}
+
+impl QuantumOperation for Postselect {
+    fn apply<B: Backend>(&self, context: &mut ExecutionContext<B>) -> Result<(), ExecutionError> {
+        let probability = context.current_state_mut().postselect(self.qbit_target, self.outcome)?;
+        context.record_postselection(&self.id, probability);
+        context.notify(|o, path| o.postselection(path, &self.id, self.qbit_target, probability))?;
+        if probability <= 0.0 && self.abort_on_zero {
+            return Err(ExecutionError::PostselectionFailed { path: context.path().clone(), id: self.id.clone() });
+        }
+        Ok(())
+    }
+
+    fn max_qbit_idx(&self) -> u8 {
+        self.qbit_target
+    }
+
+    fn check_validity(&self, nb_qbits: u8) -> Result<(), String> {
+        if self.qbit_target >= nb_qbits {
+            return Err(format!("Index to high {}", self.qbit_target));
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests_postselect {
+    use crate::common_gate::CommonGate::{Hadamard, Not};
+    use crate::computer::QuantumComputer;
+    use crate::execution::ExecutionError;
+    use crate::mps::MpsState;
+    use crate::sparse::SparseState;
+    use crate::stabilizer::Tableau;
+
+    use super::*;
+
+    fn heralded_circuit(computer: &QuantumComputer) -> crate::circuit::Circuit {
+        computer.bell_state()
+            .add_postselect("herald", 0, true)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn postselection_projects_and_renormalizes() {
+        let computer = QuantumComputer::new(2);
+        let circuit = heralded_circuit(&computer);
+
+        let dense = circuit.execute(&computer.zero_state()).unwrap();
+        let sparse = circuit.execute_on(SparseState::zero(2)).unwrap();
+        let mps = circuit.execute_on(MpsState::zero(2, 4)).unwrap();
+        let stabilizer = circuit.execute_on(Tableau::new(2)).unwrap();
+
+        assert!((dense.last_postselection_probability("herald").unwrap() - 0.5).abs() < 1e-9);
+        assert!((sparse.postselection_probability() - 0.5).abs() < 1e-9);
+        assert!((mps.postselection_probability() - 0.5).abs() < 1e-9);
+        assert!((stabilizer.postselection_probability() - 0.5).abs() < 1e-9);
+        for probability_of_one in [dense.current_state().probability_of_one(1), sparse.current_state().probability_of_one(1),
+                                   mps.current_state().probability_of_one(1), stabilizer.current_state().probability_of_one(1)] {
+            assert!((probability_of_one - 1.0).abs() < 1e-9);
+        }
+        assert!((dense.current_state().probabilities().iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn probabilities_of_successive_postselections_are_multiplied() {
+        let computer = QuantumComputer::new(2);
+        let circuit = computer.new_circuit_builder()
+            .add_operation(Hadamard(0))
+            .add_operation(Hadamard(1))
+            .add_postselect("a", 0, false)
+            .add_postselect("b", 1, true)
+            .build()
+            .unwrap();
+
+        let result = circuit.execute(&computer.zero_state()).unwrap();
+        assert_eq!(result.postselections().len(), 2);
+        assert!((result.postselection_probability() - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn impossible_outcome() {
+        let computer = QuantumComputer::new(1);
+        let keep_going = computer.new_circuit_builder()
+            .add_operation(Not(0))
+            .add_postselect("zero", 0, false)
+            .build()
+            .unwrap();
+        let aborting = computer.new_circuit_builder()
+            .add_operation(Not(0))
+            .add_operation(Postselect::new("zero", 0, false).aborting_on_zero())
+            .build()
+            .unwrap();
+
+        let result = keep_going.execute(&computer.zero_state()).unwrap();
+        assert_eq!(result.postselection_probability(), 0.0);
+        assert!((result.current_state().probability_of_one(0) - 1.0).abs() < 1e-9);
+
+        match aborting.execute(&computer.zero_state()) {
+            Err(ExecutionError::PostselectionFailed { path, id }) => {
+                assert_eq!(path.to_string(), "/1");
+                assert_eq!(id, "zero");
+            }
+            _ => panic!("the execution should fail"),
+        }
+    }
+}
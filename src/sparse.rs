@@ -0,0 +1,539 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter, Result};
+
+use num_complex::Complex64;
+use num_traits::{One, Zero};
+
+use crate::backend::{Backend, SAME_STATE_THRESHOLD};
+use crate::base_gate::BaseGate;
+use crate::gate_op::sparse::apply_controlled_gate_sparse;
+use crate::pauli::{Pauli, PauliMasks};
+use crate::power_of_two;
+use crate::state::{control_mask, QbitOrdering, QuantumState};
+
+/// Amplitudes with a squared norm below this value are removed from a sparse state
+const PRUNE_THRESHOLD: f64 = 1e-24;
+
+/// Quantum state that only stores the non-zero amplitudes,
+/// indexed like the amplitudes of a [`QuantumState`].
+///
+/// The memory used depends on the number of populated basis states
+/// and not on the number of qbits, which can go up to the number of
+/// bits of `usize`.
+#[derive(Clone)]
+pub struct SparseState {
+    nb_qbits: u8,
+    amplitudes: HashMap<usize, Complex64>,
+    ordering: QbitOrdering,
+}
+
+impl SparseState {
+    /// Create the state |0...0>
+    pub fn zero(nb_qbits: u8) -> Self {
+        SparseState::basis_state(nb_qbits, 0)
+    }
+
+    /// Create the state with only the given basis state populated
+    pub fn basis_state(nb_qbits: u8, basis_state: usize) -> Self {
+        let mut state = SparseState::nil(nb_qbits);
+        state.amplitudes.insert(basis_state, Complex64::one());
+        state
+    }
+
+    pub(crate) fn nil(nb_qbits: u8) -> Self {
+        assert!((nb_qbits as u32) <= usize::BITS, "A sparse state cannot have more than {} qbits", usize::BITS);
+        SparseState { nb_qbits, amplitudes: HashMap::new(), ordering: QbitOrdering::default() }
+    }
+
+    /// Return a state without amplitudes and with the same qbits and ordering as this one
+    pub(crate) fn nil_like(&self) -> Self {
+        let mut result = SparseState::nil(self.nb_qbits);
+        result.ordering = self.ordering;
+        result
+    }
+
+    pub fn nb_qbits(&self) -> u8 {
+        self.nb_qbits
+    }
+
+    pub fn ordering(&self) -> QbitOrdering {
+        self.ordering
+    }
+
+    /// Return the number of populated basis states
+    pub fn support_size(&self) -> usize {
+        self.amplitudes.len()
+    }
+
+    pub fn amplitude(&self, basis_state: usize) -> Complex64 {
+        self.amplitudes.get(&basis_state).cloned().unwrap_or_else(Complex64::zero)
+    }
+
+    /// Iterate over the populated basis states and their amplitudes
+    pub fn iter(&self) -> impl Iterator<Item=(usize, Complex64)> + '_ {
+        self.amplitudes.iter().map(|(i, a)| (*i, *a))
+    }
+
+    /// Return the dense state, with the same ordering
+    pub fn to_dense(&self) -> QuantumState {
+        let mut result = QuantumState::nil(self.nb_qbits).with_ordering_unchanged(self.ordering);
+        for (index, amplitude) in self.iter() {
+            result[index] = amplitude;
+        }
+        result
+    }
+
+    /// Create the sparse state from a dense state, with the same ordering
+    pub fn from_dense(state: &QuantumState) -> Self {
+        let mut result = SparseState::nil(state.nb_qbits());
+        result.ordering = state.ordering();
+        for (index, amplitude) in state.iter().enumerate() {
+            result.add_to(index, *amplitude);
+        }
+        result.prune();
+        result
+    }
+
+    pub(crate) fn add_to(&mut self, basis_state: usize, amplitude: Complex64) {
+        *self.amplitudes.entry(basis_state).or_insert_with(Complex64::zero) += amplitude;
+    }
+
+    /// Remove the amplitudes that cancelled out
+    pub(crate) fn prune(&mut self) {
+        self.amplitudes.retain(|_, a| a.norm_sqr() >= PRUNE_THRESHOLD);
+    }
+
+    pub(crate) fn mask(&self, qbit_idx: u8) -> usize {
+        self.ordering.qbit_mask(self.nb_qbits, qbit_idx)
+    }
+
+    pub(crate) fn control_mask(&self, control_qbits: &[u8]) -> usize {
+        control_mask(self.ordering, self.nb_qbits, control_qbits)
+    }
+
+    fn pick_on_state(&self) -> usize {
+        let mut target = 1.0 - (rand::random::<f64>());
+        let mut last = 0;
+        for (index, amplitude) in self.iter() {
+            target -= amplitude.norm_sqr();
+            if target <= 0.0 {
+                return index;
+            }
+            last = index;
+        }
+        last
+    }
+}
+
+impl Debug for SparseState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let mut indices: Vec<usize> = self.amplitudes.keys().cloned().collect();
+        indices.sort_unstable();
+        let wave_function: String = indices.iter()
+            .map(|i| {
+                let a = self.amplitudes[i];
+                format!(" ({0:.6},{1:.6})x|{2:0>3$b}>", a.re, a.im, i, self.nb_qbits as usize)
+            })
+            .collect();
+        write!(f, "Sparse state {} qbits : {} ", self.nb_qbits, wave_function)
+    }
+}
+
+impl Backend for SparseState {
+    fn nb_qbits(&self) -> usize {
+        self.nb_qbits as usize
+    }
+
+    fn apply_gate(&mut self, gate: &BaseGate, control_qbits: &[u8]) -> std::result::Result<(), String> {
+        apply_controlled_gate_sparse(gate, control_qbits, self);
+        Ok(())
+    }
+
+    fn probability_of_one(&self, qbit: u8) -> f64 {
+        let mask = self.mask(qbit);
+        self.iter()
+            .filter(|(i, _)| (i & mask) != 0)
+            .map(|(_, a)| a.norm_sqr())
+            .sum()
+    }
+
+    /// Like the dense state, the state is collapsed to one basis state
+    fn measure(&mut self, qbit: u8) -> bool {
+        let select_state = self.pick_on_state();
+        let mask = self.mask(qbit);
+        let mut result = self.nil_like();
+        result.amplitudes.insert(select_state, Complex64::one());
+        *self = result;
+        (select_state & mask) != 0
+    }
+
+    fn postselect(&mut self, qbit: u8, one: bool) -> std::result::Result<f64, String> {
+        let probability_of_one = self.probability_of_one(qbit);
+        let probability = if one { probability_of_one } else { 1.0 - probability_of_one };
+        if probability <= 0.0 {
+            return Ok(0.0);
+        }
+        let mask = self.mask(qbit);
+        let scale = 1.0 / probability.sqrt();
+        self.amplitudes.retain(|i, _| ((i & mask) != 0) == one);
+        self.amplitudes.values_mut().for_each(|a| *a *= scale);
+        Ok(probability)
+    }
+
+    /// The result has 2^nb_qbits elements, this should only be used with small registers
+    fn probabilities(&self) -> Vec<f64> {
+        let mut result = vec![0.0; power_of_two(self.nb_qbits)];
+        for (index, amplitude) in self.iter() {
+            result[index] = amplitude.norm_sqr();
+        }
+        result
+    }
+
+    fn sample(&self) -> Vec<bool> {
+        let select_state = self.pick_on_state();
+        (0..self.nb_qbits).map(|qbit| (select_state & self.mask(qbit)) != 0).collect()
+    }
+
+    fn pauli_expectation(&self, product: &[(u8, Pauli)]) -> std::result::Result<f64, String> {
+        let masks = PauliMasks::new(product, |qbit| self.mask(qbit));
+        Ok(self.iter()
+            .map(|(i, a)| {
+                let (j, phase) = masks.apply(i);
+                (self.amplitude(j).conj() * phase * a).re
+            })
+            .sum())
+    }
+
+    /// The state is projected on the eigenspace of the measured eigenvalue
+    fn measure_pauli_product(&mut self, product: &[(u8, Pauli)]) -> std::result::Result<bool, String> {
+        let probability_of_one = (1.0 - self.pauli_expectation(product)?) / 2.0;
+        let measured_one = rand::random::<f64>() < probability_of_one;
+        let (sign, probability) = if measured_one { (-1.0, probability_of_one) } else { (1.0, 1.0 - probability_of_one) };
+
+        let masks = PauliMasks::new(product, |qbit| self.mask(qbit));
+        let scale = 1.0 / (2.0 * probability.sqrt());
+        let mut result = self.nil_like();
+        for (i, a) in self.iter() {
+            let (j, phase) = masks.apply(i);
+            result.add_to(i, a * scale);
+            result.add_to(j, phase * a * sign * scale);
+        }
+        result.prune();
+        *self = result;
+        Ok(measured_one)
+    }
+
+    fn snapshot(&self) -> Option<Self> {
+        Some(self.clone())
+    }
+
+    fn same_state(&self, other: &Self) -> bool {
+        if self.nb_qbits != other.nb_qbits {
+            return false;
+        }
+        let overlap: Complex64 = self.iter()
+            .map(|(i, a)| a.conj() * other.amplitude(self.ordering.convert_index(self.nb_qbits, i, other.ordering)))
+            .sum();
+        overlap.norm_sqr() >= SAME_STATE_THRESHOLD
+    }
+}
+
+
+/// Maximal number of qbits for which a dense representation is used
+const MAX_DENSE_QBITS: u8 = 24;
+/// A sparse state switches to dense when it populates more than 1/SPARSE_RATIO of the basis states
+const SPARSE_RATIO: usize = 8;
+/// A dense state switches to sparse when it populates less than 1/DENSE_RATIO of the basis states
+const DENSE_RATIO: usize = 32;
+/// Counting the support of a dense state costs as much as a gate, it is only
+/// counted every DENSE_CHECK_PERIOD gates and after the measurements
+const DENSE_CHECK_PERIOD: u32 = 16;
+
+/// Backend that uses a [`SparseState`] while the support stays small
+/// and switches to a dense [`QuantumState`] when it grows (and back when it shrinks).
+/// [`Circuit::execute`](crate::circuit::Circuit::execute) runs on it.
+pub struct AdaptiveState {
+    representation: Representation,
+    /// number of gates applied to the dense representation since its support was counted
+    nb_unchecked_gates: u32,
+}
+
+enum Representation {
+    Sparse(SparseState),
+    Dense(QuantumState),
+}
+
+impl AdaptiveState {
+    /// Create the state |0...0>
+    pub fn zero(nb_qbits: u8) -> Self {
+        AdaptiveState::new(Representation::Sparse(SparseState::zero(nb_qbits)))
+    }
+
+    /// Create the state from a dense state, it is kept dense
+    /// when it populates many basis states or has many qbits
+    pub fn from_dense(state: &QuantumState) -> Self {
+        let mut result = AdaptiveState::new(Representation::Dense(QuantumState::from(state)));
+        result.adapt();
+        result
+    }
+
+    fn new(representation: Representation) -> Self {
+        AdaptiveState { representation, nb_unchecked_gates: 0 }
+    }
+
+    pub fn is_sparse(&self) -> bool {
+        matches!(self.representation, Representation::Sparse(_))
+    }
+
+    pub fn to_dense(&self) -> QuantumState {
+        match &self.representation {
+            Representation::Sparse(s) => s.to_dense(),
+            Representation::Dense(d) => QuantumState::from(d),
+        }
+    }
+
+    /// Return the dense state, without copy if the representation is already dense
+    pub fn into_dense(self) -> QuantumState {
+        match self.representation {
+            Representation::Sparse(s) => s.to_dense(),
+            Representation::Dense(d) => d,
+        }
+    }
+
+    fn backend(&self) -> &dyn Backend {
+        match &self.representation {
+            Representation::Sparse(s) => s,
+            Representation::Dense(d) => d,
+        }
+    }
+
+    fn backend_mut(&mut self) -> &mut dyn Backend {
+        match &mut self.representation {
+            Representation::Sparse(s) => s,
+            Representation::Dense(d) => d,
+        }
+    }
+
+    /// Count the support after a gate, every DENSE_CHECK_PERIOD gates for a dense state
+    fn adapt_after_gate(&mut self) {
+        if let Representation::Dense(_) = self.representation {
+            self.nb_unchecked_gates += 1;
+            if self.nb_unchecked_gates < DENSE_CHECK_PERIOD {
+                return;
+            }
+        }
+        self.adapt();
+    }
+
+    /// Switch to the most efficient representation for the current support
+    fn adapt(&mut self) {
+        self.nb_unchecked_gates = 0;
+        let nb_qbits = self.backend().nb_qbits();
+        if nb_qbits > MAX_DENSE_QBITS as usize {
+            return;
+        }
+        let nb_amplitudes = power_of_two(nb_qbits as u8);
+        let replacement = match &self.representation {
+            Representation::Sparse(s) if s.support_size() * SPARSE_RATIO > nb_amplitudes => {
+                Some(Representation::Dense(s.to_dense()))
+            }
+            Representation::Dense(d) if d.iter().filter(|a| a.norm_sqr() >= PRUNE_THRESHOLD).count() * DENSE_RATIO <= nb_amplitudes => {
+                Some(Representation::Sparse(SparseState::from_dense(d)))
+            }
+            _ => None
+        };
+        if let Some(replacement) = replacement {
+            self.representation = replacement;
+        }
+    }
+}
+
+impl Debug for AdaptiveState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match &self.representation {
+            Representation::Sparse(s) => s.fmt(f),
+            Representation::Dense(d) => d.fmt(f),
+        }
+    }
+}
+
+impl Backend for AdaptiveState {
+    fn nb_qbits(&self) -> usize {
+        self.backend().nb_qbits()
+    }
+
+    fn apply_gate(&mut self, gate: &BaseGate, control_qbits: &[u8]) -> std::result::Result<(), String> {
+        self.backend_mut().apply_gate(gate, control_qbits)?;
+        self.adapt_after_gate();
+        Ok(())
+    }
+
+    fn probability_of_one(&self, qbit: u8) -> f64 {
+        self.backend().probability_of_one(qbit)
+    }
+
+    fn measure(&mut self, qbit: u8) -> bool {
+        let result = self.backend_mut().measure(qbit);
+        self.adapt();
+        result
+    }
+
+    fn postselect(&mut self, qbit: u8, one: bool) -> std::result::Result<f64, String> {
+        let result = self.backend_mut().postselect(qbit, one);
+        self.adapt();
+        result
+    }
+
+    fn probabilities(&self) -> Vec<f64> {
+        self.backend().probabilities()
+    }
+
+    fn sample(&self) -> Vec<bool> {
+        self.backend().sample()
+    }
+
+    fn pauli_expectation(&self, product: &[(u8, Pauli)]) -> std::result::Result<f64, String> {
+        match &self.representation {
+            Representation::Sparse(s) => s.pauli_expectation(product),
+            Representation::Dense(d) => d.pauli_expectation(product),
+        }
+    }
+
+    fn measure_pauli_product(&mut self, product: &[(u8, Pauli)]) -> std::result::Result<bool, String> {
+        let result = self.backend_mut().measure_pauli_product(product);
+        self.adapt();
+        result
+    }
+
+    fn snapshot(&self) -> Option<Self> {
+        let representation = match &self.representation {
+            Representation::Sparse(s) => Representation::Sparse(s.snapshot()?),
+            Representation::Dense(d) => Representation::Dense(d.snapshot()?),
+        };
+        Some(AdaptiveState { representation, nb_unchecked_gates: self.nb_unchecked_gates })
+    }
+
+    fn same_state(&self, other: &Self) -> bool {
+        match (&self.representation, &other.representation) {
+            (Representation::Sparse(lhs), Representation::Sparse(rhs)) => lhs.same_state(rhs),
+            (Representation::Dense(lhs), Representation::Dense(rhs)) => lhs.same_state(rhs),
+            (Representation::Sparse(lhs), Representation::Dense(rhs)) => lhs.to_dense().same_state(rhs),
+            (Representation::Dense(lhs), Representation::Sparse(rhs)) => lhs.same_state(&rhs.to_dense()),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests_sparse {
+    use crate::common_gate::CommonGate::{CNot, Hadamard, Toffoli};
+    use crate::computer::QuantumComputer;
+
+    use super::*;
+
+    #[test]
+    fn measure_keeps_the_ordering() {
+        let dense = QuantumState::from_bitstrings_in(&["01"], QbitOrdering::LsbFirst).unwrap();
+        let mut state = SparseState::from_dense(&dense);
+
+        assert!(state.measure(0));
+        assert_eq!(state.ordering(), QbitOrdering::LsbFirst);
+        assert!((state.probability_of_one(0) - 1.0).abs() < 1e-9);
+        assert!(state.probability_of_one(1).abs() < 1e-9);
+        assert!(!state.measure(1));
+    }
+
+    #[test]
+    fn sparse_and_dense_give_the_same_state() {
+        let computer = QuantumComputer::new(4);
+        let circuit = computer.bell_state()
+            .add_operation(BaseGate::Y(2))
+            .add_operation(BaseGate::S(1).with_one_control(3))
+            .add_operation(Toffoli(0, [1, 2]))
+            .add_operation(BaseGate::Swap(0, 3))
+            .add_operation(Hadamard(1))
+            .build()
+            .unwrap();
+
+        let dense = circuit.execute(&computer.zero_state()).unwrap();
+        let sparse = circuit.execute_on(SparseState::zero(4)).unwrap();
+
+        let expected = dense.current_state();
+        for index in 0..expected.len() {
+            assert!((expected[index] - sparse.current_state().amplitude(index)).norm() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn hadamard_twice_keeps_the_support_small() {
+        let mut state = SparseState::zero(3);
+        state.apply_gate(&BaseGate::Hadamard(1), &[]).unwrap();
+        assert_eq!(state.support_size(), 2);
+        state.apply_gate(&BaseGate::Hadamard(1), &[]).unwrap();
+        assert_eq!(state.support_size(), 1);
+    }
+
+    #[test]
+    fn large_reversible_circuit() {
+        let computer = QuantumComputer::new(48);
+        let mut builder = computer.new_circuit_builder();
+        builder.add_operation(Hadamard(0));
+        for i in 1..48 {
+            builder.add_operation(CNot(i, [i - 1]));
+        }
+        builder.add_operation(Toffoli(47, [0, 1]));
+        let circuit = builder.build().unwrap();
+
+        let result = circuit.execute_on(AdaptiveState::zero(48)).unwrap();
+        let state = result.current_state();
+        assert!(state.is_sparse());
+        assert!((state.probability_of_one(46) - 0.5).abs() < 1e-9);
+        assert!(state.probability_of_one(47) < 1e-9);
+    }
+
+    #[test]
+    fn support_of_a_dense_state_is_counted_periodically() {
+        let mut state = AdaptiveState::from_dense(&QuantumState::same_amplitude(6, &(0..64).collect::<Vec<usize>>()));
+        assert!(!state.is_sparse());
+        for qbit in 0..6 {
+            state.apply_gate(&BaseGate::Hadamard(qbit), &[]).unwrap();
+        }
+        // back to |000000> but the support is not counted yet
+        assert!(!state.is_sparse());
+        for _ in 6..DENSE_CHECK_PERIOD {
+            state.apply_gate(&BaseGate::Z(0), &[]).unwrap();
+        }
+        assert!(state.is_sparse());
+        assert!((state.into_dense().probabilities()[0] - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn execution_starts_sparse() {
+        assert!(AdaptiveState::from_dense(&QuantumState::zero(10)).is_sparse());
+
+        let computer = QuantumComputer::new(16);
+        let mut builder = computer.new_circuit_builder();
+        builder.add_operation(Hadamard(0));
+        for i in 1..16 {
+            builder.add_operation(CNot(i, [i - 1]));
+        }
+        let result = builder.build().unwrap().execute(&computer.zero_state()).unwrap();
+        let probabilities = result.current_state().probabilities();
+        assert!((probabilities[0] - 0.5).abs() < 1e-9);
+        assert!((probabilities[(1 << 16) - 1] - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn adaptive_state_switches_to_dense() {
+        let computer = QuantumComputer::new(3);
+        let circuit = computer.new_circuit_builder()
+            .add_operation(Hadamard(0))
+            .add_operation(Hadamard(1))
+            .add_operation(Hadamard(2))
+            .build()
+            .unwrap();
+
+        let result = circuit.execute_on(AdaptiveState::zero(3)).unwrap();
+        assert!(!result.current_state().is_sparse());
+    }
+}
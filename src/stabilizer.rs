@@ -0,0 +1,714 @@
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::fmt::{Debug, Formatter, Result};
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::Backend;
+use crate::base_gate::BaseGate;
+use crate::circuit::Circuit;
+use crate::execution::{ExecutionContext, ExecutionError};
+use crate::operation::CircuitElement;
+use crate::power_of_two;
+
+const WORD_SIZE: usize = 64;
+
+/// Stabilizer tableau (Aaronson-Gottesman) used to simulate
+/// circuits made only of Clifford gates in polynomial time.
+///
+/// Rows `0..n` are the destabilizers, rows `n..2n` the stabilizers
+/// and row `2n` is a scratch row used for deterministic measurements.
+/// Qbit indices are `usize` so the tableau itself is not limited to the
+/// `u8` indices of the circuit model. Used as a [`Backend`], a larger tableau
+/// runs a circuit on its first qbits.
+#[derive(Clone)]
+pub struct Tableau {
+    nb_qbits: usize,
+    nb_words: usize,
+    x: Vec<u64>,
+    z: Vec<u64>,
+    r: Vec<bool>,
+}
+
+impl Tableau {
+    /// Create a tableau for the state |0...0>
+    pub fn new(nb_qbits: usize) -> Self {
+        let nb_words = nb_qbits.div_ceil(WORD_SIZE);
+        let nb_rows = 2 * nb_qbits + 1;
+        let mut tableau = Tableau {
+            nb_qbits,
+            nb_words,
+            x: vec![0; nb_rows * nb_words],
+            z: vec![0; nb_rows * nb_words],
+            r: vec![false; nb_rows],
+        };
+        for i in 0..nb_qbits {
+            tableau.set_x(i, i, true);
+            tableau.set_z(i + nb_qbits, i, true);
+        }
+        tableau
+    }
+
+    pub fn nb_qbits(&self) -> usize {
+        self.nb_qbits
+    }
+
+    /// Return the stabilizer generators of the current state,
+    /// for instance `+XX` and `+ZZ` for a Bell pair
+    pub fn stabilizers(&self) -> Vec<String> {
+        (self.nb_qbits..2 * self.nb_qbits).map(|row| self.row_to_string(row)).collect()
+    }
+
+    pub fn hadamard(&mut self, qbit: usize) {
+        for row in 0..2 * self.nb_qbits {
+            let x = self.x(row, qbit);
+            let z = self.z(row, qbit);
+            self.r[row] ^= x && z;
+            self.set_x(row, qbit, z);
+            self.set_z(row, qbit, x);
+        }
+    }
+
+    pub fn phase(&mut self, qbit: usize) {
+        for row in 0..2 * self.nb_qbits {
+            let x = self.x(row, qbit);
+            let z = self.z(row, qbit);
+            self.r[row] ^= x && z;
+            self.set_z(row, qbit, x ^ z);
+        }
+    }
+
+    pub fn cnot(&mut self, control: usize, target: usize) {
+        for row in 0..2 * self.nb_qbits {
+            let xc = self.x(row, control);
+            let zc = self.z(row, control);
+            let xt = self.x(row, target);
+            let zt = self.z(row, target);
+            self.r[row] ^= xc && zt && (xt ^ zc ^ true);
+            self.set_x(row, target, xt ^ xc);
+            self.set_z(row, control, zc ^ zt);
+        }
+    }
+
+    pub fn cz(&mut self, control: usize, target: usize) {
+        self.hadamard(target);
+        self.cnot(control, target);
+        self.hadamard(target);
+    }
+
+    pub fn cy(&mut self, control: usize, target: usize) {
+        self.phase_dagger(target);
+        self.cnot(control, target);
+        self.phase(target);
+    }
+
+    pub fn pauli_x(&mut self, qbit: usize) {
+        for row in 0..2 * self.nb_qbits {
+            self.r[row] ^= self.z(row, qbit);
+        }
+    }
+
+    pub fn pauli_y(&mut self, qbit: usize) {
+        for row in 0..2 * self.nb_qbits {
+            self.r[row] ^= self.x(row, qbit) ^ self.z(row, qbit);
+        }
+    }
+
+    pub fn pauli_z(&mut self, qbit: usize) {
+        for row in 0..2 * self.nb_qbits {
+            self.r[row] ^= self.x(row, qbit);
+        }
+    }
+
+    pub fn swap(&mut self, qbit1: usize, qbit2: usize) {
+        self.cnot(qbit1, qbit2);
+        self.cnot(qbit2, qbit1);
+        self.cnot(qbit1, qbit2);
+    }
+
+    fn phase_dagger(&mut self, qbit: usize) {
+        self.phase(qbit);
+        self.phase(qbit);
+        self.phase(qbit);
+    }
+
+    /// Return the outcome of a measurement of the qbit if it is deterministic,
+    /// `None` if both outcomes are equally likely. The state is not modified.
+    pub fn deterministic_outcome(&self, qbit: usize) -> Option<bool> {
+        if self.random_pivot(qbit).is_some() {
+            return None;
+        }
+        let mut tableau = self.clone();
+        Some(tableau.deterministic_measure(qbit))
+    }
+
+    /// Measure the qbit in the computational basis and collapse the state
+    pub fn measure(&mut self, qbit: usize) -> bool {
+        match self.random_pivot(qbit) {
+            Some(pivot) => self.random_measure(qbit, pivot, rand::random::<bool>()),
+            None => self.deterministic_measure(qbit),
+        }
+    }
+
+    /// Project the qbit on the given outcome and return the probability of this outcome
+    /// (0, 1/2 or 1). The state is left unchanged when the probability is zero.
+    pub fn postselect(&mut self, qbit: usize, one: bool) -> f64 {
+        match self.random_pivot(qbit) {
+            Some(pivot) => {
+                self.random_measure(qbit, pivot, one);
+                0.5
+            }
+            None if self.deterministic_measure(qbit) == one => 1.0,
+            None => 0.0,
+        }
+    }
+
+    fn random_pivot(&self, qbit: usize) -> Option<usize> {
+        (self.nb_qbits..2 * self.nb_qbits).find(|row| self.x(*row, qbit))
+    }
+
+    fn random_measure(&mut self, qbit: usize, pivot: usize, outcome: bool) -> bool {
+        let n = self.nb_qbits;
+        for row in 0..2 * n {
+            if row != pivot && self.x(row, qbit) {
+                self.rowsum(row, pivot);
+            }
+        }
+        self.copy_row(pivot - n, pivot);
+        self.clear_row(pivot);
+        self.set_z(pivot, qbit, true);
+
+        self.r[pivot] = outcome;
+        outcome
+    }
+
+    fn deterministic_measure(&mut self, qbit: usize) -> bool {
+        let n = self.nb_qbits;
+        let scratch = 2 * n;
+        self.clear_row(scratch);
+        for row in 0..n {
+            if self.x(row, qbit) {
+                self.rowsum(scratch, row + n);
+            }
+        }
+        self.r[scratch]
+    }
+
+    /// Replace row h by the product of rows h and i, keeping track of the phase
+    fn rowsum(&mut self, h: usize, i: usize) {
+        let mut phase: i64 = 2 * (self.r[h] as i64) + 2 * (self.r[i] as i64);
+        for word in 0..self.nb_words {
+            let (h_idx, i_idx) = (h * self.nb_words + word, i * self.nb_words + word);
+            phase += g(self.x[i_idx], self.z[i_idx], self.x[h_idx], self.z[h_idx]);
+            self.x[h_idx] ^= self.x[i_idx];
+            self.z[h_idx] ^= self.z[i_idx];
+        }
+        self.r[h] = phase.rem_euclid(4) == 2;
+    }
+
+    fn copy_row(&mut self, dst: usize, src: usize) {
+        for word in 0..self.nb_words {
+            self.x[dst * self.nb_words + word] = self.x[src * self.nb_words + word];
+            self.z[dst * self.nb_words + word] = self.z[src * self.nb_words + word];
+        }
+        self.r[dst] = self.r[src];
+    }
+
+    fn clear_row(&mut self, row: usize) {
+        for word in 0..self.nb_words {
+            self.x[row * self.nb_words + word] = 0;
+            self.z[row * self.nb_words + word] = 0;
+        }
+        self.r[row] = false;
+    }
+
+    fn row_to_string(&self, row: usize) -> String {
+        let mut result = String::with_capacity(self.nb_qbits + 1);
+        result.push(if self.r[row] { '-' } else { '+' });
+        for qbit in 0..self.nb_qbits {
+            let pauli = match (self.x(row, qbit), self.z(row, qbit)) {
+                (false, false) => '_',
+                (true, false) => 'X',
+                (true, true) => 'Y',
+                (false, true) => 'Z',
+            };
+            result.push(pauli);
+        }
+        result
+    }
+
+    fn x(&self, row: usize, qbit: usize) -> bool {
+        (self.x[row * self.nb_words + qbit / WORD_SIZE] >> (qbit % WORD_SIZE)) & 1 == 1
+    }
+
+    fn z(&self, row: usize, qbit: usize) -> bool {
+        (self.z[row * self.nb_words + qbit / WORD_SIZE] >> (qbit % WORD_SIZE)) & 1 == 1
+    }
+
+    fn set_x(&mut self, row: usize, qbit: usize, value: bool) {
+        set_bit(&mut self.x[row * self.nb_words + qbit / WORD_SIZE], qbit % WORD_SIZE, value)
+    }
+
+    fn set_z(&mut self, row: usize, qbit: usize, value: bool) {
+        set_bit(&mut self.z[row * self.nb_words + qbit / WORD_SIZE], qbit % WORD_SIZE, value)
+    }
+}
+
+fn set_bit(word: &mut u64, bit: usize, value: bool) {
+    if value {
+        *word |= 1 << bit;
+    } else {
+        *word &= !(1 << bit);
+    }
+}
+
+/// Sum of the exponents of i obtained when multiplying, qbit by qbit,
+/// the Pauli matrices encoded by the bits of (x1,z1) and (x2,z2)
+fn g(x1: u64, z1: u64, x2: u64, z2: u64) -> i64 {
+    let plus = (x1 & z1 & !x2 & z2) | (x1 & !z1 & x2 & z2) | (!x1 & z1 & x2 & !z2);
+    let minus = (x1 & z1 & x2 & !z2) | (x1 & !z1 & !x2 & z2) | (!x1 & z1 & x2 & z2);
+    plus.count_ones() as i64 - minus.count_ones() as i64
+}
+
+impl Debug for Tableau {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "Tableau {} qbits : {}", self.nb_qbits, self.stabilizers().join(" "))
+    }
+}
+
+impl Tableau {
+    /// Apply a gate of the circuit model to the tableau.
+    /// Fails if the gate is not a Clifford gate.
+    pub fn apply_gate(&mut self, gate: &BaseGate, control_qbits: &[u8]) -> std::result::Result<(), String> {
+        match (gate, control_qbits) {
+            (BaseGate::Not(t), []) | (BaseGate::X(t), []) => self.pauli_x(*t as usize),
+            (BaseGate::Y(t), []) => self.pauli_y(*t as usize),
+            (BaseGate::Z(t), []) => self.pauli_z(*t as usize),
+            (BaseGate::Hadamard(t), []) => self.hadamard(*t as usize),
+            (BaseGate::S(t), []) => self.phase(*t as usize),
+            (BaseGate::Swap(t1, t2), []) => self.swap(*t1 as usize, *t2 as usize),
+            (BaseGate::Not(t), [c]) | (BaseGate::X(t), [c]) => self.cnot(*c as usize, *t as usize),
+            (BaseGate::Y(t), [c]) => self.cy(*c as usize, *t as usize),
+            (BaseGate::Z(t), [c]) => self.cz(*c as usize, *t as usize),
+            (_, _) => return Err(unsupported_gate(gate, control_qbits.len())),
+        };
+        Ok(())
+    }
+}
+
+/// Return true if the gate with `nb_controls` control qbits is one
+/// of the Clifford gates applied by [`Tableau::apply_gate`]
+fn is_supported(gate: &BaseGate, nb_controls: usize) -> bool {
+    match (gate, nb_controls) {
+        (BaseGate::Hadamard(_), 0) | (BaseGate::S(_), 0) | (BaseGate::Swap(_, _), 0) => true,
+        (BaseGate::Not(_), 0..=1) | (BaseGate::X(_), 0..=1) | (BaseGate::Y(_), 0..=1) | (BaseGate::Z(_), 0..=1) => true,
+        (_, _) => false,
+    }
+}
+
+fn unsupported_gate(gate: &BaseGate, nb_controls: usize) -> String {
+    let kind = if nb_controls <= 1 { "Non Clifford" } else { "Multi-controlled" };
+    format!("{} gate {} is not supported by the stabilizer simulator", kind, gate_name(gate, nb_controls))
+}
+
+fn gate_name(gate: &BaseGate, nb_controls: usize) -> String {
+    let name = match gate {
+        BaseGate::Not(_) => "Not",
+        BaseGate::X(_) => "X",
+        BaseGate::Y(_) => "Y",
+        BaseGate::Z(_) => "Z",
+        BaseGate::Hadamard(_) => "Hadamard",
+        BaseGate::Swap(_, _) => "Swap",
+        BaseGate::S(_) => "S",
+        BaseGate::T(_) => "T",
+        BaseGate::Phase(_, _) => "Phase",
+        BaseGate::Rx(_, _) => "Rx",
+        BaseGate::Ry(_, _) => "Ry",
+        BaseGate::Rz(_, _) => "Rz",
+    };
+    format!("{} with {} control(s)", name, nb_controls)
+}
+
+/// Check that a circuit can be run by the stabilizer simulator.
+/// Only the kind of the gates and their number of control qbits are checked:
+/// the definition of a sub-circuit is checked once for each number of controls of its calls.
+pub fn check_clifford(circuit: &Circuit) -> std::result::Result<(), String> {
+    let mut checked = BTreeSet::new();
+    check_elements(circuit, &circuit.elements, 0, &mut checked)
+}
+
+/// `nb_controls` is the number of control qbits added by the enclosing sub-circuit calls
+fn check_elements(circuit: &Circuit, elements: &[CircuitElement], nb_controls: usize, checked: &mut BTreeSet<(String, usize)>) -> std::result::Result<(), String> {
+    for element in elements {
+        match element {
+            CircuitElement::Gate(g) => {
+                let nb_controls = nb_controls + g.get_control_bits().len();
+                if !is_supported(&g.get_gate(), nb_controls) {
+                    return Err(unsupported_gate(&g.get_gate(), nb_controls));
+                }
+            }
+            CircuitElement::Measure(_) | CircuitElement::RegisterMeasure(_) | CircuitElement::Postselect(_) => {}
+            CircuitElement::Loop(l) => check_elements(circuit, &l.circuit.elements, nb_controls, checked)?,
+            CircuitElement::SubCircuit(call) => {
+                let nb_controls = nb_controls + call.control_bits.len();
+                // no gate is supported with more than one control, the definition is checked once for all of them
+                if checked.insert((call.name.clone(), nb_controls.min(2))) {
+                    let definition = circuit.definitions.get(&call.name).ok_or_else(|| format!("Unknown sub-circuit {}", call.name))?;
+                    check_elements(circuit, &definition.elements, nb_controls, checked)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Operation of a [`CliffordCircuit`], two qbits gates take the control qbit first
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CliffordOperation {
+    Hadamard(usize),
+    S(usize),
+    X(usize),
+    Y(usize),
+    Z(usize),
+    CNot(usize, usize),
+    CY(usize, usize),
+    CZ(usize, usize),
+    Swap(usize, usize),
+    /// Measure the qbit, the outcomes are counted under the id
+    Measure(String, usize),
+}
+
+impl CliffordOperation {
+    fn qbits(&self) -> Vec<usize> {
+        match self {
+            CliffordOperation::Hadamard(q) | CliffordOperation::S(q) | CliffordOperation::X(q)
+            | CliffordOperation::Y(q) | CliffordOperation::Z(q) | CliffordOperation::Measure(_, q) => vec![*q],
+            CliffordOperation::CNot(c, t) | CliffordOperation::CY(c, t) | CliffordOperation::CZ(c, t)
+            | CliffordOperation::Swap(c, t) => vec![*c, *t],
+        }
+    }
+
+    fn apply(&self, context: &mut ExecutionContext<Tableau>) {
+        let tableau = context.current_state_mut();
+        match self {
+            CliffordOperation::Hadamard(q) => tableau.hadamard(*q),
+            CliffordOperation::S(q) => tableau.phase(*q),
+            CliffordOperation::X(q) => tableau.pauli_x(*q),
+            CliffordOperation::Y(q) => tableau.pauli_y(*q),
+            CliffordOperation::Z(q) => tableau.pauli_z(*q),
+            CliffordOperation::CNot(c, t) => tableau.cnot(*c, *t),
+            CliffordOperation::CY(c, t) => tableau.cy(*c, *t),
+            CliffordOperation::CZ(c, t) => tableau.cz(*c, *t),
+            CliffordOperation::Swap(q1, q2) => tableau.swap(*q1, *q2),
+            CliffordOperation::Measure(id, q) => {
+                let probability = if tableau.random_pivot(*q).is_some() { 0.5 } else { 1.0 };
+                let outcome = tableau.measure(*q);
+                match outcome {
+                    true => context.increase_one(id),
+                    false => context.increase_zero(id),
+                }
+                context.record_measure(id, outcome, probability);
+            }
+        }
+    }
+}
+
+/// Circuit of Clifford gates and measurements run by the stabilizer simulator.
+/// The qbits of a [`Circuit`] are `u8` indices, the ones of this circuit are `usize`
+/// indices so that registers of thousands of qbits can be simulated.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CliffordCircuit {
+    pub nb_qbits: usize,
+    pub operations: Vec<CliffordOperation>,
+}
+
+impl CliffordCircuit {
+    pub fn new(nb_qbits: usize) -> Self {
+        CliffordCircuit { nb_qbits, operations: vec![] }
+    }
+
+    pub fn add_operation(&mut self, operation: CliffordOperation) -> &mut CliffordCircuit {
+        self.operations.push(operation);
+        self
+    }
+
+    /// Check that the qbits of each operation are in the register and distinct
+    pub fn check_validity(&self) -> std::result::Result<(), String> {
+        for operation in self.operations.iter() {
+            let qbits = operation.qbits();
+            if let Some(qbit) = qbits.iter().find(|q| **q >= self.nb_qbits) {
+                return Err(format!("Index to high {}", qbit));
+            }
+            if qbits.len() == 2 && qbits[0] == qbits[1] {
+                return Err(format!("Duplicate qbit : {} ", qbits[0]));
+            }
+        }
+        Ok(())
+    }
+
+    /// Execute the circuit from the state |0...0>. The measurements are counted
+    /// and recorded in the history like the ones of a [`Circuit`], with an empty path.
+    pub fn execute(&self) -> std::result::Result<ExecutionContext<Tableau>, ExecutionError> {
+        self.check_validity()?;
+        let mut context = ExecutionContext::initialize(Tableau::new(self.nb_qbits));
+        for operation in self.operations.iter() {
+            operation.apply(&mut context);
+        }
+        Ok(context)
+    }
+}
+
+impl Tableau {
+    /// Add to `result` the probabilities of the basis states compatible
+    /// with the outcomes already fixed for the qbits before `qbit`
+    fn accumulate_probabilities(&self, qbit: usize, basis_state: usize, probability: f64, result: &mut Vec<f64>) {
+        if qbit == self.nb_qbits {
+            result[basis_state] += probability;
+            return;
+        }
+        let mask = power_of_two((self.nb_qbits - 1 - qbit) as u8);
+        match self.random_pivot(qbit) {
+            None => {
+                let outcome = self.clone().deterministic_measure(qbit);
+                let basis_state = if outcome { basis_state | mask } else { basis_state };
+                self.accumulate_probabilities(qbit + 1, basis_state, probability, result);
+            }
+            Some(pivot) => {
+                for outcome in [false, true] {
+                    let mut branch = self.clone();
+                    branch.random_measure(qbit, pivot, outcome);
+                    let basis_state = if outcome { basis_state | mask } else { basis_state };
+                    branch.accumulate_probabilities(qbit + 1, basis_state, probability * 0.5, result);
+                }
+            }
+        }
+    }
+}
+
+/// The tableau can be used as a [`Backend`] for circuits containing only Clifford gates.
+/// Unlike the dense state vector, a measurement only collapses the measured qbit.
+impl Backend for Tableau {
+    fn nb_qbits(&self) -> usize {
+        self.nb_qbits
+    }
+
+    fn apply_gate(&mut self, gate: &BaseGate, control_qbits: &[u8]) -> std::result::Result<(), String> {
+        Tableau::apply_gate(self, gate, control_qbits)
+    }
+
+    fn probability_of_one(&self, qbit: u8) -> f64 {
+        match self.deterministic_outcome(qbit as usize) {
+            None => 0.5,
+            Some(true) => 1.0,
+            Some(false) => 0.0,
+        }
+    }
+
+    fn measure(&mut self, qbit: u8) -> bool {
+        Tableau::measure(self, qbit as usize)
+    }
+
+    fn postselect(&mut self, qbit: u8, one: bool) -> std::result::Result<f64, String> {
+        Ok(Tableau::postselect(self, qbit as usize, one))
+    }
+
+    /// The number of probabilities grows exponentially with the number of qbits,
+    /// this should only be used with small registers
+    ///
+    /// # Panics
+    ///
+    /// if the tableau has too many qbits for the probabilities to fit in memory
+    fn probabilities(&self) -> Vec<f64> {
+        let nb_qbits = u8::try_from(self.nb_qbits).expect("Too many qbits to list the probabilities");
+        let mut result = vec![0.0; power_of_two(nb_qbits)];
+        self.accumulate_probabilities(0, 0, 1.0, &mut result);
+        result
+    }
+
+    fn sample(&self) -> Vec<bool> {
+        let mut tableau = self.clone();
+        (0..self.nb_qbits).map(|qbit| tableau.measure(qbit)).collect()
+    }
+
+    fn snapshot(&self) -> Option<Self> {
+        Some(self.clone())
+    }
+
+    /// Compare the stabilizer generators, the same state described
+    /// by different generators is not detected
+    fn same_state(&self, other: &Self) -> bool {
+        let words = self.nb_qbits * self.nb_words..2 * self.nb_qbits * self.nb_words;
+        let rows = self.nb_qbits..2 * self.nb_qbits;
+        self.nb_qbits == other.nb_qbits
+            && self.x[words.clone()] == other.x[words.clone()]
+            && self.z[words.clone()] == other.z[words]
+            && self.r[rows.clone()] == other.r[rows]
+    }
+}
+
+
+#[cfg(test)]
+mod tests_stabilizer {
+    use crate::common_gate::CommonGate::{CNot, Hadamard, Toffoli};
+    use crate::computer::QuantumComputer;
+    use crate::condition::StopCondition;
+    use crate::sub_circuit::SubCircuit;
+
+    use super::*;
+
+    #[test]
+    fn bell_pair_stabilizers() {
+        let mut tableau = Tableau::new(2);
+        tableau.hadamard(0);
+        tableau.cnot(0, 1);
+
+        assert_eq!(tableau.stabilizers(), vec!["+XX", "+ZZ"]);
+    }
+
+    #[test]
+    fn bell_pair_measurements_are_correlated() {
+        for _ in 0..20 {
+            let mut tableau = Tableau::new(2);
+            tableau.hadamard(0);
+            tableau.cnot(0, 1);
+
+            assert_eq!(tableau.deterministic_outcome(1), None);
+            let first = tableau.measure(0);
+            assert_eq!(tableau.deterministic_outcome(1), Some(first));
+            assert_eq!(tableau.measure(1), first);
+        }
+    }
+
+    #[test]
+    fn hadamard_phase_hadamard_is_deterministic() {
+        let mut tableau = Tableau::new(1);
+        tableau.hadamard(0);
+        tableau.phase(0);
+        tableau.phase(0);
+        tableau.hadamard(0);
+
+        assert!(tableau.measure(0));
+    }
+
+    #[test]
+    fn large_tableau_is_a_backend() {
+        let circuit = QuantumComputer::new(2).bell_state().build().unwrap();
+        let result = circuit.execute_on(Tableau::new(300)).unwrap();
+
+        assert_eq!(result.nb_qbits(), 300);
+        assert_eq!(Backend::probability_of_one(result.current_state(), 1), 0.5);
+        assert_eq!(result.current_state().deterministic_outcome(299), Some(false));
+    }
+
+    #[test]
+    fn large_ghz_circuit() {
+        let nb_qbits = 1000;
+        let mut circuit = CliffordCircuit::new(nb_qbits);
+        circuit.add_operation(CliffordOperation::Hadamard(0));
+        for i in 1..nb_qbits {
+            circuit.add_operation(CliffordOperation::CNot(i - 1, i));
+        }
+        for i in 0..nb_qbits {
+            circuit.add_operation(CliffordOperation::Measure("ghz".to_string(), i));
+        }
+
+        let result = circuit.execute().unwrap();
+        let history = result.history();
+        assert_eq!(history.len(), nb_qbits);
+        assert!(history.iter().all(|r| r.outcome == history[0].outcome));
+        assert_eq!(history[0].probability, 0.5);
+        assert!(history[1..].iter().all(|r| r.probability == 1.0));
+        assert_eq!(result.get_nb_one("ghz") + result.get_nb_zero("ghz"), nb_qbits as u32);
+    }
+
+    #[test]
+    fn invalid_clifford_circuits_are_rejected() {
+        let mut circuit = CliffordCircuit::new(300);
+        circuit.add_operation(CliffordOperation::CNot(299, 300));
+        assert!(circuit.execute().is_err());
+
+        let mut circuit = CliffordCircuit::new(300);
+        circuit.add_operation(CliffordOperation::Swap(256, 256));
+        assert!(circuit.execute().is_err());
+    }
+
+    #[test]
+    fn large_ghz_state() {
+        let nb_qbits = 1000;
+        let mut tableau = Tableau::new(nb_qbits);
+        tableau.hadamard(0);
+        for i in 1..nb_qbits {
+            tableau.cnot(i - 1, i);
+        }
+        let first = tableau.measure(0);
+        assert!((1..nb_qbits).all(|i| tableau.measure(i) == first));
+    }
+
+    #[test]
+    fn probabilities_match_dense_simulation() {
+        let computer = QuantumComputer::new(3);
+        let circuit = computer.bell_state()
+            .add_operation(BaseGate::S(1))
+            .add_operation(BaseGate::Hadamard(1))
+            .build()
+            .unwrap();
+
+        let dense = circuit.execute(&computer.zero_state()).unwrap();
+        let stabilizer = circuit.execute_stabilizer().unwrap();
+
+        let expected = dense.current_state().probabilities();
+        let actual = stabilizer.current_state().probabilities();
+        assert!(expected.iter().zip(actual.iter()).all(|(e, a)| (e - a).abs() < 1e-9));
+    }
+
+    #[test]
+    fn execute_circuit() {
+        let computer = QuantumComputer::new(200);
+        let circuit = computer.bell_state()
+            .add_measure("q0", 0)
+            .add_measure("q199", 199)
+            .build()
+            .unwrap();
+
+        let result = circuit.execute_stabilizer().unwrap();
+        assert_eq!(result.get_nb_one("q0"), result.get_nb_one("q199"));
+    }
+
+    #[test]
+    fn execute_loop() {
+        let computer = QuantumComputer::new(200);
+        let sub_circuit = computer.new_circuit_builder()
+            .add_operation(Hadamard(0))
+            .add_measure("q0", 0)
+            .build()
+            .unwrap();
+        let circuit = computer.new_circuit_builder()
+            .add_loop(sub_circuit, StopCondition::MaxIteration(5))
+            .build()
+            .unwrap();
+
+        let result = circuit.execute_stabilizer().unwrap();
+        assert_eq!(result.get_nb_zero("q0") + result.get_nb_one("q0"), 5);
+    }
+
+    #[test]
+    fn non_clifford_gates_are_rejected() {
+        let computer = QuantumComputer::new(3);
+        let toffoli = computer.new_circuit_builder().add_operation(Toffoli(2, [0, 1])).build().unwrap();
+        let controlled_hadamard = computer.new_circuit_builder().add_operation(BaseGate::Hadamard(1).with_one_control(0)).build().unwrap();
+        let clifford = computer.new_circuit_builder().add_operation(Hadamard(0)).add_operation(CNot(1, [0])).build().unwrap();
+
+        assert!(toffoli.execute_stabilizer().err().unwrap().to_string().starts_with("Multi-controlled"));
+        assert!(controlled_hadamard.execute_stabilizer().err().unwrap().to_string().starts_with("Non Clifford"));
+        assert!(clifford.execute_stabilizer().is_ok());
+
+        let pair = QuantumComputer::new(2).new_circuit_builder().add_operation(Hadamard(0)).add_operation(CNot(1, [0])).build().unwrap();
+        let controlled_pair = computer.new_circuit_builder()
+            .add_definition("pair", pair)
+            .add_sub_circuit("pair", &[1, 2])
+            .add_operation(SubCircuit::new("pair", &[1, 2]).controlled(&[0]))
+            .build()
+            .unwrap();
+        assert!(controlled_pair.execute_stabilizer().err().unwrap().to_string().starts_with("Non Clifford gate Hadamard with 1"));
+    }
+}
@@ -1,16 +1,74 @@
 use std::fmt::{Debug, Formatter, Result};
-use std::ops::{Add, Deref, DerefMut};
+use std::ops::{Add, Deref, DerefMut, Sub};
 
 use num_complex::Complex64;
-use num_traits::Zero;
+use num_traits::{One, Zero};
 
+use crate::backend::{Backend, SAME_STATE_THRESHOLD};
+use crate::base_gate::BaseGate;
+use crate::gate::check_for_no_duplicate;
+use crate::gate_op::hadamard::apply_controlled_hadamard;
+use crate::gate_op::matrix::apply_controlled_matrix;
+use crate::gate_op::pauli::{apply_controlled_not, apply_controlled_pauli_x, apply_controlled_pauli_y, apply_controlled_pauli_z};
+use crate::gate_op::phase::apply_controlled_s;
+use crate::gate_op::swap::apply_controlled_swap;
+use serde::{Deserialize, Serialize};
+
+use crate::pauli::{Pauli, PauliMasks};
 use crate::power_of_two;
 
+/// Order of the qbits in the index of a basis state
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum QbitOrdering {
+    /// The qbit 0 is the most significant bit: |01> is |0> on the qbit 0 and |1> on the qbit 1
+    #[default]
+    MsbFirst,
+    /// The qbit 0 is the least significant bit, like in Qiskit: |01> is |1> on the qbit 0
+    LsbFirst,
+}
+
+impl QbitOrdering {
+    /// Return the mask of the bit associated to a qbit in the index of a basis state
+    pub fn qbit_mask(&self, nb_qbits: u8, qbit_idx: u8) -> usize {
+        match self {
+            QbitOrdering::MsbFirst => power_of_two(nb_qbits - 1 - qbit_idx),
+            QbitOrdering::LsbFirst => power_of_two(qbit_idx),
+        }
+    }
+
+    /// Convert the index of a basis state in this ordering to its index in the other ordering
+    pub fn convert_index(&self, nb_qbits: u8, index: usize, other: QbitOrdering) -> usize {
+        if *self == other || nb_qbits == 0 {
+            index
+        } else {
+            index.reverse_bits() >> (usize::BITS - nb_qbits as u32)
+        }
+    }
+
+    /// Return the bitstring of a basis state: the binary representation of its index,
+    /// so the qbit 0 is written first with `MsbFirst` and last with `LsbFirst`
+    pub fn bitstring(&self, nb_qbits: u8, index: usize) -> String {
+        format!("{:0>1$b}", index, nb_qbits as usize)
+    }
+
+    /// Return the index of the basis state written as a bitstring (see [`bitstring`](QbitOrdering::bitstring))
+    pub fn parse_bitstring(&self, bits: &str) -> std::result::Result<usize, String> {
+        bits.chars().try_fold(0, |value: usize, c| match c {
+            '0' => Ok(value << 1),
+            '1' => Ok((value << 1) | 1),
+            _ => Err(format!("Invalid bit '{}' in {}", c, bits)),
+        })
+    }
+}
+
 /// Quantum state
 ///
+/// The amplitudes are indexed by the basis states, the position of
+/// each qbit in the index depends on the [`QbitOrdering`] of the state.
 pub struct QuantumState {
     nb_qbits: u8,
     amplitudes: Vec<Complex64>,
+    ordering: QbitOrdering,
 }
 
 impl Deref for QuantumState {
@@ -26,6 +84,8 @@ impl DerefMut for QuantumState {
     }
 }
 
+/// The basis states are written as the binary representation of their index,
+/// so the position of the qbit 0 depends on the ordering
 impl Debug for QuantumState {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         let threshold = 1e-6/((1<<self.nb_qbits) as f64);
@@ -36,7 +96,7 @@ impl Debug for QuantumState {
             .reduce(|s1, s2| s1.add(&s2))
             .unwrap_or("".to_string());
 
-        let text = format!("State {} qbits : {} ", self.nb_qbits, &wave_function);
+        let text = format!("State {} qbits ({:?}) : {} ", self.nb_qbits, self.ordering, &wave_function);
         f.write_str(&text)
     }
 }
@@ -45,39 +105,48 @@ impl QuantumState {
     pub fn nb_qbits(&self) -> u8 {
         return self.nb_qbits;
     }
+
+    pub fn ordering(&self) -> QbitOrdering {
+        self.ordering
+    }
 }
 
 impl QuantumState {
     pub(crate) fn mask(&self, qbit_idx: u8) -> usize {
-        return power_of_two(self.nb_qbits - 1 - qbit_idx);
+        self.ordering.qbit_mask(self.nb_qbits, qbit_idx)
     }
 
     pub(crate) fn control_mask(&self, control_qbits: &[u8]) -> usize {
-        control_qbits.iter()
-            .map(|i| self.mask(*i))
-            .reduce(|m1, m2| m1 + m2)
-            .unwrap_or(0)
+        control_mask(self.ordering, self.nb_qbits, control_qbits)
     }
 }
 
+pub(crate) fn control_mask(ordering: QbitOrdering, nb_qbits: u8, control_qbits: &[u8]) -> usize {
+    control_qbits.iter()
+        .map(|i| ordering.qbit_mask(nb_qbits, *i))
+        .reduce(|m1, m2| m1 + m2)
+        .unwrap_or(0)
+}
+
 
 impl QuantumState {
-    pub(crate) fn same_amplitude(nb_qbits: u8, qbit_idx: &[usize]) -> QuantumState {
+    pub(crate) fn same_amplitude(nb_qbits: u8, basis_states: &[usize]) -> QuantumState {
         let nb_amplitudes = power_of_two(nb_qbits);
         let mut amplitudes = Vec::with_capacity(nb_amplitudes);
         amplitudes.resize_with(nb_amplitudes, || Complex64::zero());
 
-        let amplitude = Complex64::new((1.0 / qbit_idx.len() as f64).sqrt(), 0.0);
+        let amplitude = Complex64::new((1.0 / basis_states.len() as f64).sqrt(), 0.0);
 
-        for qbit_idx in qbit_idx {
-            amplitudes[*qbit_idx] = amplitude;
+        for basis_state in basis_states {
+            amplitudes[*basis_state] = amplitude;
         }
 
-        return Self { nb_qbits, amplitudes };
+        return Self { nb_qbits, amplitudes, ordering: QbitOrdering::default() };
     }
 
 
-    pub(crate) fn zero(nb_quits: u8) -> Self {
+    /// Return the state |0...0>
+    pub fn zero(nb_quits: u8) -> Self {
         QuantumState::same_amplitude(nb_quits, &[0])
     }
 
@@ -85,11 +154,507 @@ impl QuantumState {
         let nb_amplitudes = power_of_two(nb_quits);
         let mut amplitudes = Vec::with_capacity(nb_amplitudes);
         amplitudes.resize_with(nb_amplitudes, || Complex64::zero());
-        Self { nb_qbits: nb_quits, amplitudes }
+        Self { nb_qbits: nb_quits, amplitudes, ordering: QbitOrdering::default() }
+    }
+
+    /// Return a state with all amplitudes to zero and the same qbits and ordering as this one
+    pub(crate) fn nil_like(&self) -> Self {
+        QuantumState::nil(self.nb_qbits).with_ordering_unchanged(self.ordering)
+    }
+
+    /// Set the ordering without moving the amplitudes
+    pub(crate) fn with_ordering_unchanged(mut self, ordering: QbitOrdering) -> Self {
+        self.ordering = ordering;
+        self
     }
 
     pub(crate) fn from(other: &QuantumState) -> Self {
-        Self { nb_qbits: other.nb_qbits, amplitudes: other.amplitudes.clone() }
+        Self { nb_qbits: other.nb_qbits, amplitudes: other.amplitudes.clone(), ordering: other.ordering }
+    }
+
+    pub(crate) fn _norm_of_diff(&self, idx: usize, reference: Complex64) -> f64 {
+        self.amplitudes[idx].sub(reference).norm()
+    }
+}
+
+/// Public constructors, the states use the default ordering unless specified
+impl QuantumState {
+    /// Create the basis state written as a bitstring, "01" being |0> on the qbit 0 and |1> on the qbit 1
+    pub fn from_bitstring(bits: &str) -> std::result::Result<Self, String> {
+        QuantumState::from_bitstrings(&[bits])
+    }
+
+    /// Create the superposition with the same amplitude of the basis states written as bitstrings,
+    /// the qbit 0 first
+    pub fn from_bitstrings(bitstrings: &[&str]) -> std::result::Result<Self, String> {
+        QuantumState::from_bitstrings_in(bitstrings, QbitOrdering::default())
+    }
+
+    /// Create the superposition with the same amplitude of the basis states written as bitstrings
+    /// in the given ordering (with `LsbFirst`, the qbit 0 is the last character)
+    pub fn from_bitstrings_in(bitstrings: &[&str], ordering: QbitOrdering) -> std::result::Result<Self, String> {
+        let nb_qbits = bitstrings.first().ok_or("At least one bitstring is needed")?.len();
+        if nb_qbits == 0 || nb_qbits > u8::MAX as usize {
+            return Err(format!("Invalid number of qbits {}", nb_qbits));
+        }
+        let mut basis_states = Vec::with_capacity(bitstrings.len());
+        for bits in bitstrings.iter() {
+            if bits.len() != nb_qbits {
+                return Err(format!("The bitstring {} does not have {} qbits", bits, nb_qbits));
+            }
+            let basis_state = ordering.parse_bitstring(bits)?;
+            if basis_states.contains(&basis_state) {
+                return Err(format!("The bitstring {} is given twice", bits));
+            }
+            basis_states.push(basis_state);
+        }
+        Ok(QuantumState::same_amplitude(nb_qbits as u8, &basis_states).with_ordering_unchanged(ordering))
+    }
+
+    /// Create a state from its amplitudes, indexed with the qbit 0 as most significant bit.
+    /// The number of amplitudes must be a power of two, the amplitudes are normalized.
+    pub fn from_amplitudes(amplitudes: Vec<Complex64>) -> std::result::Result<Self, String> {
+        QuantumState::from_amplitudes_in(amplitudes, QbitOrdering::default())
+    }
+
+    /// Create a state from its amplitudes indexed in the given ordering
+    pub fn from_amplitudes_in(amplitudes: Vec<Complex64>, ordering: QbitOrdering) -> std::result::Result<Self, String> {
+        let len = amplitudes.len();
+        if len < 2 || !len.is_power_of_two() {
+            return Err(format!("The number of amplitudes must be a power of two greater than 1, got {}", len));
+        }
+        let norm = amplitudes.iter().map(|a| a.norm_sqr()).sum::<f64>().sqrt();
+        if !norm.is_finite() || norm <= f64::EPSILON {
+            return Err("The amplitudes cannot be normalized".to_string());
+        }
+        let nb_qbits = len.trailing_zeros() as u8;
+        Ok(Self { nb_qbits, amplitudes: amplitudes.into_iter().map(|a| a / norm).collect(), ordering })
+    }
+
+    /// Create the product of single qbit states given as (amplitude of |0>, amplitude of |1>),
+    /// each one being normalized
+    pub fn from_qbit_states(qbit_states: &[[Complex64; 2]]) -> std::result::Result<Self, String> {
+        let mut result: Option<QuantumState> = None;
+        for qbit_state in qbit_states.iter() {
+            let qbit_state = QuantumState::from_amplitudes(qbit_state.to_vec())?;
+            result = Some(match result {
+                None => qbit_state,
+                Some(state) => state.tensor(&qbit_state),
+            });
+        }
+        result.ok_or("At least one qbit state is needed".to_string())
+    }
+
+    /// Return the tensor product |self>|other>, the qbits of `other` being added after the ones
+    /// of this state. The result has the ordering of this state.
+    pub fn tensor(&self, other: &QuantumState) -> QuantumState {
+        let converted;
+        let other = if other.ordering == self.ordering {
+            other
+        } else {
+            converted = other.to_ordering(self.ordering);
+            &converted
+        };
+        let (high, low) = match self.ordering {
+            QbitOrdering::MsbFirst => (self, other),
+            QbitOrdering::LsbFirst => (other, self),
+        };
+        let mut amplitudes = Vec::with_capacity(high.amplitudes.len() * low.amplitudes.len());
+        for a in high.amplitudes.iter() {
+            amplitudes.extend(low.amplitudes.iter().map(|b| a * b));
+        }
+        Self { nb_qbits: self.nb_qbits + other.nb_qbits, amplitudes, ordering: self.ordering }
+    }
+
+    /// Return the same state with its amplitudes indexed in the given ordering.
+    /// This copies the amplitudes even if the ordering is the same.
+    pub fn to_ordering(&self, ordering: QbitOrdering) -> QuantumState {
+        if ordering == self.ordering {
+            return QuantumState::from(self);
+        }
+        let mut result = QuantumState::nil(self.nb_qbits).with_ordering_unchanged(ordering);
+        for (i, a) in self.amplitudes.iter().enumerate() {
+            result[self.ordering.convert_index(self.nb_qbits, i, ordering)] = *a;
+        }
+        result
+    }
+
+    /// Create a random state drawn from the Haar measure,
+    /// the amplitudes are normalized complex Gaussian numbers
+    pub fn random(nb_qbits: u8) -> Self {
+        let gaussian = || {
+            let radius = (-2.0 * (1.0 - rand::random::<f64>()).ln()).sqrt();
+            let angle = 2.0 * std::f64::consts::PI * rand::random::<f64>();
+            Complex64::from_polar(radius, angle)
+        };
+        let amplitudes = (0..power_of_two(nb_qbits)).map(|_| gaussian()).collect();
+        QuantumState::from_amplitudes(amplitudes).unwrap_or_else(|_| QuantumState::zero(nb_qbits))
+    }
+}
+
+impl QuantumState {
+    /// Pick a basis state with the probabilities given by the amplitudes
+    fn pick_on_state(&self) -> usize {
+        let mut target = 1.0 - (rand::random::<f64>());
+        for (index, amplitude) in self.amplitudes.iter().enumerate() {
+            target -= amplitude.norm_sqr();
+            if target <= 0.0 {
+                return index;
+            }
+        };
+        self.amplitudes.len() - 1
+    }
+
+    fn bits_of(&self, basis_state: usize) -> Vec<bool> {
+        (0..self.nb_qbits).map(|qbit| (basis_state & self.mask(qbit)) != 0).collect()
+    }
+}
+
+impl Backend for QuantumState {
+    fn nb_qbits(&self) -> usize {
+        self.nb_qbits as usize
+    }
+
+    fn apply_gate(&mut self, gate: &BaseGate, control_qbits: &[u8]) -> std::result::Result<(), String> {
+        match gate {
+            BaseGate::Not(target) => apply_controlled_not(*target, control_qbits, self),
+            BaseGate::X(target) => apply_controlled_pauli_x(*target, control_qbits, self),
+            BaseGate::Y(target) => apply_controlled_pauli_y(*target, control_qbits, self),
+            BaseGate::Z(target) => apply_controlled_pauli_z(*target, control_qbits, self),
+            BaseGate::Hadamard(target) => apply_controlled_hadamard(control_qbits, *target, self),
+            BaseGate::Swap(target1, target2) => apply_controlled_swap(control_qbits, *target1, *target2, self),
+            BaseGate::S(target) => apply_controlled_s(*target, control_qbits, self),
+            BaseGate::T(target) | BaseGate::Phase(_, target) | BaseGate::Rx(_, target) | BaseGate::Ry(_, target) | BaseGate::Rz(_, target) => {
+                let matrix = gate.matrix().expect("single qbit gates have a matrix");
+                apply_controlled_matrix(*target, control_qbits, matrix, self)
+            }
+        };
+        Ok(())
+    }
+
+    fn probability_of_one(&self, qbit: u8) -> f64 {
+        let mask = self.mask(qbit);
+        self.amplitudes.iter()
+            .enumerate()
+            .filter(|(i, _)| (i & mask) != 0)
+            .map(|(_, a)| a.norm_sqr())
+            .sum()
+    }
+
+    /// The state is collapsed to one basis state: after the measurement
+    /// there is no superposition left, even on the qbits that were not measured
+    fn measure(&mut self, qbit: u8) -> bool {
+        let select_state = self.pick_on_state();
+        let mut output = self.nil_like();
+        output[select_state] = Complex64::one();
+        *self = output;
+        (select_state & self.mask(qbit)) != 0
+    }
+
+    fn postselect(&mut self, qbit: u8, one: bool) -> std::result::Result<f64, String> {
+        let probability_of_one = self.probability_of_one(qbit);
+        let probability = if one { probability_of_one } else { 1.0 - probability_of_one };
+        if probability <= 0.0 {
+            return Ok(0.0);
+        }
+        let mask = self.mask(qbit);
+        let scale = 1.0 / probability.sqrt();
+        for (i, a) in self.amplitudes.iter_mut().enumerate() {
+            *a = if ((i & mask) != 0) == one { *a * scale } else { Complex64::zero() };
+        }
+        Ok(probability)
+    }
+
+    fn probabilities(&self) -> Vec<f64> {
+        self.amplitudes.iter().map(|a| a.norm_sqr()).collect()
+    }
+
+    fn sample(&self) -> Vec<bool> {
+        self.bits_of(self.pick_on_state())
+    }
+
+    fn pauli_expectation(&self, product: &[(u8, Pauli)]) -> std::result::Result<f64, String> {
+        let masks = PauliMasks::new(product, |qbit| self.mask(qbit));
+        Ok(self.amplitudes.iter()
+            .enumerate()
+            .map(|(i, a)| {
+                let (j, phase) = masks.apply(i);
+                (self.amplitudes[j].conj() * phase * a).re
+            })
+            .sum())
+    }
+
+    /// The state is projected on the eigenspace of the measured eigenvalue
+    fn measure_pauli_product(&mut self, product: &[(u8, Pauli)]) -> std::result::Result<bool, String> {
+        let probability_of_one = (1.0 - self.pauli_expectation(product)?) / 2.0;
+        let measured_one = rand::random::<f64>() < probability_of_one;
+        let (sign, probability) = if measured_one { (-1.0, probability_of_one) } else { (1.0, 1.0 - probability_of_one) };
+
+        let masks = PauliMasks::new(product, |qbit| self.mask(qbit));
+        let mut result = self.nil_like();
+        for (i, a) in self.amplitudes.iter().enumerate() {
+            let (j, phase) = masks.apply(i);
+            result[i] += a;
+            result[j] += phase * a * sign;
+        }
+        let scale = 1.0 / (2.0 * probability.sqrt());
+        result.amplitudes.iter_mut().for_each(|a| *a *= scale);
+        *self = result;
+        Ok(measured_one)
+    }
+
+    fn snapshot(&self) -> Option<Self> {
+        Some(QuantumState::from(self))
+    }
+
+    fn same_state(&self, other: &Self) -> bool {
+        match self.inner_product(other) {
+            Ok(overlap) => overlap.norm_sqr() >= SAME_STATE_THRESHOLD,
+            Err(_) => false,
+        }
+    }
+}
+
+/// Analysis of the state, the probabilities are indexed like the amplitudes
+impl QuantumState {
+    /// Return the probability of the given basis state, fails if there is no such basis state
+    pub fn probability(&self, basis_state: usize) -> std::result::Result<f64, String> {
+        self.amplitudes.get(basis_state)
+            .map(|a| a.norm_sqr())
+            .ok_or(format!("No basis state {} for a state of {} qbits", basis_state, self.nb_qbits))
+    }
+
+    /// Fail if a qbit is not in the state or is given twice
+    fn check_qbits(&self, qbits: &[u8]) -> std::result::Result<(), String> {
+        if let Some(qbit) = qbits.iter().find(|q| **q >= self.nb_qbits) {
+            return Err(format!("No qbit {} in a state of {} qbits", qbit, self.nb_qbits));
+        }
+        check_for_no_duplicate(qbits.to_vec())
+    }
+
+    /// Return the probability of each value of the given qbits, the other qbits being ignored.
+    /// The first qbit gives the most significant bit of the value, like with a register measurement.
+    /// Fails if a qbit is not in the state or is given twice.
+    pub fn marginal(&self, qbits: &[u8]) -> std::result::Result<Vec<f64>, String> {
+        self.check_qbits(qbits)?;
+        let masks: Vec<usize> = qbits.iter().map(|q| self.mask(*q)).collect();
+        let mut result = vec![0.0; power_of_two(qbits.len() as u8)];
+        for (i, a) in self.amplitudes.iter().enumerate() {
+            let value = masks.iter().fold(0, |value, mask| (value << 1) | ((i & mask) != 0) as usize);
+            result[value] += a.norm_sqr();
+        }
+        Ok(result)
+    }
+
+    /// Return the probabilities of |0> and |1> for the given qbit, fails if the qbit is not in the state
+    pub fn qbit_marginal(&self, qbit: u8) -> std::result::Result<[f64; 2], String> {
+        self.check_qbits(&[qbit])?;
+        let probability_of_one = self.probability_of_one(qbit);
+        Ok([1.0 - probability_of_one, probability_of_one])
+    }
+
+    /// Return the Bloch vector (<X>, <Y>, <Z>) of the given qbit. Its length is
+    /// 1 for a qbit in a pure state and less when the qbit is entangled.
+    /// Fails if the qbit is not in the state.
+    pub fn bloch_vector(&self, qbit: u8) -> std::result::Result<[f64; 3], String> {
+        self.check_qbits(&[qbit])?;
+        let mut result = [0.0; 3];
+        for (component, pauli) in result.iter_mut().zip([Pauli::X, Pauli::Y, Pauli::Z]) {
+            *component = self.pauli_expectation(&[(qbit, pauli)])?;
+        }
+        Ok(result)
+    }
+
+    /// Return the Bloch vector of each qbit, qbit 0 first
+    pub fn bloch_vectors(&self) -> Vec<[f64; 3]> {
+        (0..self.nb_qbits).map(|qbit| self.bloch_vector(qbit).expect("the qbits of the state are valid")).collect()
+    }
+
+    /// Return <self|other>, fails if the states do not have the same number of qbits
+    pub fn inner_product(&self, other: &QuantumState) -> std::result::Result<Complex64, String> {
+        if self.nb_qbits != other.nb_qbits {
+            return Err(format!("Cannot compare a state of {} qbits with a state of {} qbits", self.nb_qbits, other.nb_qbits));
+        }
+        let converted;
+        let other = if other.ordering == self.ordering {
+            other
+        } else {
+            converted = other.to_ordering(self.ordering);
+            &converted
+        };
+        Ok(self.amplitudes.iter().zip(other.amplitudes.iter()).map(|(a, b)| a.conj() * b).sum())
+    }
+
+    /// Return |<self|other>|², 1 for the same states and 0 for orthogonal states
+    pub fn fidelity(&self, other: &QuantumState) -> std::result::Result<f64, String> {
+        self.inner_product(other).map(|overlap| overlap.norm_sqr())
+    }
+
+    /// Return true if the amplitudes of the other state are the ones of this state
+    /// multiplied by a global phase, each amplitude being compared with the tolerance
+    pub fn equals_up_to_global_phase(&self, other: &QuantumState, tolerance: f64) -> bool {
+        if self.nb_qbits != other.nb_qbits {
+            return false;
+        }
+        let converted;
+        let other = if other.ordering == self.ordering {
+            other
+        } else {
+            converted = other.to_ordering(self.ordering);
+            &converted
+        };
+        let reference = (0..self.amplitudes.len())
+            .max_by(|i, j| self.amplitudes[*i].norm_sqr().total_cmp(&self.amplitudes[*j].norm_sqr()))
+            .unwrap_or(0);
+        if self.amplitudes[reference].norm() <= tolerance {
+            return other.amplitudes.iter().all(|a| a.norm() <= tolerance);
+        }
+        let phase = other.amplitudes[reference] / self.amplitudes[reference];
+        let phase = phase / phase.norm();
+        self.amplitudes.iter().zip(other.amplitudes.iter()).all(|(a, b)| (a * phase - b).norm() <= tolerance)
+    }
+}
+
+
+#[cfg(test)]
+mod tests_state {
+    use std::f64::consts::FRAC_1_SQRT_2;
+
+    use crate::base_gate::BaseGate;
+    use crate::common_gate::CommonGate::{CNot, Hadamard, Not, S};
+    use crate::computer::QuantumComputer;
+    use crate::sparse::SparseState;
+
+    use super::*;
+
+    #[test]
+    fn constructors() {
+        let basis = QuantumState::from_bitstring("011").unwrap();
+        assert!((basis.probability(0b011).unwrap() - 1.0).abs() < 1e-9);
+        assert!((basis.probability_of_one(0)).abs() < 1e-9);
+        assert!(QuantumState::from_bitstring("012").is_err());
+
+        let bell = QuantumState::from_bitstrings(&["00", "11"]).unwrap();
+        let circuit = QuantumComputer::new(2).bell_state().build().unwrap();
+        assert!(bell.equals_up_to_global_phase(circuit.execute(&QuantumState::zero(2)).unwrap().current_state(), 1e-9));
+        assert!(QuantumState::from_bitstrings(&["00", "110"]).is_err());
+
+        let amplitudes = QuantumState::from_amplitudes(vec![Complex64::new(1.0, 0.0), Complex64::zero(), Complex64::zero(), Complex64::new(0.0, 1.0)]).unwrap();
+        assert_eq!(amplitudes.nb_qbits(), 2);
+        assert!((amplitudes.probability(3).unwrap() - 0.5).abs() < 1e-9);
+        assert!(QuantumState::from_amplitudes(vec![Complex64::one(); 3]).is_err());
+        assert!(QuantumState::from_amplitudes(vec![Complex64::zero(); 4]).is_err());
+    }
+
+    #[test]
+    fn qbit_ordering() {
+        let msb = QuantumComputer::new(3);
+        let lsb = QuantumComputer::new(3).with_ordering(QbitOrdering::LsbFirst);
+        let circuit = msb.new_circuit_builder()
+            .add_operation(Not(0))
+            .add_operation(Hadamard(1))
+            .add_operation(CNot(2, [1]))
+            .add_operation(S(2))
+            .build()
+            .unwrap();
+
+        let msb_state = circuit.execute(&msb.zero_state()).unwrap().current_state().to_ordering(QbitOrdering::MsbFirst);
+        let lsb_result = circuit.execute(&lsb.zero_state()).unwrap();
+        let lsb_state = lsb_result.current_state();
+        assert_eq!(lsb_state.ordering(), QbitOrdering::LsbFirst);
+        assert!((lsb_state.probability(0b001).unwrap() - 0.5).abs() < 1e-9);
+        assert!((msb_state.probability(0b100).unwrap() - 0.5).abs() < 1e-9);
+        assert!((lsb_state.fidelity(&msb_state).unwrap() - 1.0).abs() < 1e-9);
+        assert!(lsb_state.equals_up_to_global_phase(&msb_state.to_ordering(QbitOrdering::LsbFirst), 1e-9));
+        assert!(format!("{:?}", lsb_state).contains("|001>"));
+        assert_eq!(QbitOrdering::LsbFirst.bitstring(3, 0b110), "110");
+
+        let sparse = SparseState::from_dense(&lsb.zero_state());
+        let sparse_result = circuit.execute_on(sparse).unwrap();
+        assert!((sparse_result.current_state().to_dense().fidelity(&msb_state).unwrap() - 1.0).abs() < 1e-9);
+        assert!((lsb_state.pauli_expectation(&[(0, Pauli::Z)]).unwrap() + 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn constructors_with_ordering() {
+        let lsb = QuantumState::from_bitstrings_in(&["001"], QbitOrdering::LsbFirst).unwrap();
+        assert!((lsb.probability_of_one(0) - 1.0).abs() < 1e-9);
+
+        let product = lsb.tensor(&QuantumState::from_bitstring("01").unwrap());
+        assert_eq!(product.ordering(), QbitOrdering::LsbFirst);
+        assert!(product.equals_up_to_global_phase(&QuantumState::from_bitstring("10001").unwrap(), 1e-9));
+    }
+
+    #[test]
+    fn tensor_products() {
+        let plus = [Complex64::new(FRAC_1_SQRT_2, 0.0), Complex64::new(FRAC_1_SQRT_2, 0.0)];
+        let one = [Complex64::zero(), Complex64::one()];
+        let state = QuantumState::from_qbit_states(&[plus, one]).unwrap();
+        let bloch = state.bloch_vectors();
+        assert!((bloch[0][0] - 1.0).abs() < 1e-9);
+        assert!((bloch[1][2] + 1.0).abs() < 1e-9);
+
+        let product = state.tensor(&QuantumState::from_bitstring("1").unwrap());
+        assert_eq!(product.nb_qbits(), 3);
+        assert!((product.probability_of_one(2) - 1.0).abs() < 1e-9);
+        assert!((product.bloch_vector(0).unwrap()[0] - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn random_states_are_normalized() {
+        let state = QuantumState::random(4);
+        assert!((state.probabilities().iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(state.fidelity(&QuantumState::random(4)).unwrap() < 1.0 - 1e-9);
+    }
+
+    #[test]
+    fn marginal_distributions() {
+        let state = QuantumState::from_bitstrings(&["001", "111"]).unwrap();
+
+        assert!((state.probability(0b001).unwrap() - 0.5).abs() < 1e-9);
+        let marginal = state.marginal(&[2, 0]).unwrap();
+        for (value, expected) in [0.0, 0.0, 0.5, 0.5].iter().enumerate() {
+            assert!((marginal[value] - expected).abs() < 1e-9);
+        }
+        let [zero, one] = state.qbit_marginal(1).unwrap();
+        assert!((zero - 0.5).abs() < 1e-9 && (one - 0.5).abs() < 1e-9);
+
+        assert!(state.probability(8).is_err());
+        assert!(state.marginal(&[0, 3]).is_err());
+        assert!(state.marginal(&[1, 1]).is_err());
+        assert!(state.qbit_marginal(3).is_err());
+        assert!(state.bloch_vector(3).is_err());
+    }
+
+    #[test]
+    fn bloch_vectors() {
+        let mut state = QuantumState::from_bitstrings(&["000", "110"]).unwrap();
+        state.apply_gate(&BaseGate::Hadamard(2), &[]).unwrap();
+        state.apply_gate(&BaseGate::S(2), &[]).unwrap();
+
+        let vectors = state.bloch_vectors();
+        for (vector, expected) in vectors.iter().zip([[0.0, 0.0, 0.0], [0.0, 0.0, 0.0], [0.0, 1.0, 0.0]]) {
+            for k in 0..3 {
+                assert!((vector[k] - expected[k]).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn fidelity_and_global_phase() {
+        let state = QuantumState::from_bitstrings(&["00", "11"]).unwrap();
+        let mut with_phase = QuantumState::from(&state);
+        with_phase.iter_mut().for_each(|a| *a *= Complex64::i());
+        let mut other = QuantumState::from(&state);
+        other.apply_gate(&BaseGate::Z(0), &[]).unwrap();
+
+        assert!((state.inner_product(&with_phase).unwrap() - Complex64::i()).norm() < 1e-9);
+        assert!((state.fidelity(&with_phase).unwrap() - 1.0).abs() < 1e-9);
+        assert!(state.equals_up_to_global_phase(&with_phase, 1e-9));
+        assert!(state.fidelity(&other).unwrap().abs() < 1e-9);
+        assert!(!state.equals_up_to_global_phase(&other, 1e-9));
+        assert!(state.fidelity(&QuantumState::zero(3)).is_err());
+        assert!((state.fidelity(&QuantumState::zero(2)).unwrap() - FRAC_1_SQRT_2 * FRAC_1_SQRT_2).abs() < 1e-9);
     }
 }
 
@@ -0,0 +1,357 @@
+use std::convert::TryInto;
+use std::io::{Read, Write};
+
+use num_complex::Complex64;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::state::{QbitOrdering, QuantumState};
+
+/// First bytes of the binary format
+const BINARY_MAGIC: &[u8; 6] = b"QSTATE";
+const BINARY_VERSION: u8 = 1;
+/// First bytes of the NumPy format
+const NPY_MAGIC: &[u8; 6] = b"\x93NUMPY";
+/// The NumPy header is padded so that the data is aligned on this size
+const NPY_ALIGNMENT: usize = 64;
+/// Largest state that can be read, 2^30 amplitudes take 16 GBytes
+const MAX_READ_QBITS: u32 = 30;
+
+/// Precision of the amplitudes in the binary format
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Precision {
+    /// two f32 per amplitude
+    Single,
+    /// two f64 per amplitude
+    Double,
+}
+
+impl Precision {
+    fn nb_bytes(&self) -> u8 {
+        match self {
+            Precision::Single => 4,
+            Precision::Double => 8,
+        }
+    }
+
+    fn from_nb_bytes(nb_bytes: u8) -> Result<Self, String> {
+        match nb_bytes {
+            4 => Ok(Precision::Single),
+            8 => Ok(Precision::Double),
+            _ => Err(format!("Invalid precision of {} bytes", nb_bytes)),
+        }
+    }
+}
+
+/// Serialized form of a state: the amplitudes as (real, imaginary) pairs
+#[derive(Serialize, Deserialize)]
+struct SerializedState {
+    nb_qbits: u8,
+    #[serde(default)]
+    ordering: QbitOrdering,
+    amplitudes: Vec<(f64, f64)>,
+}
+
+impl Serialize for QuantumState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let amplitudes = self.iter().map(|a| (a.re, a.im)).collect();
+        SerializedState { nb_qbits: self.nb_qbits(), ordering: self.ordering(), amplitudes }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for QuantumState {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let serialized = SerializedState::deserialize(deserializer)?;
+        let amplitudes = serialized.amplitudes.into_iter().map(|(re, im)| Complex64::new(re, im)).collect();
+        let state = QuantumState::from_amplitudes_in(amplitudes, serialized.ordering).map_err(D::Error::custom)?;
+        if state.nb_qbits() != serialized.nb_qbits {
+            return Err(D::Error::custom(format!("Expected {} qbits, got {} amplitudes", serialized.nb_qbits, state.len())));
+        }
+        Ok(state)
+    }
+}
+
+fn io_error(error: std::io::Error) -> String {
+    format!("Cannot read or write the state : {}", error)
+}
+
+/// The buffer grows with the bytes actually read: a corrupted size
+/// fails at the end of the input instead of being allocated up front
+fn read_bytes<R: Read>(reader: &mut R, nb_bytes: usize) -> Result<Vec<u8>, String> {
+    let mut buffer = vec![];
+    reader.by_ref().take(nb_bytes as u64).read_to_end(&mut buffer).map_err(io_error)?;
+    if buffer.len() != nb_bytes {
+        return Err(format!("Cannot read the state : expected {} bytes, got {}", nb_bytes, buffer.len()));
+    }
+    Ok(buffer)
+}
+
+/// Read the given number of amplitudes, which must be a power of two
+/// and not exceed 2^MAX_READ_QBITS
+fn read_amplitudes<R: Read>(reader: &mut R, nb_amplitudes: usize, precision: Precision, little_endian: bool) -> Result<Vec<Complex64>, String> {
+    if nb_amplitudes < 2 || !nb_amplitudes.is_power_of_two() || nb_amplitudes.trailing_zeros() > MAX_READ_QBITS {
+        return Err(format!("The number of amplitudes must be a power of two between 2 and 2^{}, got {}", MAX_READ_QBITS, nb_amplitudes));
+    }
+    let nb_bytes = nb_amplitudes.checked_mul(2 * precision.nb_bytes() as usize)
+        .ok_or(format!("Too many amplitudes {}", nb_amplitudes))?;
+    let data = read_bytes(reader, nb_bytes)?;
+    Ok(decode_amplitudes(&data, precision, little_endian))
+}
+
+/// Decode the amplitudes stored as (real, imaginary) pairs
+fn decode_amplitudes(data: &[u8], precision: Precision, little_endian: bool) -> Vec<Complex64> {
+    let decode = |bytes: &[u8]| -> f64 {
+        match (precision, little_endian) {
+            (Precision::Single, true) => f32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            (Precision::Single, false) => f32::from_be_bytes(bytes.try_into().unwrap()) as f64,
+            (Precision::Double, true) => f64::from_le_bytes(bytes.try_into().unwrap()),
+            (Precision::Double, false) => f64::from_be_bytes(bytes.try_into().unwrap()),
+        }
+    };
+    data.chunks_exact(2 * precision.nb_bytes() as usize)
+        .map(|pair| {
+            let (re, im) = pair.split_at(precision.nb_bytes() as usize);
+            Complex64::new(decode(re), decode(im))
+        })
+        .collect()
+}
+
+impl QuantumState {
+    pub fn to_json(&self) -> serde_json::error::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    pub fn from_json(serialized_state: &str) -> serde_json::error::Result<Self> {
+        serde_json::from_str(serialized_state)
+    }
+
+    /// Write the state in a compact binary format: a header ("QSTATE", version,
+    /// endianness, precision in bytes, number of qbits and qbit ordering) followed
+    /// by the amplitudes as (real, imaginary) pairs in the native endianness
+    pub fn write_binary<W: Write>(&self, writer: &mut W, precision: Precision) -> Result<(), String> {
+        let little_endian = cfg!(target_endian = "little");
+        let lsb_first = self.ordering() == QbitOrdering::LsbFirst;
+        let header = [BINARY_VERSION, little_endian as u8, precision.nb_bytes(), self.nb_qbits(), lsb_first as u8];
+        writer.write_all(BINARY_MAGIC).map_err(io_error)?;
+        writer.write_all(&header).map_err(io_error)?;
+
+        let mut data = Vec::with_capacity(self.len() * 2 * precision.nb_bytes() as usize);
+        for a in self.iter() {
+            match precision {
+                Precision::Single => {
+                    data.extend_from_slice(&(a.re as f32).to_ne_bytes());
+                    data.extend_from_slice(&(a.im as f32).to_ne_bytes());
+                }
+                Precision::Double => {
+                    data.extend_from_slice(&a.re.to_ne_bytes());
+                    data.extend_from_slice(&a.im.to_ne_bytes());
+                }
+            }
+        }
+        writer.write_all(&data).map_err(io_error)
+    }
+
+    /// Read a state written by [`write_binary`](QuantumState::write_binary), whatever
+    /// the endianness of the machine that wrote it
+    pub fn read_binary<R: Read>(reader: &mut R) -> Result<Self, String> {
+        if read_bytes(reader, BINARY_MAGIC.len())? != BINARY_MAGIC {
+            return Err("Not a binary quantum state".to_string());
+        }
+        let header = read_bytes(reader, 5)?;
+        if header[0] != BINARY_VERSION {
+            return Err(format!("Unsupported binary format version {}", header[0]));
+        }
+        let little_endian = header[1] != 0;
+        let precision = Precision::from_nb_bytes(header[2])?;
+        let nb_qbits = header[3];
+        let ordering = if header[4] != 0 { QbitOrdering::LsbFirst } else { QbitOrdering::MsbFirst };
+        if nb_qbits == 0 || nb_qbits as u32 > MAX_READ_QBITS {
+            return Err(format!("Invalid number of qbits {}", nb_qbits));
+        }
+
+        let amplitudes = read_amplitudes(reader, 1usize << nb_qbits, precision, little_endian)?;
+        QuantumState::from_amplitudes_in(amplitudes, ordering)
+    }
+
+    /// Write the amplitudes as a one dimensional NumPy array of complex128 (`.npy` format 1.0)
+    /// indexed in the default ordering
+    pub fn write_npy<W: Write>(&self, writer: &mut W) -> Result<(), String> {
+        self.write_npy_in(writer, QbitOrdering::default())
+    }
+
+    /// Same as [`write_npy`](QuantumState::write_npy) with the amplitudes
+    /// indexed in the given ordering (`LsbFirst` for Qiskit)
+    pub fn write_npy_in<W: Write>(&self, writer: &mut W, ordering: QbitOrdering) -> Result<(), String> {
+        let state = self.to_ordering(ordering);
+        let mut header = format!("{{'descr': '<c16', 'fortran_order': False, 'shape': ({},), }}", self.len());
+        let unpadded = NPY_MAGIC.len() + 4 + header.len() + 1;
+        let padding = (NPY_ALIGNMENT - unpadded % NPY_ALIGNMENT) % NPY_ALIGNMENT;
+        header.push_str(&" ".repeat(padding));
+        header.push('\n');
+
+        writer.write_all(NPY_MAGIC).map_err(io_error)?;
+        writer.write_all(&[1, 0]).map_err(io_error)?;
+        writer.write_all(&(header.len() as u16).to_le_bytes()).map_err(io_error)?;
+        writer.write_all(header.as_bytes()).map_err(io_error)?;
+
+        let mut data = Vec::with_capacity(state.len() * 16);
+        for a in state.iter() {
+            data.extend_from_slice(&a.re.to_le_bytes());
+            data.extend_from_slice(&a.im.to_le_bytes());
+        }
+        writer.write_all(&data).map_err(io_error)
+    }
+
+    /// Read a one dimensional NumPy array of complex64 or complex128 (`.npy` format 1.0 to 3.0).
+    /// The amplitudes are normalized.
+    pub fn read_npy<R: Read>(reader: &mut R) -> Result<Self, String> {
+        QuantumState::read_npy_in(reader, QbitOrdering::default())
+    }
+
+    /// Same as [`read_npy`](QuantumState::read_npy) with the amplitudes indexed in the given ordering
+    pub fn read_npy_in<R: Read>(reader: &mut R, ordering: QbitOrdering) -> Result<Self, String> {
+        if read_bytes(reader, NPY_MAGIC.len())? != NPY_MAGIC {
+            return Err("Not a NumPy file".to_string());
+        }
+        let version = read_bytes(reader, 2)?;
+        let header_len = match version[0] {
+            1 => u16::from_le_bytes(read_bytes(reader, 2)?.try_into().unwrap()) as usize,
+            2 | 3 => u32::from_le_bytes(read_bytes(reader, 4)?.try_into().unwrap()) as usize,
+            v => return Err(format!("Unsupported NumPy format version {}", v)),
+        };
+        let header = String::from_utf8(read_bytes(reader, header_len)?).map_err(|e| e.to_string())?;
+
+        let descr = npy_header_value(&header, "descr")?;
+        let (little_endian, precision) = match descr.trim_matches(|c| c == '\'' || c == '"') {
+            "<c16" => (true, Precision::Double),
+            ">c16" => (false, Precision::Double),
+            "<c8" => (true, Precision::Single),
+            ">c8" => (false, Precision::Single),
+            other => return Err(format!("Unsupported NumPy type {}, expected complex amplitudes", other)),
+        };
+        let shape = npy_header_value(&header, "shape")?;
+        let dimensions: Vec<&str> = shape.trim_matches(|c| c == '(' || c == ')').split(',').map(|d| d.trim()).filter(|d| !d.is_empty()).collect();
+        let len = match dimensions.as_slice() {
+            [len] => len.parse::<usize>().map_err(|e| format!("Invalid NumPy shape {} : {}", shape, e))?,
+            _ => return Err(format!("Expected a one dimensional NumPy array, got the shape {}", shape)),
+        };
+
+        let amplitudes = read_amplitudes(reader, len, precision, little_endian)?;
+        QuantumState::from_amplitudes_in(amplitudes, ordering)
+    }
+}
+
+/// Return the raw value of a key of the NumPy header, a Python dictionary literal
+fn npy_header_value<'a>(header: &'a str, key: &str) -> Result<&'a str, String> {
+    let start = header.find(&format!("'{}'", key)).ok_or(format!("Missing '{}' in the NumPy header", key))?;
+    let value = header[start + key.len() + 2..].trim_start().trim_start_matches(':').trim_start();
+    let end = if value.starts_with('(') {
+        value.find(')').map(|i| i + 1)
+    } else {
+        value.find(',')
+    };
+    end.map(|end| value[..end].trim()).ok_or(format!("Invalid '{}' in the NumPy header", key))
+}
+
+
+#[cfg(test)]
+mod tests_state_io {
+    use super::*;
+
+    fn assert_same(lhs: &QuantumState, rhs: &QuantumState, tolerance: f64) {
+        assert_eq!(lhs.nb_qbits(), rhs.nb_qbits());
+        assert_eq!(lhs.ordering(), rhs.ordering());
+        for (a, b) in lhs.iter().zip(rhs.iter()) {
+            assert!((a - b).norm() < tolerance);
+        }
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let state = QuantumState::random(3);
+        let json = state.to_json().unwrap();
+        assert_same(&QuantumState::from_json(&json).unwrap(), &state, 1e-12);
+        assert!(QuantumState::from_json("{\"nb_qbits\":2,\"amplitudes\":[[1.0,0.0],[0.0,0.0]]}").is_err());
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let state = QuantumState::random(4);
+        for (precision, tolerance) in [(Precision::Double, 1e-15), (Precision::Single, 1e-6)] {
+            let mut buffer = vec![];
+            state.write_binary(&mut buffer, precision).unwrap();
+            assert_eq!(buffer.len(), 11 + 16 * 2 * precision.nb_bytes() as usize);
+            assert_same(&QuantumState::read_binary(&mut buffer.as_slice()).unwrap(), &state, tolerance);
+        }
+        assert!(QuantumState::read_binary(&mut b"QSTATE\x02".as_slice()).is_err());
+    }
+
+    #[test]
+    fn corrupted_sizes_are_rejected() {
+        let mut buffer = vec![];
+        QuantumState::random(2).write_binary(&mut buffer, Precision::Double).unwrap();
+        let mut too_many_qbits = buffer.clone();
+        too_many_qbits[9] = 60;
+        assert!(QuantumState::read_binary(&mut too_many_qbits.as_slice()).is_err());
+        let mut truncated = buffer.clone();
+        truncated[9] = 30;
+        assert!(QuantumState::read_binary(&mut truncated.as_slice()).err().unwrap().contains("expected"));
+
+        for shape in ["(3,)", "(1099511627776,)", "(18446744073709551615,)", "(0,)"] {
+            let header = format!("{{'descr': '<c16', 'fortran_order': False, 'shape': {}, }}", shape);
+            let mut npy = b"\x93NUMPY\x01\x00".to_vec();
+            npy.extend_from_slice(&(header.len() as u16).to_le_bytes());
+            npy.extend_from_slice(header.as_bytes());
+            npy.extend_from_slice(&[0; 64]);
+            assert!(QuantumState::read_npy(&mut npy.as_slice()).is_err());
+        }
+    }
+
+    #[test]
+    fn npy_round_trip() {
+        let state = QuantumState::random(3);
+        let mut buffer = vec![];
+        state.write_npy(&mut buffer).unwrap();
+
+        assert_eq!(&buffer[..8], b"\x93NUMPY\x01\x00");
+        let header_len = u16::from_le_bytes([buffer[8], buffer[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        assert_eq!(buffer.len(), 10 + header_len + 8 * 16);
+        assert_same(&QuantumState::read_npy(&mut buffer.as_slice()).unwrap(), &state, 1e-15);
+    }
+
+    #[test]
+    fn ordering_is_kept_or_converted() {
+        let state = QuantumState::random(3).to_ordering(QbitOrdering::LsbFirst);
+        assert_same(&QuantumState::from_json(&state.to_json().unwrap()).unwrap(), &state, 1e-12);
+
+        let mut buffer = vec![];
+        state.write_binary(&mut buffer, Precision::Double).unwrap();
+        assert_same(&QuantumState::read_binary(&mut buffer.as_slice()).unwrap(), &state, 1e-15);
+
+        let mut buffer = vec![];
+        state.write_npy(&mut buffer).unwrap();
+        let read = QuantumState::read_npy(&mut buffer.as_slice()).unwrap();
+        assert_same(&read.to_ordering(QbitOrdering::LsbFirst), &state, 1e-15);
+
+        let mut buffer = vec![];
+        state.write_npy_in(&mut buffer, QbitOrdering::LsbFirst).unwrap();
+        assert_same(&QuantumState::read_npy_in(&mut buffer.as_slice(), QbitOrdering::LsbFirst).unwrap(), &state, 1e-15);
+    }
+
+    #[test]
+    fn read_npy_written_by_numpy() {
+        // np.save of np.array([0.6, 0.8j], dtype=np.complex64)
+        let header = "{'descr': '<c8', 'fortran_order': False, 'shape': (2,), }";
+        let mut file = b"\x93NUMPY\x01\x00".to_vec();
+        file.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        file.extend_from_slice(header.as_bytes());
+        for value in [0.6f32, 0.0, 0.0, 0.8] {
+            file.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let state = QuantumState::read_npy(&mut file.as_slice()).unwrap();
+        assert_eq!(state.nb_qbits(), 1);
+        assert!((state[1] - Complex64::new(0.0, 0.8)).norm() < 1e-6);
+    }
+}
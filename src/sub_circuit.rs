@@ -0,0 +1,414 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::_loop::Loop;
+use crate::backend::Backend;
+use crate::circuit::Circuit;
+use crate::compose::map_element;
+use crate::execution::{ExecutionContext, ExecutionError};
+use crate::gate::check_for_no_duplicate;
+use crate::operation::{CircuitElement, QuantumOperation};
+
+/// Call of a sub-circuit stored once in the [`definitions`](Circuit::definitions) of the circuit.
+/// The qbit `i` of the sub-circuit is the qbit `qbits[i]` of the circuit. When the call is
+/// controlled, each gate of the sub-circuit is controlled by the control qbits.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SubCircuit {
+    /// name of the definition
+    pub name: String,
+    /// the qbits the sub-circuit is applied on
+    pub qbits: Vec<u8>,
+    /// the control qbits, empty for a call that is not controlled
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub control_bits: Vec<u8>,
+}
+
+impl SubCircuit {
+    pub fn new(name: &str, qbits: &[u8]) -> Self {
+        SubCircuit { name: name.to_string(), qbits: qbits.to_vec(), control_bits: vec![] }
+    }
+
+    /// Return the same call controlled by the given qbits too. Only the sub-circuits
+    /// made of gates and sub-circuits can be controlled.
+    pub fn controlled(mut self, control_bits: &[u8]) -> Self {
+        self.control_bits.extend_from_slice(control_bits);
+        self
+    }
+
+    /// Return the elements of the definition moved on the qbits of the call
+    /// of a circuit with `nb_qbits` qbits
+    pub(crate) fn instantiate(&self, definition: &Circuit, nb_qbits: u8) -> Result<Vec<CircuitElement>, String> {
+        self.check_arity(definition)?;
+        definition.elements.iter()
+            .map(|element| match (map_element(element, &self.qbits, nb_qbits), self.control_bits.is_empty()) {
+                (element, true) => Ok(element),
+                (CircuitElement::Gate(gate), false) => Ok(CircuitElement::Gate(gate.controlled_by(&self.control_bits))),
+                (CircuitElement::SubCircuit(call), false) => Ok(CircuitElement::SubCircuit(call.controlled(&self.control_bits))),
+                (_, false) => Err(format!("The sub-circuit {} cannot be controlled, it contains measurements, postselections or loops", self.name)),
+            })
+            .collect()
+    }
+
+    fn check_arity(&self, definition: &Circuit) -> Result<(), String> {
+        if definition.nb_qbits as usize != self.qbits.len() {
+            return Err(format!("The sub-circuit {} has {} qbits, it is called on {}", self.name, definition.nb_qbits, self.qbits.len()));
+        }
+        Ok(())
+    }
+}
+
+impl Into<CircuitElement> for SubCircuit {
+    fn into(self) -> CircuitElement {
+        CircuitElement::SubCircuit(self)
+    }
+}
+
+impl QuantumOperation for SubCircuit {
+    /// Execute the elements of the definition, their path starting with the path of the call
+    fn apply<B: Backend>(&self, context: &mut ExecutionContext<B>) -> Result<(), ExecutionError> {
+        context.sub_circuit_instance(self)?.apply(context)
+    }
+
+    fn max_qbit_idx(&self) -> u8 {
+        self.qbits.iter().chain(self.control_bits.iter()).max().cloned().unwrap_or(0)
+    }
+
+    fn check_validity(&self, nb_qbits: u8) -> Result<(), String> {
+        let qbits: Vec<u8> = self.qbits.iter().chain(self.control_bits.iter()).cloned().collect();
+        for qbit in qbits.iter() {
+            if *qbit >= nb_qbits {
+                return Err(format!("Index to high {}", qbit));
+            }
+        }
+        check_for_no_duplicate(qbits)
+    }
+}
+
+impl Circuit {
+    /// Return the same circuit where each call of a sub-circuit is replaced by the elements
+    /// of its definition, like the circuit would be without sub-circuits
+    pub fn flatten(&self) -> Result<Circuit, String> {
+        let elements = flatten_elements(&self.elements, &self.definitions, self.nb_qbits, &mut vec![])?;
+        Ok(Circuit { nb_qbits: self.nb_qbits, elements, definitions: BTreeMap::new() })
+    }
+
+    /// Check that each call refers to a definition with the same number of qbits,
+    /// that the definitions are valid and that no sub-circuit calls itself.
+    /// Each definition is checked once whatever the number of calls.
+    pub(crate) fn check_sub_circuits(&self) -> Result<(), String> {
+        for (name, definition) in self.definitions.iter() {
+            if !definition.definitions.is_empty() {
+                return Err(format!("The sub-circuit {} must not have definitions, they belong to the enclosing circuit", name));
+            }
+            definition.check_elements_validity(definition.nb_qbits)?;
+        }
+        let mut controllable = BTreeMap::new();
+        for name in self.definitions.keys() {
+            check_definition(name, &self.definitions, &mut controllable, &mut vec![])?;
+        }
+        check_calls(&self.elements, &self.definitions, &mut controllable, &mut vec![]).map(|_| ())
+    }
+}
+
+/// Check the calls of the definition and return true if it can be controlled. `controllable`
+/// keeps the result for the definitions already checked, `called` holds the names of the
+/// definitions being checked, to detect the recursive calls.
+fn check_definition(name: &str, definitions: &BTreeMap<String, Circuit>, controllable: &mut BTreeMap<String, bool>, called: &mut Vec<String>) -> Result<bool, String> {
+    if let Some(result) = controllable.get(name) {
+        return Ok(*result);
+    }
+    if called.iter().any(|n| n == name) {
+        return Err(format!("The sub-circuit {} calls itself", name));
+    }
+    let definition = definitions.get(name).ok_or_else(|| format!("Unknown sub-circuit {}", name))?;
+    called.push(name.to_string());
+    let result = check_calls(&definition.elements, definitions, controllable, called)?;
+    called.pop();
+    controllable.insert(name.to_string(), result);
+    Ok(result)
+}
+
+/// Check the calls of the elements and return true if the elements can be controlled:
+/// they are gates and calls of sub-circuits that can be controlled
+fn check_calls(elements: &[CircuitElement], definitions: &BTreeMap<String, Circuit>, controllable: &mut BTreeMap<String, bool>, called: &mut Vec<String>) -> Result<bool, String> {
+    let mut result = true;
+    for element in elements {
+        match element {
+            CircuitElement::Gate(_) => {}
+            CircuitElement::SubCircuit(call) => {
+                let definition = definitions.get(&call.name).ok_or_else(|| format!("Unknown sub-circuit {}", call.name))?;
+                call.check_arity(definition)?;
+                let can_be_controlled = check_definition(&call.name, definitions, controllable, called)?;
+                if !can_be_controlled && !call.control_bits.is_empty() {
+                    return Err(format!("The sub-circuit {} cannot be controlled, it contains measurements, postselections or loops", call.name));
+                }
+                result &= can_be_controlled;
+            }
+            CircuitElement::Loop(l) => {
+                check_calls(&l.circuit.elements, definitions, controllable, called)?;
+                result = false;
+            }
+            _ => result = false,
+        }
+    }
+    Ok(result)
+}
+
+/// `called` holds the names of the sub-circuits being expanded, to detect the recursive calls
+fn flatten_elements(elements: &[CircuitElement], definitions: &BTreeMap<String, Circuit>, nb_qbits: u8, called: &mut Vec<String>) -> Result<Vec<CircuitElement>, String> {
+    let mut result = Vec::with_capacity(elements.len());
+    for element in elements {
+        match element {
+            CircuitElement::SubCircuit(call) => {
+                if called.contains(&call.name) {
+                    return Err(format!("The sub-circuit {} calls itself", call.name));
+                }
+                let definition = definitions.get(&call.name).ok_or_else(|| format!("Unknown sub-circuit {}", call.name))?;
+                let instance = call.instantiate(definition, nb_qbits)?;
+                called.push(call.name.clone());
+                result.extend(flatten_elements(&instance, definitions, nb_qbits, called)?);
+                called.pop();
+            }
+            CircuitElement::Loop(l) => {
+                let elements = flatten_elements(&l.circuit.elements, definitions, nb_qbits, called)?;
+                let circuit = Circuit { nb_qbits: l.circuit.nb_qbits, elements, definitions: BTreeMap::new() };
+                result.push(CircuitElement::Loop(Loop { circuit, stop_condition: l.stop_condition.clone(), max_iterations: l.max_iterations }));
+            }
+            other => result.push(other.clone()),
+        }
+    }
+    Ok(result)
+}
+
+/// Add the definitions to `target`. Fails if a name is already used by a different sub-circuit.
+pub(crate) fn merge_definitions(target: &mut BTreeMap<String, Circuit>, definitions: &BTreeMap<String, Circuit>) -> Result<(), String> {
+    for (name, definition) in definitions.iter() {
+        match target.get(name) {
+            None => {
+                target.insert(name.clone(), definition.clone());
+            }
+            Some(existing) if existing.to_string().ok() == definition.to_string().ok() => {}
+            Some(_) => return Err(format!("Two different sub-circuits are named {}", name)),
+        }
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests_sub_circuit {
+    use std::collections::HashMap;
+
+    use crate::backend::Backend;
+    use crate::base_gate::BaseGate;
+    use crate::common_gate::CommonGate::{CNot, Hadamard, Not};
+    use crate::computer::QuantumComputer;
+    use crate::condition::StopCondition;
+    use crate::debugger::{ElementPath, StepKind};
+    use crate::hamiltonian::Hamiltonian;
+    use crate::state::QuantumState;
+
+    use super::*;
+
+    fn bell_pair() -> Circuit {
+        QuantumComputer::new(2).bell_state().build().unwrap()
+    }
+
+    fn assert_same_state(lhs: &QuantumState, rhs: &QuantumState) {
+        assert!((lhs.inner_product(rhs).unwrap().norm() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn calls_are_executed_on_their_qbits() {
+        let computer = QuantumComputer::new(4);
+        let circuit = computer.new_circuit_builder()
+            .add_definition("bell", bell_pair())
+            .add_sub_circuit("bell", &[3, 0])
+            .add_sub_circuit("bell", &[1, 2])
+            .build()
+            .unwrap();
+
+        let result = circuit.execute(&computer.zero_state()).unwrap();
+        let expected = QuantumState::from_bitstrings(&["0000", "0110", "1001", "1111"]).unwrap();
+        assert_same_state(result.current_state(), &expected);
+
+        let flat = circuit.flatten().unwrap();
+        assert_eq!(flat.elements.len(), 4);
+        assert!(flat.definitions.is_empty());
+    }
+
+    #[test]
+    fn controlled_call_controls_each_gate() {
+        let computer = QuantumComputer::new(3);
+        let circuit = computer.new_circuit_builder()
+            .add_definition("bell", bell_pair())
+            .add_operation(Hadamard(0))
+            .add_operation(SubCircuit::new("bell", &[1, 2]).controlled(&[0]))
+            .build()
+            .unwrap();
+
+        let result = circuit.execute(&computer.zero_state()).unwrap();
+        // |000>/√2 + (|100> + |111>)/2
+        let probabilities = result.current_state().probabilities();
+        assert!((probabilities[0] - 0.5).abs() < 1e-9);
+        assert!((probabilities[4] - 0.25).abs() < 1e-9);
+        assert!((probabilities[7] - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn calls_on_other_qbits_are_instantiated_again() {
+        let computer = QuantumComputer::new(3);
+        let flip = QuantumComputer::new(1).new_circuit_builder().add_operation(Not(0)).build().unwrap();
+        let circuit = computer.new_circuit_builder()
+            .add_definition("flip", flip)
+            .add_sub_circuit("flip", &[0])
+            .add_operation(SubCircuit::new("flip", &[1]).controlled(&[0]))
+            .add_sub_circuit("flip", &[1])
+            .add_operation(SubCircuit::new("flip", &[2]).controlled(&[1]))
+            .add_sub_circuit("flip", &[2])
+            .build()
+            .unwrap();
+
+        let result = circuit.execute(&computer.zero_state()).unwrap();
+        assert!((result.current_state().probabilities()[0b101] - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn invalid_calls_are_rejected() {
+        let computer = QuantumComputer::new(3);
+        let measured = QuantumComputer::new(1).new_circuit_builder().add_measure("m", 0).build().unwrap();
+        let builder = || {
+            let mut builder = computer.new_circuit_builder();
+            builder.add_definition("bell", bell_pair()).add_definition("measured", measured.clone());
+            builder
+        };
+
+        assert!(builder().add_sub_circuit("unknown", &[0, 1]).build().is_err());
+        assert!(builder().add_sub_circuit("bell", &[0, 1, 2]).build().is_err());
+        assert!(builder().add_sub_circuit("bell", &[0, 3]).build().is_err());
+        assert!(builder().add_operation(SubCircuit::new("bell", &[0, 1]).controlled(&[1])).build().is_err());
+        assert!(builder().add_operation(SubCircuit::new("measured", &[0]).controlled(&[1])).build().is_err());
+        assert!(builder().add_operation(SubCircuit::new("measured", &[0])).build().is_ok());
+        assert!(builder().add_definition("bell", measured.clone()).build().is_err());
+
+        let calls_measured = QuantumComputer::new(1).new_circuit_builder()
+            .add_definition("measured", measured.clone())
+            .add_sub_circuit("measured", &[0])
+            .build()
+            .unwrap();
+        assert!(builder().add_definition("outer", calls_measured.clone()).add_operation(SubCircuit::new("outer", &[0]).controlled(&[1])).build().is_err());
+        assert!(builder().add_definition("outer", calls_measured).add_sub_circuit("outer", &[0]).build().is_ok());
+
+        let recursive = Circuit { nb_qbits: 2, elements: vec![SubCircuit::new("loop", &[1, 0]).into()], definitions: BTreeMap::new() };
+        assert!(builder().add_definition("loop", recursive.clone()).build().is_err());
+        assert!(builder().add_definition("loop", recursive).add_sub_circuit("loop", &[0, 1]).build().is_err());
+    }
+
+    #[test]
+    fn nested_definitions_are_hoisted() {
+        let computer = QuantumComputer::new(4);
+        let two_pairs = computer.new_circuit_builder()
+            .add_definition("bell", bell_pair())
+            .add_sub_circuit("bell", &[0, 1])
+            .add_sub_circuit("bell", &[2, 3])
+            .build()
+            .unwrap();
+        let body = computer.new_circuit_builder()
+            .add_definition("two_pairs", two_pairs)
+            .add_sub_circuit("two_pairs", &[0, 1, 2, 3])
+            .add_measure("m", 0)
+            .build()
+            .unwrap();
+        let circuit = computer.new_circuit_builder()
+            .add_loop(body, StopCondition::MaxIteration(2))
+            .build()
+            .unwrap();
+        assert_eq!(circuit.definitions.keys().cloned().collect::<Vec<String>>(), vec!["bell", "two_pairs"]);
+        assert!(circuit.definitions["two_pairs"].definitions.is_empty());
+
+        let result = circuit.execute(&computer.zero_state()).unwrap();
+        assert_eq!(result.history().len(), 2);
+        assert_eq!(result.history()[0].path, ElementPath::new(&[0, 1]));
+    }
+
+    #[test]
+    fn definitions_are_serialized_once() {
+        let computer = QuantumComputer::new(2);
+        let mut builder = computer.new_circuit_builder();
+        builder.add_definition("bell", bell_pair());
+        for _ in 0..10 {
+            builder.add_sub_circuit("bell", &[1, 0]);
+        }
+        let circuit = builder.build().unwrap();
+        let json = circuit.to_string().unwrap();
+        assert_eq!(json.matches("Hadamard").count(), 1);
+        assert_eq!(json.matches(r#"{"SubCircuit":{"name":"bell","qbits":[1,0]}}"#).count(), 10);
+
+        let loaded = Circuit::from_string(&json).unwrap();
+        let state = loaded.execute(&computer.zero_state()).unwrap();
+        let expected = circuit.flatten().unwrap().execute(&computer.zero_state()).unwrap();
+        assert_same_state(state.current_state(), expected.current_state());
+    }
+
+    #[test]
+    fn composition_keeps_the_definitions() {
+        let computer = QuantumComputer::new(2);
+        let pair = computer.new_circuit_builder()
+            .add_definition("bell", bell_pair())
+            .add_sub_circuit("bell", &[0, 1])
+            .build()
+            .unwrap();
+        let two_pairs = pair.tensor(&pair).unwrap();
+        assert_eq!(two_pairs.definitions.len(), 1);
+        let result = two_pairs.execute_stabilizer().unwrap();
+        let expected = two_pairs.flatten().unwrap().execute_stabilizer().unwrap();
+        assert_eq!(result.current_state().probabilities(), expected.current_state().probabilities());
+
+        let other = computer.new_circuit_builder()
+            .add_definition("bell", computer.new_circuit_builder().add_operation(Not(0)).build().unwrap())
+            .add_sub_circuit("bell", &[0, 1])
+            .build()
+            .unwrap();
+        assert!(pair.append(&other).is_err());
+    }
+
+    #[test]
+    fn parameters_of_the_definitions_are_bound() {
+        let computer = QuantumComputer::new(2);
+        let layer = computer.new_circuit_builder()
+            .add_operation(BaseGate::Ry(0.0, 0).with_parameter("θ").unwrap())
+            .add_operation(CNot(1, [0]))
+            .build()
+            .unwrap();
+        let ansatz = computer.new_circuit_builder()
+            .add_definition("layer", layer)
+            .add_sub_circuit("layer", &[0, 1])
+            .add_sub_circuit("layer", &[1, 0])
+            .build()
+            .unwrap();
+        assert_eq!(ansatz.parameters(), vec!["θ"]);
+
+        let hamiltonian = Hamiltonian::parse(&[(1.0, "ZZ"), (0.5, "XI")]).unwrap();
+        let values: HashMap<String, f64> = vec![("θ".to_string(), 0.7)].into_iter().collect();
+        let zero = computer.zero_state();
+        let gradient = ansatz.adjoint_gradient(&hamiltonian, &values, &zero).unwrap();
+        let expected = ansatz.flatten().unwrap().adjoint_gradient(&hamiltonian, &values, &zero).unwrap();
+        assert!((gradient["θ"] - expected["θ"]).abs() < 1e-12);
+        assert!((ansatz.expectation(&hamiltonian, &values, &zero).unwrap()
+            - ansatz.flatten().unwrap().expectation(&hamiltonian, &values, &zero).unwrap()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn debugger_steps_over_calls() {
+        let computer = QuantumComputer::new(2);
+        let circuit = computer.new_circuit_builder()
+            .add_definition("bell", bell_pair())
+            .add_sub_circuit("bell", &[0, 1])
+            .add_operation(Not(0))
+            .build()
+            .unwrap();
+        let steps: Vec<StepKind> = circuit.debug(&computer.zero_state()).map(|s| s.unwrap().kind).collect();
+        assert_eq!(steps, vec![StepKind::SubCircuit { name: "bell".to_string() }, StepKind::Gate]);
+    }
+}
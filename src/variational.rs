@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::f64::consts::FRAC_PI_2;
+
+use crate::base_gate::BaseGate;
+use crate::circuit::Circuit;
+use crate::common_gate::CommonGate::{CNot, Hadamard};
+use crate::computer::QuantumComputer;
+use crate::gate::Gate;
+use crate::hamiltonian::Hamiltonian;
+use crate::optimizer::Optimizer;
+use crate::pauli::Pauli;
+use crate::state::QuantumState;
+
+/// Starting value of the parameters that are not given explicitly. Zero is avoided
+/// because many ansatzes have a stationary point there.
+const DEFAULT_INITIAL_VALUE: f64 = 0.1;
+
+/// Result of a variational algorithm
+#[derive(Clone, Debug)]
+pub struct VariationalResult {
+    /// the lowest expectation value found
+    pub energy: f64,
+    /// the parameters giving this expectation value
+    pub parameters: HashMap<String, f64>,
+    /// the best expectation value at the start of each iteration of the optimizer
+    pub history: Vec<f64>,
+    /// the number of times the expectation value has been computed
+    pub nb_evaluations: usize,
+}
+
+/// Variational quantum eigensolver: search the parameters of the ansatz that minimize
+/// the expectation value of the Hamiltonian on the state it prepares
+pub struct Vqe {
+    ansatz: Circuit,
+    hamiltonian: Hamiltonian,
+    optimizer: Optimizer,
+    initial_parameters: HashMap<String, f64>,
+}
+
+impl Vqe {
+    /// The ansatz is a parameterized circuit, or the builder of one
+    pub fn new(ansatz: impl Into<Circuit>, hamiltonian: Hamiltonian, optimizer: Optimizer) -> Self {
+        Vqe { ansatz: ansatz.into(), hamiltonian, optimizer, initial_parameters: HashMap::new() }
+    }
+
+    /// Set the starting values of some parameters, the other ones start at 0.1
+    pub fn with_initial_parameters(mut self, values: HashMap<String, f64>) -> Self {
+        self.initial_parameters = values;
+        self
+    }
+
+    pub fn ansatz(&self) -> &Circuit {
+        &self.ansatz
+    }
+
+    /// Run the optimization, the ansatz being applied to the given state
+    pub fn run(&self, initial_state: &QuantumState) -> Result<VariationalResult, String> {
+        let names = self.ansatz.parameters();
+        let to_values = |x: &[f64]| -> HashMap<String, f64> { names.iter().cloned().zip(x.iter().cloned()).collect() };
+        let initial: Vec<f64> = names.iter()
+            .map(|name| self.initial_parameters.get(name).cloned().unwrap_or(DEFAULT_INITIAL_VALUE))
+            .collect();
+
+        let cost = |x: &[f64]| self.ansatz.expectation(&self.hamiltonian, &to_values(x), initial_state);
+        let gradient = |x: &[f64]| {
+            let gradient = self.ansatz.adjoint_gradient(&self.hamiltonian, &to_values(x), initial_state)?;
+            Ok(names.iter().map(|name| gradient[name]).collect())
+        };
+        let result = self.optimizer.minimize(cost, gradient, &initial)?;
+        Ok(VariationalResult {
+            energy: result.cost,
+            parameters: to_values(&result.parameters),
+            history: result.history,
+            nb_evaluations: result.nb_evaluations,
+        })
+    }
+}
+
+/// Quantum approximate optimization algorithm: the ansatz alternates `nb_layers` times the
+/// evolution under the cost Hamiltonian, of angle `γ_l`, and a mixer made of X rotations
+/// of angle `2*β_l`, starting from the uniform superposition
+pub struct Qaoa {
+    cost: Hamiltonian,
+    nb_layers: usize,
+    optimizer: Optimizer,
+    initial_parameters: HashMap<String, f64>,
+}
+
+impl Qaoa {
+    pub fn new(cost: Hamiltonian, nb_layers: usize, optimizer: Optimizer) -> Self {
+        Qaoa { cost, nb_layers, optimizer, initial_parameters: HashMap::new() }
+    }
+
+    /// Set the starting values of some of the `γ_l` and `β_l`, the other ones start at 0.1
+    pub fn with_initial_parameters(mut self, values: HashMap<String, f64>) -> Self {
+        self.initial_parameters = values;
+        self
+    }
+
+    /// Return the parameterized circuit of the algorithm
+    pub fn ansatz(&self, computer: &QuantumComputer) -> Result<Circuit, String> {
+        let mut builder = computer.new_circuit_builder();
+        for qbit in 0..computer.nb_qbits() {
+            builder.add_operation(Hadamard(qbit));
+        }
+        for layer in 0..self.nb_layers {
+            for (coefficient, pauli_string) in self.cost.terms.iter() {
+                let product = pauli_string.product();
+                let (last, _) = match product.last() {
+                    None => continue,
+                    Some(last) => *last,
+                };
+                let angle = format!("{}*γ_{}", 2.0 * coefficient, layer);
+                for (qbit, pauli) in product.iter() {
+                    add_change_of_basis(&mut builder, *qbit, *pauli, false);
+                }
+                for pair in product.windows(2) {
+                    builder.add_operation(CNot(pair[1].0, [pair[0].0]));
+                }
+                builder.add_operation(BaseGate::Rz(0.0, last).with_parameter(&angle)?);
+                for pair in product.windows(2).rev() {
+                    builder.add_operation(CNot(pair[1].0, [pair[0].0]));
+                }
+                for (qbit, pauli) in product.iter() {
+                    add_change_of_basis(&mut builder, *qbit, *pauli, true);
+                }
+            }
+            for qbit in 0..computer.nb_qbits() {
+                builder.add_operation(BaseGate::Rx(0.0, qbit).with_parameter(&format!("2*β_{}", layer))?);
+            }
+        }
+        builder.build()
+    }
+
+    /// Run the optimization on the qbits of the computer
+    pub fn run(&self, computer: &QuantumComputer) -> Result<VariationalResult, String> {
+        Vqe::new(self.ansatz(computer)?, self.cost.clone(), self.optimizer.clone())
+            .with_initial_parameters(self.initial_parameters.clone())
+            .run(&computer.zero_state())
+    }
+}
+
+/// Rotate the eigenbasis of the Pauli operator to the Z basis (or back)
+fn add_change_of_basis(builder: &mut crate::builder::QuantumCircuitBuilder, qbit: u8, pauli: Pauli, back: bool) {
+    match (pauli, back) {
+        (Pauli::X, _) => {
+            builder.add_operation(Hadamard(qbit));
+        }
+        (Pauli::Y, false) => {
+            builder.add_operation(Gate::new(BaseGate::Rx(FRAC_PI_2, qbit), vec![]));
+        }
+        (Pauli::Y, true) => {
+            builder.add_operation(Gate::new(BaseGate::Rx(-FRAC_PI_2, qbit), vec![]));
+        }
+        (Pauli::I, _) | (Pauli::Z, _) => {}
+    }
+}
+
+
+#[cfg(test)]
+mod tests_variational {
+    use crate::backend::Backend;
+
+    use super::*;
+
+    /// H = Z0 Z1 + 0.5 X0 + 0.5 X1
+    fn transverse_field_ising() -> Hamiltonian {
+        Hamiltonian::parse(&[(1.0, "ZZ"), (0.5, "XI"), (0.5, "IX")]).unwrap()
+    }
+
+    fn hardware_efficient_ansatz(computer: &QuantumComputer) -> Circuit {
+        computer.new_circuit_builder()
+            .add_operation(BaseGate::Ry(0.0, 0).with_parameter("a").unwrap())
+            .add_operation(BaseGate::Ry(0.0, 1).with_parameter("b").unwrap())
+            .add_operation(CNot(1, [0]))
+            .add_operation(BaseGate::Ry(0.0, 0).with_parameter("c").unwrap())
+            .add_operation(BaseGate::Ry(0.0, 1).with_parameter("d").unwrap())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn vqe_finds_the_ground_state() {
+        // in the basis (|00>+|11>)/√2, (|01>+|10>)/√2 the Hamiltonian is [[1, 1], [1, -1]]
+        let ground_energy = -(2.0f64).sqrt();
+        let computer = QuantumComputer::new(2);
+        for optimizer in [Optimizer::nelder_mead(), Optimizer::cobyla(), Optimizer::gradient_descent(0.2)] {
+            let vqe = Vqe::new(hardware_efficient_ansatz(&computer), transverse_field_ising(), optimizer.clone());
+            let result = vqe.run(&computer.zero_state()).unwrap();
+            assert!((result.energy - ground_energy).abs() < 1e-4, "{:?} {}", optimizer, result.energy);
+            assert_eq!(result.parameters.len(), 4);
+            assert!(result.history[0] > result.energy);
+        }
+    }
+
+    #[test]
+    fn qaoa_solves_max_cut() {
+        // max cut of the triangle 0-1-2 plus the edge 2-3: the cost is the number of uncut edges
+        let cost = Hamiltonian::parse(&[(0.5, "ZZII"), (0.5, "IZZI"), (0.5, "ZIZI"), (0.5, "IIZZ")]).unwrap();
+        let computer = QuantumComputer::new(4);
+        let qaoa = Qaoa::new(cost.clone(), 2, Optimizer::nelder_mead());
+        let ansatz = qaoa.ansatz(&computer).unwrap();
+        assert_eq!(ansatz.parameters(), vec!["β_0", "β_1", "γ_0", "γ_1"]);
+
+        let result = qaoa.run(&computer).unwrap();
+        let state = ansatz.bind(&result.parameters).unwrap().execute(&computer.zero_state()).unwrap();
+        let probabilities = state.current_state().probabilities();
+        let most_likely = (0..16).max_by(|a, b| probabilities[*a].total_cmp(&probabilities[*b])).unwrap();
+        let optimal_cut = QuantumState::from_bitstring(&format!("{:04b}", most_likely)).unwrap();
+        // the best cuts leave one edge of the triangle uncut: <H> = 0.5 * (1 - 3) = -1
+        assert!((cost.expectation(&optimal_cut).unwrap() + 1.0).abs() < 1e-9);
+        assert!(result.energy < -0.5);
+    }
+}